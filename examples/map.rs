@@ -6,9 +6,7 @@ fn main() {
     let mut kanji_to_readings = HashMap::new();
     kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
     kanji_to_readings.insert("怪".to_string(), vec!["け".to_string()]);
-    let mapping = furigana::map("物の怪", "もののけ", &kanji_to_readings)
-        .into_iter()
-        .max_by_key(|f| f.accuracy)
-        .unwrap();
+    let mappings = furigana::map("物の怪", "もののけ", &kanji_to_readings);
+    let mapping = mappings.best().unwrap();
     println!("{mapping}");
 }
@@ -0,0 +1,36 @@
+//! Maps arbitrary words and readings, checking that nothing panics and that every mapping is consistent with its input.
+//! Run with `cargo fuzz run map` in the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::collections::HashMap;
+
+fuzz_target!(|data: &str| {
+    // the input is split into the word and the reading at the first tab
+    let (word, reading) = data.split_once('\t').unwrap_or((data, ""));
+
+    let mut kanji_to_readings = HashMap::new();
+    kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+    kanji_to_readings.insert("日".to_string(), vec!["ひ".to_string(), "ニチ".to_string()]);
+
+    for mappings in [
+        furigana::map_naive(word, reading),
+        furigana::map(word, reading, &kanji_to_readings),
+    ] {
+        for mapping in &mappings {
+            if let Err(err) = furigana::check_invariants(mapping, word, reading) {
+                panic!("{word} ({reading}): {err}");
+            }
+        }
+    }
+    // the OCR preset repairs damaged kana, so its mappings are checked with the same options
+    let config = furigana::MapConfig::ocr();
+    for mapping in &furigana::map_with(word, reading, &kanji_to_readings, &config) {
+        if let Err(err) = furigana::check_invariants_with(mapping, word, reading, &config) {
+            panic!("{word} ({reading}) with the OCR preset: {err}");
+        }
+    }
+    furigana::is_mappable(word, reading);
+    furigana::count_mappings(word, reading);
+});
@@ -0,0 +1,99 @@
+//! Contains readings of kanji that act as prefixes and suffixes, such as 不 in 不便 and 的 in 目的.
+
+use crate::utils;
+
+/// Where a bound morpheme attaches to the kanji around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    /// Comes before another kanji, like 不 in 不便.
+    Prefix,
+    /// Comes after another kanji, like 的 in 目的. Its readings may undergo rendaku, like しゃ in 患者 (かんじゃ).
+    Suffix,
+}
+
+const AFFIXES: &[(&str, Position, &[&str])] = &[
+    ("不", Position::Prefix, &["ふ", "ぶ"]),
+    ("無", Position::Prefix, &["む", "ぶ"]),
+    ("非", Position::Prefix, &["ひ"]),
+    ("未", Position::Prefix, &["み"]),
+    ("再", Position::Prefix, &["さい"]),
+    ("超", Position::Prefix, &["ちょう"]),
+    ("的", Position::Suffix, &["てき"]),
+    ("者", Position::Suffix, &["しゃ"]),
+    ("性", Position::Suffix, &["せい"]),
+    ("化", Position::Suffix, &["か"]),
+    ("家", Position::Suffix, &["か"]),
+    ("界", Position::Suffix, &["かい"]),
+    ("式", Position::Suffix, &["しき"]),
+    ("論", Position::Suffix, &["ろん"]),
+];
+
+/// Returns the readings the kanji has as a prefix or suffix, if it is one between the given characters.
+/// Prefixes have to be followed by a kanji and suffixes preceded by one.
+pub fn readings(
+    previous: Option<char>,
+    kanji: &str,
+    next: Option<char>,
+) -> Option<(Position, &'static [&'static str])> {
+    let is_kanji = |c: Option<char>| c.map_or(false, |c| utils::is_kanji(c) || c == '々');
+    AFFIXES
+        .iter()
+        .find(|(affix, position, _)| {
+            *affix == kanji
+                && match position {
+                    Position::Prefix => is_kanji(next),
+                    Position::Suffix => is_kanji(previous),
+                }
+        })
+        .map(|&(_, position, readings)| (position, readings))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::KanjiAccuracy;
+    use std::collections::HashMap;
+
+    #[test]
+    fn grades_affix_readings() {
+        let mut kanji_to_readings = HashMap::new();
+        for (kanji, reading) in [
+            ("目", "もく"),
+            ("的", "まと"),
+            ("患", "かん"),
+            ("者", "もの"),
+            ("気", "き"),
+            ("味", "み"),
+            ("中", "ちゅう"),
+        ] {
+            kanji_to_readings.insert(kanji.to_string(), vec![reading.to_string()]);
+        }
+        let accuracies = |word, reading| {
+            let mappings = crate::map(word, reading, &kanji_to_readings);
+            mappings
+                .best()
+                .unwrap()
+                .furigana
+                .iter()
+                .map(|segment| segment.kanji_accuracy())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            accuracies("目的", "もくてき")[1],
+            Some(KanjiAccuracy::Accurate)
+        );
+        assert_eq!(
+            accuracies("患者", "かんじゃ")[1],
+            Some(KanjiAccuracy::AccurateWithRendaku)
+        );
+        assert_eq!(
+            accuracies("不気味", "ぶきみ")[0],
+            Some(KanjiAccuracy::Accurate)
+        );
+        // 的 only reads てき as a suffix
+        assert_eq!(
+            accuracies("的中", "てきちゅう")[0],
+            Some(KanjiAccuracy::Inaccurate)
+        );
+    }
+}
@@ -0,0 +1,659 @@
+//! Contains functions for annotating whole texts with furigana.
+
+use crate::{
+    config::MapConfig,
+    furigana::{AccuracyWeights, Furigana, FuriganaSegment, KanjiAccuracy},
+    kana_equivalent,
+    owned::OwnedFurigana,
+    render::RenderOptions,
+};
+use std::{collections::HashMap, fmt::Write, ops::Range, thread};
+
+/// Annotates the tokens of the text with furigana using HTML ruby tags, leaving the rest of the text untouched.
+/// The tokens are (surface, reading) pairs in the order they appear in the text, such as the output of a morphological analyser.
+/// Tokens that can't be found in the text are skipped, and everything between the tokens,
+/// such as punctuation and whitespace, is copied to the output as is.
+///
+/// Tokens that don't need furigana or can't be mapped to their reading are left as plain text.
+/// Tokens with several possible mappings are annotated as a whole, as there's no way to know which mapping is correct.
+pub fn annotate_plain<I, S, R>(text: &str, readings_by_token: I) -> String
+where
+    I: IntoIterator<Item = (S, R)>,
+    S: AsRef<str>,
+    R: AsRef<str>,
+{
+    annotate_tokens(text, readings_by_token, None, &RenderOptions::default())
+}
+
+/// Works like `annotate_plain`, using the information in `kanji_to_readings` and the config to map the tokens
+/// and leaving out furigana as set by the options, such as for common words, kanji the reader is expected to know
+/// or readings below the accuracy threshold.
+/// Tokens left without any furigana are written as plain text.
+pub fn annotate_plain_with<I, S, R>(
+    text: &str,
+    readings_by_token: I,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+    config: &MapConfig,
+    options: &RenderOptions,
+) -> String
+where
+    I: IntoIterator<Item = (S, R)>,
+    S: AsRef<str>,
+    R: AsRef<str>,
+{
+    annotate_tokens(
+        text,
+        readings_by_token,
+        Some((kanji_to_readings, config)),
+        options,
+    )
+}
+
+// maps the tokens naively when there's no dictionary
+fn annotate_tokens<I, S, R>(
+    text: &str,
+    readings_by_token: I,
+    dictionary: Option<(&HashMap<String, Vec<String>>, &MapConfig)>,
+    options: &RenderOptions,
+) -> String
+where
+    I: IntoIterator<Item = (S, R)>,
+    S: AsRef<str>,
+    R: AsRef<str>,
+{
+    let mut annotated = String::with_capacity(text.len());
+    let mut rest = text;
+    for (surface, reading) in readings_by_token {
+        let (surface, reading) = (surface.as_ref(), reading.as_ref());
+        let start = match rest.find(surface) {
+            Some(start) if !surface.is_empty() => start,
+            _ => continue,
+        };
+        annotated.push_str(&rest[..start]);
+        annotate_token(&mut annotated, surface, reading, dictionary, options);
+        rest = &rest[start + surface.len()..];
+    }
+    annotated.push_str(rest);
+    annotated
+}
+
+/// A reading of a part of a text, located by character offsets instead of markup,
+/// as used by annotation tools like doccano and brat.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StandoffAnnotation {
+    /// The offset of the first character of the part in the text, counted in chars.
+    pub start: usize,
+    /// The offset after the last character of the part in the text, counted in chars.
+    pub end: usize,
+    pub reading: String,
+    pub accuracy: Option<KanjiAccuracy>,
+}
+
+impl StandoffAnnotation {
+    /// Formats the annotation as a JSON object, such as `{"start":1,"end":3,"reading":"おとな","accuracy":"irregular"}`.
+    /// The accuracy is null when unknown.
+    pub fn to_json(&self) -> String {
+        let accuracy = match self.accuracy {
+            Some(accuracy) => format!("\"{}\"", accuracy.key()),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"start\":{},\"end\":{},\"reading\":\"{}\",\"accuracy\":{}}}",
+            self.start,
+            self.end,
+            escape_json(&self.reading),
+            accuracy
+        )
+    }
+}
+
+/// Formats the annotations as a JSON array of the objects described in `StandoffAnnotation::to_json`.
+pub fn standoff_to_json(annotations: &[StandoffAnnotation]) -> String {
+    let objects = annotations
+        .iter()
+        .map(StandoffAnnotation::to_json)
+        .collect::<Vec<_>>();
+    format!("[{}]", objects.join(","))
+}
+
+/// Works like `annotate_plain_with`, returning the furigana as annotations of the parts of the text
+/// instead of marking up the text, in the order they appear in the text.
+pub fn annotate_standoff<I, S, R>(
+    text: &str,
+    readings_by_token: I,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+    config: &MapConfig,
+    options: &RenderOptions,
+) -> Vec<StandoffAnnotation>
+where
+    I: IntoIterator<Item = (S, R)>,
+    S: AsRef<str>,
+    R: AsRef<str>,
+{
+    let dictionary = Some((kanji_to_readings, config));
+    let mut annotations = vec![];
+    let mut rest = text;
+    // the offset of rest in the text in chars
+    let mut offset = 0;
+    for (surface, reading) in readings_by_token {
+        let (surface, reading) = (surface.as_ref(), reading.as_ref());
+        let start = match rest.find(surface) {
+            Some(start) if !surface.is_empty() => start,
+            _ => continue,
+        };
+        offset += rest[..start].chars().count();
+        let mut segment_start = offset;
+        for (segment, furigana) in token_furigana(surface, reading, dictionary, options) {
+            let segment_end = segment_start + segment.segment.chars().count();
+            if let Some(furigana) = furigana {
+                annotations.push(StandoffAnnotation {
+                    start: segment_start,
+                    end: segment_end,
+                    reading: furigana.to_string(),
+                    accuracy: segment.kanji_accuracy,
+                });
+            }
+            segment_start = segment_end;
+        }
+        offset += surface.chars().count();
+        rest = &rest[start + surface.len()..];
+    }
+    annotations
+}
+
+fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                // writing to a string can't fail
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Maps the reading to a word that already has furigana for some of its parts, given as HTML ruby tags
+/// like `<ruby>物<rt>もの</rt></ruby>の怪`. Works like `map_with` for the word without the tags,
+/// keeping only the mappings that agree with the existing furigana, which narrows down the rest of the word.
+/// `<rp>` tags are ignored.
+pub fn map_partially_annotated(
+    annotated: &str,
+    reading: &str,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+    config: &MapConfig,
+) -> Vec<OwnedFurigana> {
+    let (word, fixed) = parse_ruby(annotated);
+    crate::map_with(&word, reading, kanji_to_readings, config)
+        .iter()
+        .filter(|mapping| agrees_with(mapping, &fixed))
+        .map(OwnedFurigana::from)
+        .collect()
+}
+
+// splits the annotated text into the plain word and the furigana in it, located by their byte ranges in the word
+fn parse_ruby(annotated: &str) -> (String, Vec<(Range<usize>, &str)>) {
+    let mut word = String::new();
+    let mut fixed = vec![];
+    let mut rest = annotated;
+    while let Some(start) = rest.find("<ruby>") {
+        word.push_str(&rest[..start]);
+        rest = &rest[start + "<ruby>".len()..];
+        let end = rest.find("</ruby>").unwrap_or(rest.len());
+        let mut ruby = &rest[..end];
+        rest = rest.get(end + "</ruby>".len()..).unwrap_or_default();
+
+        // the ruby alternates between the base text and its furigana
+        while !ruby.is_empty() {
+            let base_end = ruby.find("<rt>").unwrap_or(ruby.len());
+            let base_start = word.len();
+            push_without_rp(&mut word, &ruby[..base_end]);
+            ruby = &ruby[base_end..];
+            if let Some(after_rt) = ruby.strip_prefix("<rt>") {
+                let rt_end = after_rt.find("</rt>").unwrap_or(after_rt.len());
+                let rt = &after_rt[..rt_end];
+                if !rt.is_empty() && word.len() > base_start {
+                    fixed.push((base_start..word.len(), rt));
+                }
+                ruby = after_rt.get(rt_end + "</rt>".len()..).unwrap_or_default();
+            }
+        }
+    }
+    word.push_str(rest);
+    (word, fixed)
+}
+
+// the parentheses in <rp> are only shown by browsers that don't support ruby
+fn push_without_rp(word: &mut String, mut base: &str) {
+    while let Some(start) = base.find("<rp>") {
+        word.push_str(&base[..start]);
+        base = base[start..]
+            .find("</rp>")
+            .map_or("", |end| &base[start + end + "</rp>".len()..]);
+    }
+    word.push_str(base);
+}
+
+// checks that the segments of the mapping line up with each fixed part of the word and are read the same way
+fn agrees_with(mapping: &Furigana, fixed: &[(Range<usize>, &str)]) -> bool {
+    let mut start = 0;
+    let segments = mapping
+        .furigana
+        .iter()
+        .map(|segment| {
+            let range = start..start + segment.segment.len();
+            start = range.end;
+            (range, segment.reading)
+        })
+        .collect::<Vec<_>>();
+    fixed.iter().all(|(span, fixed_reading)| {
+        let inside = segments
+            .iter()
+            .filter(|(range, _)| range.start >= span.start && range.end <= span.end)
+            .collect::<Vec<_>>();
+        let lines_up = inside
+            .first()
+            .map_or(false, |(range, _)| range.start == span.start)
+            && inside
+                .last()
+                .map_or(false, |(range, _)| range.end == span.end);
+        let reading = inside
+            .iter()
+            .map(|(_, reading)| *reading)
+            .collect::<String>();
+        lines_up && kana_equivalent(&reading, fixed_reading)
+    })
+}
+
+fn annotate_token(
+    annotated: &mut String,
+    surface: &str,
+    reading: &str,
+    dictionary: Option<(&HashMap<String, Vec<String>>, &MapConfig)>,
+    options: &RenderOptions,
+) {
+    let furigana = token_furigana(surface, reading, dictionary, options);
+    if furigana.is_empty() {
+        annotated.push_str(surface);
+        return;
+    }
+    annotated.push_str("<ruby>");
+    for (segment, furigana) in furigana {
+        // writing to a string can't fail
+        let _ = write!(
+            annotated,
+            "{}<rt>{}</rt>",
+            segment.segment,
+            furigana.unwrap_or_default()
+        );
+    }
+    annotated.push_str("</ruby>");
+}
+
+// the segments of the token with the furigana to show for each, or nothing if the token is left as plain text
+fn token_furigana<'a>(
+    surface: &'a str,
+    reading: &'a str,
+    dictionary: Option<(&HashMap<String, Vec<String>>, &MapConfig)>,
+    options: &RenderOptions,
+) -> Vec<(FuriganaSegment<'a>, Option<&'a str>)> {
+    let mappings = match dictionary {
+        Some((kanji_to_readings, config)) => {
+            crate::map_with(surface, reading, kanji_to_readings, config)
+        }
+        None => crate::map_naive(surface, reading),
+    };
+    let segments = match &mappings[..] {
+        [mapping, ..] if options.skips_word(mapping) => return vec![],
+        [] => return vec![],
+        [mapping] => mapping.furigana.to_vec(),
+        // the token is annotated as a whole, as there's no way to know which mapping is correct
+        [_, ..] => vec![FuriganaSegment::new(surface, Some(reading), reading, None)],
+    };
+    let furigana = segments
+        .into_iter()
+        .map(|segment| (segment, options.furigana(&segment)))
+        .collect::<Vec<_>>();
+    if furigana.iter().all(|(_, furigana)| furigana.is_none()) {
+        return vec![];
+    }
+    furigana
+}
+
+/// Maps each (word, reading) pair in the corpus using `map`, returning the best mapping for each pair in input order,
+/// or None for pairs that can't be mapped.
+/// Identical pairs are only mapped once, which saves a lot of work on corpora like JMdict where pairs repeat heavily.
+pub fn annotate_corpus<I, W, R>(
+    entries: I,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+) -> Vec<Option<OwnedFurigana>>
+where
+    I: IntoIterator<Item = (W, R)>,
+    W: AsRef<str>,
+    R: AsRef<str>,
+{
+    annotate_corpus_parallel(entries, kanji_to_readings, 1)
+}
+
+/// Works like `annotate_corpus`, splitting the unique pairs between the given number of threads.
+pub fn annotate_corpus_parallel<I, W, R>(
+    entries: I,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+    threads: usize,
+) -> Vec<Option<OwnedFurigana>>
+where
+    I: IntoIterator<Item = (W, R)>,
+    W: AsRef<str>,
+    R: AsRef<str>,
+{
+    annotate_corpus_report(entries, kanji_to_readings, threads)
+        .into_iter()
+        .map(AnnotationReport::into_furigana)
+        .collect()
+}
+
+/// The result of annotating a pair in a corpus.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AnnotationReport {
+    /// The pair was mapped with readings backed by the reading information.
+    Ok(OwnedFurigana),
+    /// The pair was mapped, but the mapping may well be wrong.
+    LowConfidence(OwnedFurigana, LowConfidence),
+    /// The pair couldn't be mapped.
+    Failed(Failure),
+}
+
+/// Why a mapping may be wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LowConfidence {
+    /// Other mappings with different furigana were just as accurate, so the best one was picked arbitrarily.
+    Ambiguous { alternatives: usize },
+    /// Some of the furigana isn't backed by the reading information.
+    UnbackedReadings,
+}
+
+/// Why a pair couldn't be mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Failure {
+    /// The word or the reading was empty.
+    EmptyInput,
+    /// No way to map the reading to the word was found, usually because the kana in them differ.
+    NoMapping,
+}
+
+impl AnnotationReport {
+    /// Returns the mapping regardless of the confidence in it.
+    pub fn furigana(&self) -> Option<&OwnedFurigana> {
+        match self {
+            Self::Ok(furigana) | Self::LowConfidence(furigana, _) => Some(furigana),
+            Self::Failed(_) => None,
+        }
+    }
+
+    pub fn into_furigana(self) -> Option<OwnedFurigana> {
+        match self {
+            Self::Ok(furigana) | Self::LowConfidence(furigana, _) => Some(furigana),
+            Self::Failed(_) => None,
+        }
+    }
+}
+
+/// Works like `annotate_corpus_parallel`, reporting how confident the mapping of each pair is or why it failed.
+pub fn annotate_corpus_report<I, W, R>(
+    entries: I,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+    threads: usize,
+) -> Vec<AnnotationReport>
+where
+    I: IntoIterator<Item = (W, R)>,
+    W: AsRef<str>,
+    R: AsRef<str>,
+{
+    let entries = entries.into_iter().collect::<Vec<_>>();
+
+    // the index of each entry's pair in unique_pairs
+    let mut pair_indices = HashMap::new();
+    let mut unique_pairs = vec![];
+    let entry_indices = entries
+        .iter()
+        .map(|(word, reading)| {
+            let pair = (word.as_ref(), reading.as_ref());
+            *pair_indices.entry(pair).or_insert_with(|| {
+                unique_pairs.push(pair);
+                unique_pairs.len() - 1
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let annotated = if threads > 1 && unique_pairs.len() > 1 {
+        let chunk_size = (unique_pairs.len() + threads - 1) / threads;
+        thread::scope(|scope| {
+            let handles = unique_pairs
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || annotate_pairs(chunk, kanji_to_readings)))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("mapping panicked"))
+                .collect::<Vec<_>>()
+        })
+    } else {
+        annotate_pairs(&unique_pairs, kanji_to_readings)
+    };
+
+    entry_indices
+        .into_iter()
+        .map(|idx| annotated[idx].clone())
+        .collect()
+}
+
+fn annotate_pairs<W: AsRef<str>, R: AsRef<str>>(
+    pairs: &[(W, R)],
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+) -> Vec<AnnotationReport> {
+    pairs
+        .iter()
+        .map(|(word, reading)| annotate_pair(word.as_ref(), reading.as_ref(), kanji_to_readings))
+        .collect()
+}
+
+pub(crate) fn annotate_pair(
+    word: &str,
+    reading: &str,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+) -> AnnotationReport {
+    if word.is_empty() || reading.is_empty() {
+        return AnnotationReport::Failed(Failure::EmptyInput);
+    }
+    let mappings = crate::map(word, reading, kanji_to_readings)
+        .filter_accurate()
+        .dedup_kana_equivalent();
+    let best = match mappings.best() {
+        Some(best) => OwnedFurigana::from(best),
+        None => return AnnotationReport::Failed(Failure::NoMapping),
+    };
+    if mappings.len() > 1 {
+        let alternatives = mappings.len() - 1;
+        return AnnotationReport::LowConfidence(best, LowConfidence::Ambiguous { alternatives });
+    }
+    let weights = AccuracyWeights::default();
+    let unbacked = best
+        .furigana
+        .iter()
+        .any(|segment| segment.furigana.is_some() && weights.weight(segment.kanji_accuracy) < 1);
+    if unbacked {
+        AnnotationReport::LowConfidence(best, LowConfidence::UnbackedReadings)
+    } else {
+        AnnotationReport::Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn annotates_text() {
+        let text = "「大人」と、ビール。 食べる！";
+        let tokens = [
+            ("大人", "おとな"),
+            ("と", "と"),
+            ("ビール", "びーる"),
+            ("見る", "みる"),
+            ("食べる", "たべる"),
+        ];
+        assert_eq!(
+            annotate_plain(text, tokens),
+            "「<ruby>大人<rt>おとな</rt></ruby>」と、ビール。 <ruby>食<rt>た</rt>べる<rt></rt></ruby>！"
+        );
+        assert_eq!(annotate_plain(text, Vec::<(&str, &str)>::new()), text);
+
+        let list = crate::FrequencyList::parse("大人\n");
+        let known = ['食'].into_iter().collect();
+        let options = RenderOptions::new()
+            .skip_frequent(&list, 1)
+            .skip_known(&known);
+        assert_eq!(
+            annotate_plain_with(
+                text,
+                tokens,
+                &HashMap::new(),
+                &MapConfig::default(),
+                &options
+            ),
+            "「大人」と、ビール。 食べる！"
+        );
+    }
+
+    #[test]
+    fn annotates_standoff() {
+        let text = "「大人」と\"食べる\"";
+        let tokens = [("大人", "おとな"), ("食べる", "たべる")];
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("食".to_string(), vec!["た".to_string()]);
+        let config = MapConfig::default();
+        let annotations = annotate_standoff(
+            text,
+            tokens,
+            &kanji_to_readings,
+            &config,
+            &RenderOptions::new(),
+        );
+        assert_eq!(
+            annotations,
+            vec![
+                StandoffAnnotation {
+                    start: 1,
+                    end: 3,
+                    reading: "おとな".to_string(),
+                    accuracy: None,
+                },
+                StandoffAnnotation {
+                    start: 6,
+                    end: 7,
+                    reading: "た".to_string(),
+                    accuracy: Some(KanjiAccuracy::Accurate),
+                },
+            ]
+        );
+        assert_eq!(
+            standoff_to_json(&annotations),
+            r#"[{"start":1,"end":3,"reading":"おとな","accuracy":null},{"start":6,"end":7,"reading":"た","accuracy":"accurate"}]"#
+        );
+        let annotation = StandoffAnnotation {
+            start: 0,
+            end: 1,
+            reading: "\"".to_string(),
+            accuracy: Some(KanjiAccuracy::AccurateWithRendaku),
+        };
+        assert_eq!(
+            annotation.to_json(),
+            r#"{"start":0,"end":1,"reading":"\"","accuracy":"accurate_with_rendaku"}"#
+        );
+    }
+
+    #[test]
+    fn keeps_existing_furigana() {
+        let kanji_to_readings = HashMap::new();
+        let config = MapConfig::default();
+        let mappings = map_partially_annotated(
+            "<ruby>物<rp>(</rp><rt>もの</rt><rp>)</rp></ruby>の怪",
+            "もののけ",
+            &kanji_to_readings,
+            &config,
+        );
+        assert_eq!(mappings.len(), 1);
+        assert_eq!(
+            mappings[0].as_furigana().to_string(),
+            "<ruby>物<rt>もの</rt>の<rt></rt>怪<rt>け</rt></ruby>"
+        );
+        assert!(map_partially_annotated(
+            "<ruby>物<rt>ぶつ</rt></ruby>の怪",
+            "もののけ",
+            &kanji_to_readings,
+            &config
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn annotates_corpus() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        kanji_to_readings.insert("怪".to_string(), vec!["け".to_string()]);
+        let entries = [
+            ("物の怪", "もののけ"),
+            ("物", "もの"),
+            ("物の怪", "もののけ"),
+            ("物の怪", "あ"),
+            ("物", "もの"),
+        ];
+        let sequential = annotate_corpus(entries, &kanji_to_readings);
+        let parallel = annotate_corpus_parallel(entries, &kanji_to_readings, 4);
+        assert_eq!(sequential, parallel);
+        assert_eq!(sequential.len(), 5);
+        assert_eq!(sequential[0], sequential[2]);
+        assert_eq!(sequential[0].as_ref().unwrap().accuracy, 4);
+        assert_eq!(
+            sequential[1].as_ref().unwrap().furigana[0]
+                .furigana
+                .as_deref(),
+            Some("もの")
+        );
+        assert_eq!(sequential[3], None);
+
+        let reports = annotate_corpus_report(
+            [
+                ("物の怪", "もののけ"),
+                ("物", "もの"),
+                ("物の怪", "あ"),
+                ("", ""),
+            ],
+            &kanji_to_readings,
+            1,
+        );
+        assert_eq!(
+            reports[0],
+            AnnotationReport::Ok(sequential[0].clone().unwrap())
+        );
+        assert_eq!(reports[2], AnnotationReport::Failed(Failure::NoMapping));
+        assert_eq!(reports[3], AnnotationReport::Failed(Failure::EmptyInput));
+        let reports = annotate_corpus_report([("大学", "だいがく")], &kanji_to_readings, 1);
+        assert!(matches!(
+            reports[0],
+            AnnotationReport::LowConfidence(_, LowConfidence::Ambiguous { .. })
+        ));
+        let reports = annotate_corpus_report([("物の本", "もののほん")], &kanji_to_readings, 1);
+        assert!(matches!(
+            reports[0],
+            AnnotationReport::LowConfidence(_, LowConfidence::UnbackedReadings)
+        ));
+    }
+}
@@ -0,0 +1,68 @@
+//! Contains an async wrapper around `Mapper` for use in tokio services.
+
+use crate::{mapper::Mapper, owned::OwnedFurigana};
+use std::{panic, sync::Arc};
+
+/// Maps words with a shared `Mapper` on tokio's blocking thread pool,
+/// so that long ambiguous inputs don't block the async executor.
+/// Cloning it is cheap and shares the `Mapper`.
+#[derive(Debug, Clone)]
+pub struct AsyncMapper {
+    mapper: Arc<Mapper>,
+}
+
+impl AsyncMapper {
+    pub fn new(mapper: Arc<Mapper>) -> Self {
+        Self { mapper }
+    }
+
+    pub fn mapper(&self) -> &Arc<Mapper> {
+        &self.mapper
+    }
+
+    /// Works like `Mapper::map`, returning the mappings from the most accurate to the least accurate.
+    /// Must be called from within a tokio runtime. Panics while mapping are passed on to the caller.
+    pub async fn map(
+        &self,
+        word: impl Into<String>,
+        reading: impl Into<String>,
+    ) -> Vec<OwnedFurigana> {
+        let mapper = Arc::clone(&self.mapper);
+        let (word, reading) = (word.into(), reading.into());
+        let task = tokio::task::spawn_blocking(move || {
+            mapper
+                .map(&word, &reading)
+                .iter_ranked()
+                .map(OwnedFurigana::from)
+                .collect()
+        });
+        match task.await {
+            Ok(mappings) => mappings,
+            Err(err) if err.is_panic() => panic::resume_unwind(err.into_panic()),
+            Err(err) => panic!("mapping was cancelled: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::MapConfig;
+    use std::collections::HashMap;
+
+    #[test]
+    fn maps_on_blocking_pool() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        let mapper = AsyncMapper::new(Arc::new(Mapper::new(
+            kanji_to_readings,
+            MapConfig::default(),
+        )));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mappings = runtime.block_on(mapper.map("物の怪", "もののけ"));
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(mappings[0].furigana[0].furigana.as_deref(), Some("もの"));
+    }
+}
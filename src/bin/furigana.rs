@@ -0,0 +1,124 @@
+//! Command line interface for mapping furigana to files of words.
+//!
+//! Usage: `furigana <command> [<args>]...`, built with the `cli` feature.
+//!
+//! Commands:
+//! - `deck <vocab.tsv> [readings.tsv]`: prints a CSV file for importing vocabulary into Anki,
+//!   with the furigana of each word in Anki's bracket format.
+//! - `overrides [--check] <overrides.txt> [<word> <reading>]...`: maps words with the readings of an override file
+//!   taking priority, or only checks the file with `--check`.
+
+use furigana::{AccuracyWeights, KanjiDict, MapConfig, Overrides, ReadingSource};
+use std::{env, fs, process};
+
+const USAGE: &str = "usage:
+  furigana deck <vocab.tsv> [readings.tsv]
+  furigana overrides [--check] <overrides.txt> [<word> <reading>]...";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("deck") => deck(&args[1..]),
+        Some("overrides") => overrides(&args[1..]),
+        _ => usage(),
+    }
+}
+
+// each line of the vocabulary file has a word and its reading separated by a tab,
+// and each line of the optional readings file has a kanji and one of its readings separated by a tab,
+// which lets the best mapping be chosen more accurately
+// the CSV has the fields word, kana, furigana and a flag that is set to "check"
+// for rows that couldn't be mapped or whose furigana isn't fully backed by the readings
+fn deck(args: &[String]) {
+    if args.is_empty() || args.len() > 2 {
+        usage();
+    }
+    let vocab = read(&args[0]);
+    let mut dict = KanjiDict::new();
+    if let Some(path) = args.get(1) {
+        for (kanji, reading) in read(path).lines().filter_map(|line| line.split_once('\t')) {
+            dict.insert(kanji.trim(), reading.trim());
+        }
+    }
+
+    let weights = AccuracyWeights::default();
+    for (word, reading) in vocab.lines().filter_map(|line| line.split_once('\t')) {
+        let (word, reading) = (word.trim(), reading.trim());
+        let mappings = furigana::map(word, reading, &dict);
+        let (furigana, flag) = match mappings.best() {
+            Some(mapping) => {
+                let uncertain = mapping.furigana().iter().any(|segment| {
+                    segment.furigana().is_some() && weights.weight(segment.kanji_accuracy()) < 1
+                });
+                (mapping.to_brackets(), if uncertain { "check" } else { "" })
+            }
+            None => (word.to_string(), "check"),
+        };
+        println!(
+            "{},{},{},{}",
+            csv_field(word),
+            csv_field(reading),
+            csv_field(&furigana),
+            flag
+        );
+    }
+}
+
+// each line of the override file has a word, whitespace and its readings separated by commas,
+// optionally followed by conditions on the characters around the word like next:not-kanji
+// errors in the file are printed as path:line:column: message, and make the program exit with status 1
+fn overrides(args: &[String]) {
+    let check = args.first().map_or(false, |arg| arg == "--check");
+    let args = if check { &args[1..] } else { args };
+    if args.is_empty() || (check && args.len() > 1) || args.len() % 2 == 0 {
+        usage();
+    }
+
+    let path = &args[0];
+    let overrides = Overrides::parse(&read(path)).unwrap_or_else(|errors| {
+        for error in errors {
+            eprintln!("{path}:{error}");
+        }
+        process::exit(1);
+    });
+    if check {
+        println!(
+            "{path}: {} words, {} context rules",
+            overrides.readings().len(),
+            overrides.context_rules().len()
+        );
+        return;
+    }
+
+    let sources = [ReadingSource::new("overrides", overrides.readings())];
+    let config = MapConfig::builder()
+        .context_rules(overrides.context_rules())
+        .build();
+    for pair in args[1..].chunks(2) {
+        let mappings = furigana::map_with_sources(&pair[0], &pair[1], &sources, &config);
+        match mappings.best() {
+            Some(mapping) => println!("{mapping}"),
+            None => println!("{} can't be mapped to {}", pair[0], pair[1]),
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{USAGE}");
+    process::exit(1);
+}
+
+fn read(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("failed to read {path}: {err}");
+        process::exit(1);
+    })
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
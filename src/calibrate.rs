@@ -0,0 +1,136 @@
+//! Contains a utility for fitting the accuracy weights to hand-checked mappings.
+
+use crate::{
+    config::MapConfig,
+    furigana::{AccuracyWeights, Furigana},
+    mappings::Mappings,
+};
+use std::collections::HashMap;
+
+/// The weights chosen by `calibrate` and how well they did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Calibration {
+    pub weights: AccuracyWeights,
+    /// The number of gold mappings that were chosen as the best mapping with the weights.
+    pub correct: usize,
+    pub total: usize,
+}
+
+/// Searches for the accuracy weights that make `map` pick the given gold mappings as the best mapping as often as possible.
+/// The word and reading of each gold mapping are taken from its segments, and the mappings are compared by `Furigana::id`.
+/// The weights of accurate and irregular readings are kept as they are, while the others are tried in a grid around their defaults.
+/// The default weights are kept unless other weights do strictly better.
+pub fn calibrate(
+    gold: &[Furigana],
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+) -> Calibration {
+    let pairs = gold
+        .iter()
+        .map(|mapping| {
+            let word = mapping
+                .furigana
+                .iter()
+                .map(|s| s.segment)
+                .collect::<String>();
+            let reading = mapping
+                .furigana
+                .iter()
+                .map(|s| s.reading)
+                .collect::<String>();
+            (word, reading, mapping.id())
+        })
+        .collect::<Vec<_>>();
+    // the search doesn't depend on the weights, so each tree only needs to be built once
+    let config = MapConfig::default();
+    let trees = pairs
+        .iter()
+        .map(|(word, reading, id)| {
+            (
+                crate::map_tree(word, reading, kanji_to_readings, &config),
+                *id,
+            )
+        })
+        .collect::<Vec<_>>();
+    let score = |weights: &AccuracyWeights| {
+        trees
+            .iter()
+            .filter(|(tree, id)| {
+                let mappings = Mappings::new(Furigana::from_tree_with(tree, weights));
+                mappings.best().map_or(false, |best| best.id() == *id)
+            })
+            .count()
+    };
+
+    let defaults = AccuracyWeights::default();
+    let mut best = Calibration {
+        weights: defaults,
+        correct: score(&defaults),
+        total: gold.len(),
+    };
+    for rendaku in 0..=2 {
+        for sokuonbin in 0..=2 {
+            for lengthening in 0..=2 {
+                for voicing_mismatch in -2..=0 {
+                    for inaccurate in -3..=-1 {
+                        for unknown in -1..=1 {
+                            let weights = AccuracyWeights {
+                                accurate_with_rendaku: rendaku,
+                                accurate_with_sokuonbin: sokuonbin,
+                                accurate_with_lengthening: lengthening,
+                                voicing_mismatch,
+                                inaccurate,
+                                unknown,
+                                ..defaults
+                            };
+                            let correct = score(&weights);
+                            if correct > best.correct {
+                                best.weights = weights;
+                                best.correct = correct;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FuriganaSegment;
+
+    #[test]
+    fn fits_weights() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        let gold = vec![Furigana::new(
+            vec![
+                FuriganaSegment::new("物", Some("もの"), "もの", None),
+                FuriganaSegment::new("の", None, "の", None),
+                FuriganaSegment::new("怪", Some("け"), "け", None),
+            ],
+            0,
+        )];
+        let calibration = calibrate(&gold, &kanji_to_readings);
+        assert_eq!(calibration.correct, 1);
+        assert_eq!(calibration.weights, AccuracyWeights::default());
+
+        // by default, the voicing mismatch of 語 (がご for かご) makes もがご be mapped as も and がご,
+        // so the gold mapping needs voicing mismatches to be penalized more than inaccurate readings
+        kanji_to_readings.insert("物".to_string(), vec!["ぶつ".to_string()]);
+        kanji_to_readings.insert("語".to_string(), vec!["かご".to_string()]);
+        let gold = vec![Furigana::new(
+            vec![
+                FuriganaSegment::new("物", Some("もが"), "もが", None),
+                FuriganaSegment::new("語", Some("ご"), "ご", None),
+            ],
+            0,
+        )];
+        let calibration = calibrate(&gold, &kanji_to_readings);
+        assert_eq!(calibration.correct, 1);
+        assert_eq!(calibration.total, 1);
+        assert!(calibration.weights.voicing_mismatch < calibration.weights.inaccurate);
+    }
+}
@@ -0,0 +1,314 @@
+//! Contains options that change how readings are mapped to words.
+
+use crate::{letters::LetterReadings, rules::ContextRule};
+use std::collections::HashMap;
+
+/// Options for mapping readings to words.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapConfig {
+    /// Accepts readings of numbers that only differ from the expected reading in voicing,
+    /// such as さんせん for 3000 (さんぜん), which are common in noisy sources.
+    /// Such readings lower the accuracy of the mapping instead of failing it.
+    pub fuzzy_numbers: bool,
+    /// Disables the built-in special cases, such as irregular readings of words like 大人,
+    /// readings of numbers like とお for 10, counters, prefixes and suffixes like 的 (てき) and the readings of letters.
+    /// Only the given kanji readings and generic matching are used.
+    pub disable_special_cases: bool,
+    /// Treats ぢ and づ as the same as じ and ず when comparing readings to kanji readings,
+    /// including in combinations like ぢゃ and じゃ, so that historical and regional spellings are still considered accurate.
+    pub merge_yotsugana: bool,
+    /// Accepts readings of kanji that lengthen the last vowel of a known reading before the next part of the word,
+    /// such as にょう in 女房 (にょうぼう) for 女 (にょ). Such readings are graded as `KanjiAccuracy::AccurateWithLengthening`.
+    pub vowel_lengthening: bool,
+    /// Readings of letters like the A in Aランク.
+    /// Defaults to the English readings of A to Z.
+    pub letter_readings: LetterReadings,
+    /// The kind of readings accepted for numbers written with digits.
+    pub number_style: NumberStyle,
+    /// Readings of words written in Latin letters that aren't read letter by letter, such as スイカ for Suica.
+    /// The words are matched ignoring ASCII case and tried before the letters' own readings.
+    pub latin_words: HashMap<String, Vec<String>>,
+    /// Readings of words and kanji that are only accurate next to certain characters, like なま for 生 before kana.
+    /// They are accurate regardless of the kanji reading data, but only graded when it's given.
+    pub context_rules: Vec<ContextRule>,
+    /// Accepts readings that leave out some or all of the kana at the end of the word, such as た for 食べる,
+    /// as given by some sources. The kana that the reading doesn't cover are left without a reading.
+    pub omitted_okurigana: bool,
+    /// Combines standalone dakuten and handakuten with the kana before them when comparing kana,
+    /// so that か゛ in the word or reading matches が, as found in degraded text like OCR output.
+    /// The mappings still contain the text as it was given, so they may not pass `check_invariants`.
+    pub combine_dakuten: bool,
+    /// Treats half-width katakana like ｶﾞｯｺｳ as the full-width katakana they stand for when comparing kana,
+    /// in both the word and the reading. Like with `combine_dakuten`, the mappings contain the text as it was given.
+    pub halfwidth_kana: bool,
+    /// Decides whether ー and 一 are the long vowel mark or the kanji for one by the characters around them,
+    /// as they are often swapped in OCR output and sloppy input:
+    /// 一 between katakana like in ラ一メン is taken as ー, and ー between kanji or at the edge of the word like in 統ー as 一.
+    /// Like with `combine_dakuten`, the mappings contain the text as it was given.
+    pub dashes_by_context: bool,
+    /// Ignores middle dots in the reading, such as the ones between the letters in シー・ディー for CD,
+    /// which some tools put between the parts of a reading. The dots are included in the reading of the segment before or after them.
+    pub ignore_middle_dots: bool,
+    /// How to handle characters that aren't kana, kanji, numbers or letters with known readings, such as ＆ or emoji.
+    pub other_policy: OtherPolicy,
+    /// How to handle whitespace, including ideographic spaces, at the start and end of the word and reading.
+    pub whitespace_policy: WhitespacePolicy,
+}
+
+/// How whitespace around the word and reading is handled, such as the stray spaces often found in CSV files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WhitespacePolicy {
+    /// Whitespace is mapped like any other character according to `MapConfig::other_policy`,
+    /// which by default fails the mapping.
+    #[default]
+    Keep,
+    /// Whitespace at the start and end is left out of the mapping and added back as segments without furigana,
+    /// so that 食べる with the reading たべる　 is mapped like 食べる with the reading たべる.
+    Trim,
+    /// Works like `Trim`, and also ignores whitespace inside the reading, such as the spaces MeCab sometimes puts in its readings.
+    /// The whitespace is included in the reading of the segment before or after it.
+    Skip,
+}
+
+/// How characters that can't be read are handled, such as the ＆ in 東京＆大阪.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OtherPolicy {
+    /// The mapping fails.
+    #[default]
+    Fail,
+    /// The character is kept in the mapping without a reading.
+    PassThroughNoReading,
+    /// The character consumes the same character from the reading if the reading has it at that point,
+    /// as when both the word and the reading contain the ＆, and is otherwise kept without a reading.
+    PassThroughConsumeMatchingKana,
+}
+
+/// The kind of readings accepted for numbers written with digits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NumberStyle {
+    /// Sino-Japanese readings like いち and に, along with the common exceptions like とお for 10.
+    #[default]
+    Any,
+    /// Only Sino-Japanese readings like いち and に.
+    SinoJapanese,
+    /// Only native Japanese readings like ひと and ふた, as used when counting with つ.
+    Native,
+}
+
+impl MapConfig {
+    /// Options for showing furigana to language learners.
+    /// Only readings that follow the expected rules are accepted, and the built-in special cases are used.
+    /// This is the same as the default configuration.
+    pub fn learner() -> Self {
+        Self::default()
+    }
+
+    /// Options for research that needs the mappings to follow from the kanji readings alone.
+    /// Disables the built-in special cases.
+    pub fn strict() -> Self {
+        Self {
+            disable_special_cases: true,
+            ..Self::default()
+        }
+    }
+
+    /// Options for generating datasets from noisy sources.
+    /// Accepts readings that are slightly off, lowering their accuracy instead of failing the mapping.
+    pub fn lenient() -> Self {
+        Self {
+            fuzzy_numbers: true,
+            ..Self::default()
+        }
+    }
+
+    /// Options for annotating text from OCR, such as scanned manga.
+    /// Tolerates the common kinds of damage: standalone dakuten, half-width katakana, swapped ー and 一, stray whitespace
+    /// and readings that are slightly off, and keeps punctuation and other characters that can't be read without a reading.
+    pub fn ocr() -> Self {
+        Self {
+            fuzzy_numbers: true,
+            merge_yotsugana: true,
+            vowel_lengthening: true,
+            combine_dakuten: true,
+            halfwidth_kana: true,
+            dashes_by_context: true,
+            other_policy: OtherPolicy::PassThroughNoReading,
+            whitespace_policy: WhitespacePolicy::Trim,
+            ..Self::default()
+        }
+    }
+
+    /// Returns a builder that starts from the default options.
+    pub fn builder() -> MapConfigBuilder {
+        MapConfigBuilder::default()
+    }
+}
+
+/// Builds a `MapConfig` one option at a time.
+/// Can start from a preset with `MapConfigBuilder::from`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapConfigBuilder {
+    config: MapConfig,
+}
+
+impl MapConfigBuilder {
+    /// Sets `MapConfig::fuzzy_numbers`.
+    pub fn fuzzy_numbers(mut self, fuzzy_numbers: bool) -> Self {
+        self.config.fuzzy_numbers = fuzzy_numbers;
+        self
+    }
+
+    /// Sets `MapConfig::disable_special_cases`.
+    pub fn disable_special_cases(mut self, disable_special_cases: bool) -> Self {
+        self.config.disable_special_cases = disable_special_cases;
+        self
+    }
+
+    /// Sets `MapConfig::merge_yotsugana`.
+    pub fn merge_yotsugana(mut self, merge_yotsugana: bool) -> Self {
+        self.config.merge_yotsugana = merge_yotsugana;
+        self
+    }
+
+    /// Sets `MapConfig::vowel_lengthening`.
+    pub fn vowel_lengthening(mut self, vowel_lengthening: bool) -> Self {
+        self.config.vowel_lengthening = vowel_lengthening;
+        self
+    }
+
+    /// Sets `MapConfig::number_style`.
+    pub fn number_style(mut self, number_style: NumberStyle) -> Self {
+        self.config.number_style = number_style;
+        self
+    }
+
+    /// Sets `MapConfig::latin_words`.
+    pub fn latin_words(mut self, latin_words: HashMap<String, Vec<String>>) -> Self {
+        self.config.latin_words = latin_words;
+        self
+    }
+
+    /// Sets `MapConfig::context_rules`.
+    pub fn context_rules(mut self, context_rules: Vec<ContextRule>) -> Self {
+        self.config.context_rules = context_rules;
+        self
+    }
+
+    /// Sets `MapConfig::omitted_okurigana`.
+    pub fn omitted_okurigana(mut self, omitted_okurigana: bool) -> Self {
+        self.config.omitted_okurigana = omitted_okurigana;
+        self
+    }
+
+    /// Sets `MapConfig::combine_dakuten`.
+    pub fn combine_dakuten(mut self, combine_dakuten: bool) -> Self {
+        self.config.combine_dakuten = combine_dakuten;
+        self
+    }
+
+    /// Sets `MapConfig::halfwidth_kana`.
+    pub fn halfwidth_kana(mut self, halfwidth_kana: bool) -> Self {
+        self.config.halfwidth_kana = halfwidth_kana;
+        self
+    }
+
+    /// Sets `MapConfig::dashes_by_context`.
+    pub fn dashes_by_context(mut self, dashes_by_context: bool) -> Self {
+        self.config.dashes_by_context = dashes_by_context;
+        self
+    }
+
+    /// Sets `MapConfig::ignore_middle_dots`.
+    pub fn ignore_middle_dots(mut self, ignore_middle_dots: bool) -> Self {
+        self.config.ignore_middle_dots = ignore_middle_dots;
+        self
+    }
+
+    /// Sets `MapConfig::other_policy`.
+    pub fn other_policy(mut self, other_policy: OtherPolicy) -> Self {
+        self.config.other_policy = other_policy;
+        self
+    }
+
+    /// Sets `MapConfig::whitespace_policy`.
+    pub fn whitespace_policy(mut self, whitespace_policy: WhitespacePolicy) -> Self {
+        self.config.whitespace_policy = whitespace_policy;
+        self
+    }
+
+    /// Sets `MapConfig::letter_readings`.
+    pub fn letter_readings(mut self, letter_readings: LetterReadings) -> Self {
+        self.config.letter_readings = letter_readings;
+        self
+    }
+
+    /// Returns the options that were set.
+    pub fn build(self) -> MapConfig {
+        self.config
+    }
+}
+
+impl From<MapConfig> for MapConfigBuilder {
+    fn from(config: MapConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn builds_from_presets() {
+        assert_eq!(MapConfig::builder().build(), MapConfig::learner());
+        assert_eq!(
+            MapConfig::builder().fuzzy_numbers(true).build(),
+            MapConfig::lenient()
+        );
+        let config = MapConfigBuilder::from(MapConfig::strict())
+            .fuzzy_numbers(true)
+            .build();
+        assert!(config.disable_special_cases && config.fuzzy_numbers);
+    }
+
+    #[test]
+    fn presets_pass_invariants() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("人".to_string(), vec!["ひと".to_string()]);
+        let inputs = [
+            "ー人",
+            "一人",
+            "ラ一メン",
+            "ｶﾀｶﾅ",
+            " 人 ",
+            "が",
+            "か゛",
+            "゛ー",
+            "ひとり",
+            "",
+        ];
+        for config in [
+            MapConfig::learner(),
+            MapConfig::strict(),
+            MapConfig::lenient(),
+            MapConfig::ocr(),
+        ] {
+            for word in inputs {
+                for reading in inputs {
+                    let mappings = crate::map_with(word, reading, &kanji_to_readings, &config);
+                    for mapping in &mappings {
+                        assert_eq!(
+                            crate::check_invariants_with(mapping, word, reading, &config),
+                            Ok(()),
+                            "{word} ({reading})"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Contains a multithreaded annotator for streaming large corpora.
+
+use crate::{
+    annotate::{self, AnnotationReport},
+    dict::KanjiDict,
+};
+use std::{
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Annotates a stream of (word, reading) pairs on worker threads that share a dictionary.
+/// The pairs are passed to the workers through bounded channels,
+/// so a corpus can be annotated without reading all of it into memory.
+#[derive(Debug, Clone)]
+pub struct CorpusAnnotator {
+    dict: Arc<KanjiDict>,
+    threads: usize,
+    capacity: usize,
+}
+
+/// The results of `CorpusAnnotator::annotate` as they become ready.
+/// Iterates over the index of each pair in the input along with its report,
+/// in the order the workers finish them rather than the input order.
+#[derive(Debug)]
+pub struct AnnotatedStream {
+    results: Receiver<(usize, AnnotationReport)>,
+    workers: Vec<JoinHandle<()>>,
+    started: Instant,
+    annotated: usize,
+}
+
+/// How fast an `AnnotatedStream` has been annotating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Throughput {
+    /// The number of pairs received from the stream so far.
+    pub annotated: usize,
+    /// The time since the stream was started.
+    pub elapsed: Duration,
+}
+
+impl CorpusAnnotator {
+    /// Creates an annotator that uses the given number of worker threads, such as one per core.
+    pub fn new(dict: Arc<KanjiDict>, threads: usize) -> Self {
+        Self {
+            dict,
+            threads: threads.max(1),
+            capacity: 1024,
+        }
+    }
+
+    /// Sets how many pairs can wait in each channel. 1024 by default.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Starts annotating the pairs in the background.
+    /// Dropping the stream before it ends stops the workers after the pairs they are working on.
+    pub fn annotate<I>(&self, entries: I) -> AnnotatedStream
+    where
+        I: IntoIterator<Item = (String, String)>,
+        I::IntoIter: Send + 'static,
+    {
+        let (pair_sender, pairs) = mpsc::sync_channel(self.capacity);
+        let (result_sender, results) = mpsc::sync_channel(self.capacity);
+        let entries = entries.into_iter();
+        thread::spawn(move || feed(entries, pair_sender));
+
+        let pairs = Arc::new(Mutex::new(pairs));
+        let workers = (0..self.threads)
+            .map(|_| {
+                let pairs = Arc::clone(&pairs);
+                let results = result_sender.clone();
+                let dict = Arc::clone(&self.dict);
+                thread::spawn(move || work(&pairs, &results, &dict))
+            })
+            .collect();
+        AnnotatedStream {
+            results,
+            workers,
+            started: Instant::now(),
+            annotated: 0,
+        }
+    }
+}
+
+fn feed<I: Iterator<Item = (String, String)>>(
+    entries: I,
+    pairs: SyncSender<(usize, String, String)>,
+) {
+    for (idx, (word, reading)) in entries.enumerate() {
+        if pairs.send((idx, word, reading)).is_err() {
+            // the workers have stopped
+            break;
+        }
+    }
+}
+
+fn work(
+    pairs: &Mutex<Receiver<(usize, String, String)>>,
+    results: &SyncSender<(usize, AnnotationReport)>,
+    dict: &KanjiDict,
+) {
+    loop {
+        // the lock is only held while receiving, so a panic can't leave the receiver in a bad state
+        let pair = pairs.lock().unwrap_or_else(PoisonError::into_inner).recv();
+        let (idx, word, reading) = match pair {
+            Ok(pair) => pair,
+            Err(_) => break,
+        };
+        let report = annotate::annotate_pair(&word, &reading, dict);
+        if results.send((idx, report)).is_err() {
+            // the stream was dropped
+            break;
+        }
+    }
+}
+
+impl AnnotatedStream {
+    pub fn throughput(&self) -> Throughput {
+        Throughput {
+            annotated: self.annotated,
+            elapsed: self.started.elapsed(),
+        }
+    }
+}
+
+impl Iterator for AnnotatedStream {
+    type Item = (usize, AnnotationReport);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.results.recv() {
+            Ok(result) => {
+                self.annotated += 1;
+                Some(result)
+            }
+            Err(_) => {
+                // all workers are done, so any panics can be passed on
+                for worker in self.workers.drain(..) {
+                    worker.join().expect("mapping panicked");
+                }
+                None
+            }
+        }
+    }
+}
+
+impl Throughput {
+    /// Returns the number of pairs annotated per second.
+    pub fn per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.annotated as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn streams_corpus() {
+        let mut dict = KanjiDict::new();
+        dict.insert("物", "もの");
+        dict.insert("怪", "け");
+        let entries = (0..100).map(|idx| {
+            if idx % 2 == 0 {
+                ("物の怪".to_string(), "もののけ".to_string())
+            } else {
+                ("物の怪".to_string(), "あ".to_string())
+            }
+        });
+        let mut stream = CorpusAnnotator::new(Arc::new(dict), 4)
+            .capacity(8)
+            .annotate(entries);
+        let mut results = stream.by_ref().collect::<Vec<_>>();
+        results.sort_by_key(|(idx, _)| *idx);
+        assert_eq!(results.len(), 100);
+        for (idx, report) in results {
+            assert_eq!(report.furigana().is_some(), idx % 2 == 0);
+        }
+        assert_eq!(stream.throughput().annotated, 100);
+    }
+}
@@ -0,0 +1,177 @@
+//! Contains readings for kanji that follow numbers, such as counters.
+
+use crate::{numbers, segmentation::Segment};
+
+/// Returns the readings the kanji can have in the context of the surrounding segments, which may be missing from kanji reading data.
+pub fn readings(
+    previous: Option<Segment>,
+    kanji: &str,
+    next: Option<Segment>,
+) -> &'static [&'static str] {
+    if let Some(number) = kanji_numeral(kanji) {
+        let followed_by_tsu =
+            matches!(next, Some(Segment::Kana(kana)) if kana.starts_with(['つ', 'ツ']));
+        let part_of_number =
+            matches!(previous, Some(Segment::Kanji(previous)) if kanji_numeral(previous).is_some());
+        let followed_by_counter =
+            matches!(next, Some(Segment::Kanji(next)) if kanji_numeral(next).is_none());
+        // 十 before a counter like 十階 (じゅっかい), where the reading in kanji reading data doesn't end in a sound that can become っ
+        if number == 10 && !part_of_number && followed_by_counter {
+            return &["とお", "じゅっ", "じっ"];
+        }
+        // native counting like 三つ (みっつ), or 十 (とお) which is read the same way without つ
+        if (followed_by_tsu && number < 10) || (number == 10 && !part_of_number) {
+            return numbers::native_readings(number);
+        }
+        // days of the month like 三日 (みっか)
+        if next == Some(Segment::Kanji("日")) && !part_of_number {
+            return day_readings(number);
+        }
+    }
+    let after_number = match previous {
+        Some(Segment::Numeric(_)) => true,
+        Some(Segment::Kanji(previous)) => kanji_numeral(previous).is_some(),
+        _ => false,
+    };
+    match (previous, kanji, next) {
+        // months like 4月, or durations like 3ヶ月
+        (_, "月", _) if after_number => &["がつ", "げつ"],
+        (Some(Segment::Exception("ヶ")), "月", _) => &["げつ"],
+        // days like 3日 or 11日
+        (_, "日", _) if after_number => &["か", "にち"],
+        // ordinals like 第3回 or 第一
+        (_, "第", Some(Segment::Numeric(_))) => &["だい"],
+        (_, "第", Some(Segment::Kanji(next))) if kanji_numeral(next).is_some() => &["だい"],
+        (_, "回", _) if after_number => &["かい"],
+        // days of the week like 月曜日
+        (_, "月", Some(Segment::Kanji("曜"))) => &["げつ"],
+        (_, "火", Some(Segment::Kanji("曜"))) => &["か"],
+        (_, "水", Some(Segment::Kanji("曜"))) => &["すい"],
+        (_, "木", Some(Segment::Kanji("曜"))) => &["もく"],
+        (_, "金", Some(Segment::Kanji("曜"))) => &["きん"],
+        (_, "土", Some(Segment::Kanji("曜"))) => &["ど"],
+        (_, "日", Some(Segment::Kanji("曜"))) => &["にち"],
+        (_, "曜", _) => &["よう"],
+        (Some(Segment::Kanji("曜")), "日", _) => &["び"],
+        // fractions like 3分の1
+        (Some(Segment::Numeric(_)), "分", Some(Segment::Kana(kana)))
+            if kana.starts_with(['の', 'ノ']) =>
+        {
+            &["ぶん"]
+        }
+        // times like 3時15分 or 10時半
+        (Some(Segment::Numeric(_)), "分", _) => &["ふん", "ぷん"],
+        (Some(Segment::Numeric(_)), "時", _) => &["じ"],
+        // ages like 8歳
+        (Some(Segment::Numeric(_)), "歳" | "才", _) => &["さい"],
+        (Some(Segment::Kanji("時")), "半", _) => &["はん"],
+        _ => &[],
+    }
+}
+
+/// Returns readings of the number that are only used before the counter, such as よ in 4時.
+pub fn number_readings(number: u64, counter: &str) -> Vec<String> {
+    if counter == "つ" {
+        // native counting like 3つ (みっつ)
+        if number >= 10 {
+            return vec![];
+        }
+        return numbers::native_readings(number)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+    }
+    if counter == "日" && !day_readings(number).is_empty() {
+        return day_readings(number)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+    }
+    let changes: &[(&str, &str)] = match counter {
+        "時" => &[("よん", "よ")],
+        "分" => &[
+            ("いち", "いっ"),
+            ("ろく", "ろっ"),
+            ("はち", "はっ"),
+            ("じゅう", "じゅっ"),
+            ("じゅう", "じっ"),
+        ],
+        "回" => &[
+            ("いち", "いっ"),
+            ("ろく", "ろっ"),
+            ("はち", "はっ"),
+            ("じゅう", "じゅっ"),
+            ("じゅう", "じっ"),
+        ],
+        // days like 14日 (じゅうよっか)
+        "日" => &[("よん", "よっ")],
+        "歳" | "才" => &[
+            ("いち", "いっ"),
+            ("はち", "はっ"),
+            ("じゅう", "じゅっ"),
+            ("じゅう", "じっ"),
+        ],
+        _ => &[],
+    };
+    numbers::readings_of(number)
+        .into_iter()
+        .flat_map(|reading| {
+            changes.iter().filter_map(move |(from, to)| {
+                reading
+                    .strip_suffix(from)
+                    .map(|stripped| format!("{stripped}{to}"))
+            })
+        })
+        .collect()
+}
+
+/// Returns readings that cover both the number and the counter, such as はたち for 20歳.
+pub fn combined_readings(number: u64, counter: &str) -> &'static [&'static str] {
+    match (number, counter) {
+        (20, "歳" | "才") => &["はたち"],
+        (1, "日") => &["ついたち"],
+        _ => &[],
+    }
+}
+
+// readings of numbers before 日 when it's a day of the month, such as みっ in 3日 (みっか)
+fn day_readings(number: u64) -> &'static [&'static str] {
+    match number {
+        2 => &["ふつ"],
+        3 => &["みっ"],
+        4 => &["よっ"],
+        5 => &["いつ"],
+        6 => &["むい"],
+        7 => &["なの"],
+        8 => &["よう"],
+        9 => &["ここの"],
+        10 => &["とお"],
+        20 => &["はつ"],
+        _ => &[],
+    }
+}
+
+// the value of a kanji numeral from 一 to 十
+fn kanji_numeral(kanji: &str) -> Option<u64> {
+    let numerals = ["一", "二", "三", "四", "五", "六", "七", "八", "九", "十"];
+    numerals
+        .iter()
+        .position(|numeral| *numeral == kanji)
+        .map(|idx| idx as u64 + 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn changes_number_readings() {
+        assert_eq!(number_readings(4, "時"), vec!["よ"]);
+        assert_eq!(number_readings(16, "分"), vec!["じゅうろっ"]);
+        assert_eq!(number_readings(20, "分"), vec!["にじゅっ", "にじっ"]);
+        assert!(number_readings(5, "分").is_empty());
+        assert_eq!(number_readings(8, "つ"), vec!["や", "やっ"]);
+        assert_eq!(number_readings(3, "日"), vec!["みっ"]);
+        assert_eq!(number_readings(24, "日"), vec!["にじゅうよっ"]);
+    }
+}
@@ -0,0 +1,271 @@
+//! Contains utilities for composing layered sets of kanji readings.
+
+use std::{collections::HashMap, ops::Deref};
+
+/// Readings of kanji and words made up of multiple kanji, along with the level of each kanji, such as its school grade.
+/// Derefs to the underlying map of readings, so it can be passed to `map` and the other mapping functions as is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KanjiDict {
+    kanji_to_readings: HashMap<String, Vec<String>>,
+    levels: HashMap<char, u8>,
+}
+
+/// How `KanjiDict::merge` resolves kanji that have different readings in both dictionaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MergeStrategy {
+    /// Keeps the existing readings and adds the new ones after them.
+    Union,
+    /// Keeps the existing readings.
+    KeepExisting,
+    /// Replaces the existing readings with the new ones.
+    Override,
+}
+
+/// A kanji that had different readings in the dictionaries being merged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Conflict {
+    pub kanji: String,
+    pub existing: Vec<String>,
+    pub incoming: Vec<String>,
+}
+
+/// A difference between two dictionaries.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DictChange {
+    Added {
+        kanji: String,
+        readings: Vec<String>,
+    },
+    Removed {
+        kanji: String,
+        readings: Vec<String>,
+    },
+    Changed {
+        kanji: String,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+}
+
+impl KanjiDict {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a reading for the kanji, keeping its existing readings.
+    pub fn insert(&mut self, kanji: impl Into<String>, reading: impl Into<String>) {
+        let reading = reading.into();
+        let readings = self.kanji_to_readings.entry(kanji.into()).or_default();
+        if !readings.contains(&reading) {
+            readings.push(reading);
+        }
+    }
+
+    /// Sets the level of the kanji, used by `RenderOptions::above_level`.
+    /// Higher levels should be harder, like school grades. JLPT levels need to be flipped, such as 1 for N5 and 5 for N1.
+    pub fn set_level(&mut self, kanji: char, level: u8) {
+        self.levels.insert(kanji, level);
+    }
+
+    pub fn level(&self, kanji: char) -> Option<u8> {
+        self.levels.get(&kanji).copied()
+    }
+
+    pub(crate) fn levels(&self) -> impl Iterator<Item = (char, u8)> + '_ {
+        self.levels.iter().map(|(&kanji, &level)| (kanji, level))
+    }
+
+    /// Merges the readings and levels of the other dictionary into this one.
+    /// Returns the kanji whose readings differed between the dictionaries, sorted by kanji,
+    /// regardless of how the strategy resolved them.
+    /// Levels are only replaced with `MergeStrategy::Override`.
+    pub fn merge(&mut self, other: &KanjiDict, strategy: MergeStrategy) -> Vec<Conflict> {
+        for (&kanji, &level) in &other.levels {
+            if strategy == MergeStrategy::Override || !self.levels.contains_key(&kanji) {
+                self.levels.insert(kanji, level);
+            }
+        }
+
+        let mut conflicts = vec![];
+        for (kanji, incoming) in &other.kanji_to_readings {
+            let existing = match self.kanji_to_readings.get_mut(kanji) {
+                Some(existing) => existing,
+                None => {
+                    self.kanji_to_readings
+                        .insert(kanji.clone(), incoming.clone());
+                    continue;
+                }
+            };
+            if same_readings(existing, incoming) {
+                continue;
+            }
+
+            conflicts.push(Conflict {
+                kanji: kanji.clone(),
+                existing: existing.clone(),
+                incoming: incoming.clone(),
+            });
+            match strategy {
+                MergeStrategy::Union => {
+                    for reading in incoming {
+                        if !existing.contains(reading) {
+                            existing.push(reading.clone());
+                        }
+                    }
+                }
+                MergeStrategy::KeepExisting => {}
+                MergeStrategy::Override => *existing = incoming.clone(),
+            }
+        }
+        conflicts.sort_by(|l, r| l.kanji.cmp(&r.kanji));
+        conflicts
+    }
+
+    /// Returns the changes to the readings that turn this dictionary into the other one, sorted by kanji.
+    /// The order of the readings of a kanji is ignored.
+    pub fn diff(&self, other: &KanjiDict) -> Vec<DictChange> {
+        let mut changes = vec![];
+        for (kanji, readings) in &self.kanji_to_readings {
+            match other.kanji_to_readings.get(kanji) {
+                None => changes.push(DictChange::Removed {
+                    kanji: kanji.clone(),
+                    readings: readings.clone(),
+                }),
+                Some(other_readings) if !same_readings(readings, other_readings) => {
+                    changes.push(DictChange::Changed {
+                        kanji: kanji.clone(),
+                        added: missing_from(other_readings, readings),
+                        removed: missing_from(readings, other_readings),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for (kanji, readings) in &other.kanji_to_readings {
+            if !self.kanji_to_readings.contains_key(kanji) {
+                changes.push(DictChange::Added {
+                    kanji: kanji.clone(),
+                    readings: readings.clone(),
+                });
+            }
+        }
+        changes.sort_by(|l, r| l.kanji().cmp(r.kanji()));
+        changes
+    }
+
+    pub fn into_inner(self) -> HashMap<String, Vec<String>> {
+        self.kanji_to_readings
+    }
+}
+
+impl DictChange {
+    /// Returns the kanji the change concerns.
+    pub fn kanji(&self) -> &str {
+        match self {
+            Self::Added { kanji, .. }
+            | Self::Removed { kanji, .. }
+            | Self::Changed { kanji, .. } => kanji,
+        }
+    }
+}
+
+// the readings in left that aren't in right
+fn missing_from(left: &[String], right: &[String]) -> Vec<String> {
+    left.iter()
+        .filter(|reading| !right.contains(reading))
+        .cloned()
+        .collect()
+}
+
+fn same_readings(left: &[String], right: &[String]) -> bool {
+    missing_from(left, right).is_empty() && missing_from(right, left).is_empty()
+}
+
+impl Deref for KanjiDict {
+    type Target = HashMap<String, Vec<String>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.kanji_to_readings
+    }
+}
+
+impl From<HashMap<String, Vec<String>>> for KanjiDict {
+    fn from(kanji_to_readings: HashMap<String, Vec<String>>) -> Self {
+        Self {
+            kanji_to_readings,
+            levels: HashMap::new(),
+        }
+    }
+}
+
+impl From<KanjiDict> for HashMap<String, Vec<String>> {
+    fn from(dict: KanjiDict) -> Self {
+        dict.kanji_to_readings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn merges_and_diffs() {
+        let mut base = KanjiDict::new();
+        base.insert("物", "もの");
+        base.insert("怪", "カイ");
+        let mut overrides = KanjiDict::new();
+        overrides.insert("怪", "け");
+        overrides.insert("日", "ひ");
+
+        let mut merged = base.clone();
+        let conflicts = merged.merge(&overrides, MergeStrategy::Union);
+        assert_eq!(
+            conflicts,
+            vec![Conflict {
+                kanji: "怪".to_string(),
+                existing: vec!["カイ".to_string()],
+                incoming: vec!["け".to_string()],
+            }]
+        );
+        assert_eq!(merged["怪"], ["カイ", "け"]);
+        assert_eq!(merged["日"], ["ひ"]);
+        let furigana = crate::map("物の怪", "もののけ", &merged);
+        assert_eq!(furigana.best().unwrap().accuracy, 4);
+
+        let mut kept = base.clone();
+        kept.merge(&overrides, MergeStrategy::KeepExisting);
+        assert_eq!(kept["怪"], ["カイ"]);
+        let mut overridden = base.clone();
+        overridden.merge(&overrides, MergeStrategy::Override);
+        assert_eq!(overridden["怪"], ["け"]);
+
+        assert_eq!(
+            base.diff(&overridden),
+            vec![
+                DictChange::Changed {
+                    kanji: "怪".to_string(),
+                    added: vec!["け".to_string()],
+                    removed: vec!["カイ".to_string()],
+                },
+                DictChange::Added {
+                    kanji: "日".to_string(),
+                    readings: vec!["ひ".to_string()],
+                },
+            ]
+        );
+        assert!(overridden.diff(&overridden).is_empty());
+
+        let mut levels = KanjiDict::new();
+        levels.set_level('物', 3);
+        levels.set_level('怪', 8);
+        let mut with_levels = base.clone();
+        with_levels.set_level('怪', 7);
+        with_levels.merge(&levels, MergeStrategy::Union);
+        assert_eq!(with_levels.level('物'), Some(3));
+        assert_eq!(with_levels.level('怪'), Some(7));
+        with_levels.merge(&levels, MergeStrategy::Override);
+        assert_eq!(with_levels.level('怪'), Some(8));
+    }
+}
@@ -0,0 +1,65 @@
+//! Contains a list of words ranked by how common they are.
+
+use std::collections::HashMap;
+
+/// Words ranked by how common they are, with the most common word at rank 0.
+/// Used by `RenderOptions::skip_frequent` to leave out the furigana of common words.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrequencyList {
+    ranks: HashMap<String, usize>,
+}
+
+impl FrequencyList {
+    /// Creates a list from words ordered from the most common to the least common.
+    /// Words that appear more than once keep their first rank.
+    pub fn new<I, S>(words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut ranks = HashMap::new();
+        for word in words {
+            let rank = ranks.len();
+            ranks.entry(word.into()).or_insert(rank);
+        }
+        Self { ranks }
+    }
+
+    /// Parses a list with one word per line, ordered from the most common to the least common.
+    /// Only the first tab-separated column of each line is used, so lists with counts or readings after the word work as is.
+    /// Empty lines are skipped.
+    pub fn parse(text: &str) -> Self {
+        Self::new(
+            text.lines()
+                .filter_map(|line| line.split('\t').next())
+                .map(str::trim)
+                .filter(|word| !word.is_empty()),
+        )
+    }
+
+    /// Returns the rank of the word, or None if it isn't in the list.
+    pub fn rank(&self, word: &str) -> Option<usize> {
+        self.ranks.get(word).copied()
+    }
+
+    pub(crate) fn words_above(&self, rank: usize) -> impl Iterator<Item = &str> {
+        self.ranks
+            .iter()
+            .filter(move |(_, &word_rank)| word_rank < rank)
+            .map(|(word, _)| word.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_list() {
+        let list = FrequencyList::parse("の\t1000\n\n日本\t500\nの\t10\n 怪 \n");
+        assert_eq!(list.rank("の"), Some(0));
+        assert_eq!(list.rank("日本"), Some(1));
+        assert_eq!(list.rank("怪"), Some(2));
+        assert_eq!(list.rank("物"), None);
+    }
+}
@@ -1,76 +1,240 @@
-use crate::{kana_equivalent, segmentation::Segment};
-use std::fmt::Display;
+use crate::{
+    kana_equivalent, render::RenderOptions, segmentation::Segment, segments::Segments, utils,
+};
+use std::{
+    fmt::{Display, Write},
+    ops::Range,
+};
 
 /// A mapping of furigana to a word.
+/// The strings in the mappings returned by the mapping functions of this crate, including the empty ones,
+/// always point into the word and reading they were mapped from, so they can be located without searching
+/// with `FuriganaSegment::segment_range` and `FuriganaSegment::reading_range`.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Furigana<'a> {
+    pub(crate) furigana: Segments<'a>,
+    pub(crate) accuracy: i32,
+}
+
+impl<'a> Furigana<'a> {
+    pub fn new(furigana: Vec<FuriganaSegment<'a>>, accuracy: i32) -> Self {
+        Self {
+            furigana: furigana.into(),
+            accuracy,
+        }
+    }
+
     /// The original word with furigana.
-    pub furigana: Vec<FuriganaSegment<'a>>,
+    pub fn furigana(&self) -> &[FuriganaSegment<'a>] {
+        &self.furigana
+    }
+
     /// A rough relative measure of this mapping's accuracy, the higher the more accurate.
     /// Only meaningful in comparison with other configurations for the same word.
-    pub accuracy: i32,
-}
+    pub fn accuracy(&self) -> i32 {
+        self.accuracy
+    }
+
+    pub fn into_furigana(self) -> Vec<FuriganaSegment<'a>> {
+        self.furigana.into_vec()
+    }
 
-impl<'a> Furigana<'a> {
     /// Flattens `FuriganaNode`s to a list of `Furigana`.
     pub fn from_tree(tree: &FuriganaTree<'a>) -> Vec<Self> {
-        Self::from_tree_inner(&tree.nodes)
+        Self::from_tree_with(tree, &AccuracyWeights::default())
     }
 
-    fn from_tree_inner(nodes: &[FuriganaNode<'a>]) -> Vec<Self> {
-        let mut furigana: Vec<Furigana> = vec![];
+    /// Works like `from_tree`, scoring the mappings with the given weights.
+    /// Flattening is cheap compared to searching, so the same tree can be scored in different ways.
+    pub fn from_tree_with(tree: &FuriganaTree<'a>, weights: &AccuracyWeights) -> Vec<Self> {
+        let mut furigana = vec![];
+        let mut path = vec![];
+        Self::flatten(tree, tree.roots(), weights, &mut path, 0, &mut furigana);
+        furigana
+    }
 
+    // walks the tree depth first, keeping the segments leading to the current node in path
+    // so that each mapping is only copied once, at its full length, when a leaf is reached
+    fn flatten(
+        tree: &FuriganaTree<'a>,
+        nodes: &[FuriganaNode<'a>],
+        weights: &AccuracyWeights,
+        path: &mut Vec<FuriganaSegment<'a>>,
+        accuracy: i32,
+        furigana: &mut Vec<Self>,
+    ) {
         for node in nodes {
-            let word = node.segment.inner();
-            let reading = if kana_equivalent(word, node.reading) {
-                // no need for furigana here
-                None
-            } else {
-                Some(node.reading)
-            };
-            let kanji_accuracy = match &node.kanji_accurate {
-                Some(KanjiAccuracy::Accurate) => 2,
-                Some(KanjiAccuracy::AccurateWithRendaku) => 1,
-                Some(KanjiAccuracy::AccurateWithSokuonbin) => 1,
-                Some(KanjiAccuracy::Inaccurate) => -2,
-                None => 0,
-            };
-            if node.extensions.is_empty() {
+            path.push(FuriganaSegment::from_node(node));
+            let accuracy = accuracy + weights.weight(node.kanji_accurate);
+            let extensions = tree.extensions(node);
+            if extensions.is_empty() {
                 furigana.push(Furigana {
-                    furigana: vec![FuriganaSegment {
-                        segment: word,
-                        furigana: reading,
-                    }],
-                    accuracy: kanji_accuracy,
+                    furigana: Segments::from(path.as_slice()),
+                    accuracy,
                 });
             } else {
-                for flattened_extensions in Self::from_tree_inner(&node.extensions) {
-                    let mut ruby = vec![FuriganaSegment {
-                        segment: word,
-                        furigana: reading,
-                    }];
-                    ruby.extend(flattened_extensions.furigana);
-                    let kanji_accuracy = kanji_accuracy + flattened_extensions.accuracy;
-                    furigana.push(Furigana {
-                        furigana: ruby,
-                        accuracy: kanji_accuracy,
-                    })
+                Self::flatten(tree, extensions, weights, path, accuracy, furigana);
+            }
+            path.pop();
+        }
+    }
+
+    /// Returns the byte range of the reading that belongs to the segment at the given index,
+    /// such as 0..6 for 物 in 物の怪 (もののけ).
+    /// Panics if the index is out of bounds.
+    pub fn reading_span_of(&self, segment_index: usize) -> Range<usize> {
+        let start = self.furigana[..segment_index]
+            .iter()
+            .map(|segment| segment.reading.len())
+            .sum();
+        start..start + self.furigana[segment_index].reading.len()
+    }
+
+    /// Returns the byte range of the word that belongs to the segment at the given index,
+    /// such as 6..9 for 怪 in 物の怪.
+    /// Panics if the index is out of bounds.
+    pub fn segment_span_of(&self, segment_index: usize) -> Range<usize> {
+        let start = self.furigana[..segment_index]
+            .iter()
+            .map(|segment| segment.segment.len())
+            .sum();
+        start..start + self.furigana[segment_index].segment.len()
+    }
+
+    // checks that every string in the mapping is a part of the word or reading it was created from
+    pub(crate) fn points_into(&self, word: &str, reading: &str) -> bool {
+        self.furigana.iter().all(|segment| {
+            utils::subslice_range(word, segment.segment).is_some()
+                && utils::subslice_range(reading, segment.reading).is_some()
+                && segment.furigana.map_or(true, |furigana| {
+                    utils::subslice_range(reading, furigana).is_some()
+                })
+        })
+    }
+
+    /// Returns the segments and their furigana as owned strings, for storing the mapping independently of the word and reading.
+    pub fn to_pairs(&self) -> Vec<(String, Option<String>)> {
+        self.furigana
+            .iter()
+            .map(|segment| {
+                (
+                    segment.segment.to_string(),
+                    segment.furigana.map(str::to_string),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns an identifier derived from how the mapping splits the word and the reading.
+    /// The identifier stays the same across runs and versions of the crate,
+    /// so it can be stored to find the same mapping again with `Mappings::find_id` after mapping the word again.
+    pub fn id(&self) -> u64 {
+        // FNV-1a, as the hashers in std aren't guaranteed to stay the same
+        let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+        let mut write = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        for segment in &self.furigana {
+            // separators that can't appear in UTF-8 keep different splits from hashing the same bytes
+            write(segment.segment.as_bytes());
+            write(&[0xff]);
+            write(segment.reading.as_bytes());
+            write(&[0xfe]);
+        }
+        hash
+    }
+
+    /// Formats the mapping in the bracket format used by Anki, such as "物[もの]の 怪[け]".
+    /// A space is added before each segment with furigana other than the first, as it marks where the segment starts.
+    pub fn to_brackets(&self) -> String {
+        let mut brackets = String::new();
+        for segment in &self.furigana {
+            match segment.furigana {
+                Some(furigana) => {
+                    if !brackets.is_empty() {
+                        brackets.push(' ');
+                    }
+                    // writing to a string can't fail
+                    let _ = write!(brackets, "{}[{furigana}]", segment.segment);
                 }
+                None => brackets.push_str(segment.segment),
             }
         }
-        furigana
+        brackets
+    }
+
+    /// Describes how each reading contributed to the accuracy of the mapping,
+    /// such as "物→もの: dictionary match (+2); 怪→け: dictionary match (+2)".
+    pub fn explain(&self) -> String {
+        self.furigana
+            .iter()
+            .filter(|segment| segment.furigana.is_some() || segment.kanji_accuracy.is_some())
+            .map(|segment| {
+                let reason = segment
+                    .kanji_accuracy
+                    .map_or("no reading information", KanjiAccuracy::describe);
+                let weight = AccuracyWeights::default().weight(segment.kanji_accuracy);
+                format!(
+                    "{}→{}: {reason} ({weight:+})",
+                    segment.segment,
+                    segment.furigana.unwrap_or(segment.segment)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
     }
 }
 
 /// Prints the word with its furigana using HTML ruby tags.
 impl Display for Furigana<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_html(f, false, &RenderOptions::default())
+    }
+}
+
+impl Furigana<'_> {
+    /// Works like the `Display` implementation, adding a class to each `rt` tag with furigana
+    /// based on the accuracy of the reading so that uncertain readings can be styled differently:
+    /// `furigana-accurate` for readings backed by the reading information and `furigana-guess` for the rest.
+    pub fn to_html_with_classes(&self) -> String {
+        let mut html = String::new();
+        // writing to a string can't fail
+        let _ = self.write_html(&mut html, true, &RenderOptions::default());
+        html
+    }
+
+    /// Works like the `Display` implementation, leaving out the furigana of the segments as set by the options.
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let mut html = String::new();
+        let _ = self.write_html(&mut html, false, options);
+        html
+    }
+
+    fn write_html(
+        &self,
+        f: &mut impl Write,
+        classes: bool,
+        options: &RenderOptions,
+    ) -> std::fmt::Result {
         write!(f, "<ruby>")?;
+        let skip_word = options.skips_word(self);
         for segment in &self.furigana {
             write!(f, "{}", segment.segment)?;
-            write!(f, "<rt>")?;
-            if let Some(furigana) = segment.furigana {
-                write!(f, "{}", furigana)?;
+            let furigana = if skip_word {
+                None
+            } else {
+                options.furigana(segment)
+            };
+            match furigana {
+                Some(furigana) if classes => {
+                    let class = KanjiAccuracy::css_class(segment.kanji_accuracy);
+                    write!(f, "<rt class=\"{class}\">{furigana}")?;
+                }
+                Some(furigana) => write!(f, "<rt>{furigana}")?,
+                None => write!(f, "<rt>")?,
             }
             write!(f, "</rt>")?;
         }
@@ -81,36 +245,488 @@ impl Display for Furigana<'_> {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct FuriganaSegment<'a> {
+    pub(crate) segment: &'a str,
+    pub(crate) furigana: Option<&'a str>,
+    pub(crate) reading: &'a str,
+    pub(crate) kanji_accuracy: Option<KanjiAccuracy>,
+    pub(crate) source: Option<usize>,
+}
+
+impl<'a> FuriganaSegment<'a> {
+    pub fn new(
+        segment: &'a str,
+        furigana: Option<&'a str>,
+        reading: &'a str,
+        kanji_accuracy: Option<KanjiAccuracy>,
+    ) -> Self {
+        Self {
+            segment,
+            furigana,
+            reading,
+            kanji_accuracy,
+            source: None,
+        }
+    }
+
+    fn from_node(node: &FuriganaNode<'a>) -> Self {
+        let word = node.segment.inner();
+        let furigana = if node.reading.is_empty() || kana_equivalent(word, node.reading) {
+            // no need for furigana here
+            None
+        } else {
+            Some(node.reading)
+        };
+        Self {
+            segment: word,
+            furigana,
+            reading: node.reading,
+            kanji_accuracy: node.kanji_accurate,
+            source: node.source,
+        }
+    }
+
+    /// Returns the byte range of the segment in the word it was mapped from,
+    /// or None if it doesn't point into the word, as with segments created with `FuriganaSegment::new`.
+    pub fn segment_range(&self, word: &str) -> Option<Range<usize>> {
+        utils::subslice_range(word, self.segment)
+    }
+
+    /// Returns the byte range of the segment's reading in the reading it was mapped from,
+    /// or None if it doesn't point into the reading, as with segments created with `FuriganaSegment::new`.
+    pub fn reading_range(&self, reading: &str) -> Option<Range<usize>> {
+        utils::subslice_range(reading, self.reading)
+    }
+
+    /// Sets the index of the `ReadingSource` that validated the reading.
+    pub fn with_source(self, source: Option<usize>) -> Self {
+        Self { source, ..self }
+    }
+
     /// A segment of the original word.
-    pub segment: &'a str,
+    pub fn segment(&self) -> &'a str {
+        self.segment
+    }
+
     /// The furigana corresponding to the segment, if any.
-    pub furigana: Option<&'a str>,
+    pub fn furigana(&self) -> Option<&'a str> {
+        self.furigana
+    }
+
+    /// The part of the reading that corresponds to the segment, even if the segment doesn't need furigana.
+    pub fn reading(&self) -> &'a str {
+        self.reading
+    }
+
+    /// The accuracy of the furigana according to known kanji readings. None when inapplicable, such as for kana segments.
+    pub fn kanji_accuracy(&self) -> Option<KanjiAccuracy> {
+        self.kanji_accuracy
+    }
+
+    /// The index of the `ReadingSource` that validated the reading, if any.
+    pub fn source(&self) -> Option<usize> {
+        self.source
+    }
+
+    /// Iterates over the individual kanji in the segment along with the part of the furigana that belongs to each.
+    /// Segments like 大人 from `map_naive` can contain several kanji,
+    /// in which case there's no way to know how the furigana is split between them and the readings are None.
+    pub fn kanji(&self) -> impl Iterator<Item = (&'a str, Option<&'a str>)> {
+        let segment = self.segment;
+        let single = segment.chars().filter(|c| utils::is_kanji(*c)).count() == 1;
+        let furigana = self.furigana.filter(|_| single);
+        segment
+            .char_indices()
+            .filter(|(_, c)| utils::is_kanji(*c))
+            .map(move |(idx, c)| (&segment[idx..idx + c.len_utf8()], furigana))
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The possible ways to map a reading to a word, stored as a flat list of nodes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FuriganaTree<'a> {
     pub word: &'a str,
     pub reading: &'a str,
+    /// All of the nodes in the tree. The extensions of each node are stored next to each other.
     pub nodes: Vec<FuriganaNode<'a>>,
+    /// The range of `nodes` that contains the first segments of the mappings.
+    pub roots: Range<usize>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+impl<'a> FuriganaTree<'a> {
+    /// Returns the nodes the mappings start from.
+    pub fn roots(&self) -> &[FuriganaNode<'a>] {
+        &self.nodes[self.roots.clone()]
+    }
+
+    /// Returns the nodes that may come after the given node.
+    pub fn extensions(&self, node: &FuriganaNode<'a>) -> &[FuriganaNode<'a>] {
+        &self.nodes[node.extensions.clone()]
+    }
+
+    /// Returns the number of mappings in the tree without flattening it.
+    pub fn count_mappings(&self) -> usize {
+        self.count_paths(self.roots())
+    }
+
+    /// Returns the mapping at the index in the order of `Furigana::from_tree`, without flattening the rest of the tree.
+    pub fn nth_mapping(&self, index: usize) -> Option<Furigana<'a>> {
+        self.mappings_range(index..index.saturating_add(1)).pop()
+    }
+
+    /// Returns the mappings in the range of indices in the order of `Furigana::from_tree`,
+    /// for paging through large numbers of mappings without flattening all of them.
+    /// Indices past the last mapping are ignored.
+    pub fn mappings_range(&self, range: Range<usize>) -> Vec<Furigana<'a>> {
+        let mut counts = vec![None; self.nodes.len()];
+        let total = self
+            .roots
+            .clone()
+            .map(|idx| self.count_through(idx, &mut counts))
+            .sum::<usize>();
+        let counts = counts
+            .into_iter()
+            .map(|count| count.unwrap_or_default())
+            .collect::<Vec<_>>();
+        (range.start..range.end.min(total))
+            .map(|index| self.mapping_at(&counts, index))
+            .collect()
+    }
+
+    /// Returns the readings each segment that needs furigana has in any of the mappings, grouped by where the segment starts in the word,
+    /// for showing the alternatives for each kanji separately instead of as whole mappings.
+    /// The groups are ordered by their position in the word and the candidates in each group from the highest score to the lowest.
+    pub fn segment_candidates(&self) -> Vec<Vec<SegmentCandidate<'a>>> {
+        let weights = AccuracyWeights::default();
+        let mut best = vec![None; self.nodes.len()];
+        for idx in self.roots.clone() {
+            self.best_through(idx, &weights, &mut best);
+        }
+        let best = best
+            .into_iter()
+            .map(|best| best.unwrap_or_default())
+            .collect::<Vec<_>>();
+        let mut counts = vec![None; self.nodes.len()];
+        let total = self
+            .roots
+            .clone()
+            .map(|idx| self.count_through(idx, &mut counts))
+            .sum::<usize>();
+        let counts = counts
+            .into_iter()
+            .map(|count| count.unwrap_or_default())
+            .collect::<Vec<_>>();
+
+        let mut candidates = vec![];
+        let totals = Totals {
+            best: &best,
+            counts: &counts,
+            total,
+        };
+        self.collect_candidates(self.roots.clone(), &weights, &totals, 0, 0, &mut candidates);
+        // the same reading can appear in several branches of the tree, such as after different readings of the previous kanji,
+        // so the candidates are grouped by reading first, keeping the best score which comes first in each group
+        candidates.sort_by(|l: &SegmentCandidate, r| {
+            l.start
+                .cmp(&r.start)
+                .then(l.segment.cmp(r.segment))
+                .then(l.reading.cmp(r.reading))
+                .then(r.score.cmp(&l.score))
+        });
+        candidates.dedup_by(|r, l| {
+            let same = l.start == r.start && l.segment == r.segment && l.reading == r.reading;
+            if same {
+                l.count += r.count;
+            }
+            same
+        });
+        candidates.sort_by(|l, r| {
+            l.start
+                .cmp(&r.start)
+                .then(r.score.cmp(&l.score))
+                .then(l.segment.cmp(r.segment))
+                .then(l.reading.cmp(r.reading))
+        });
+
+        let mut grouped: Vec<Vec<SegmentCandidate<'a>>> = vec![];
+        for candidate in candidates {
+            match grouped.last_mut() {
+                Some(group) if group[0].start == candidate.start => group.push(candidate),
+                _ => grouped.push(vec![candidate]),
+            }
+        }
+        grouped
+    }
+
+    // the accuracy of the best way to finish a mapping from the node, including the node, memoized in best
+    fn best_through(&self, idx: usize, weights: &AccuracyWeights, best: &mut [Option<i32>]) -> i32 {
+        if let Some(score) = best[idx] {
+            return score;
+        }
+        let node = &self.nodes[idx];
+        let rest = node
+            .extensions
+            .clone()
+            .map(|extension| self.best_through(extension, weights, best))
+            .max()
+            .unwrap_or_default();
+        let score = weights.weight(node.kanji_accurate) + rest;
+        best[idx] = Some(score);
+        score
+    }
+
+    // walks the tree with the accuracy of the segments before the nodes and the byte offset of the nodes in the word
+    fn collect_candidates(
+        &self,
+        nodes: Range<usize>,
+        weights: &AccuracyWeights,
+        totals: &Totals,
+        before: i32,
+        start: usize,
+        candidates: &mut Vec<SegmentCandidate<'a>>,
+    ) {
+        for idx in nodes {
+            let node = &self.nodes[idx];
+            let segment = FuriganaSegment::from_node(node);
+            if segment.furigana.is_some() {
+                candidates.push(SegmentCandidate {
+                    start,
+                    segment: segment.segment,
+                    reading: segment.reading,
+                    kanji_accuracy: segment.kanji_accuracy,
+                    score: before + totals.best[idx],
+                    count: totals.counts[idx],
+                    total: totals.total,
+                });
+            }
+            self.collect_candidates(
+                node.extensions.clone(),
+                weights,
+                totals,
+                before + weights.weight(node.kanji_accurate),
+                start + segment.segment.len(),
+                candidates,
+            );
+        }
+    }
+
+    // the number of mappings that go through the node, memoized in counts
+    fn count_through(&self, idx: usize, counts: &mut [Option<usize>]) -> usize {
+        if let Some(count) = counts[idx] {
+            return count;
+        }
+        let extensions = self.nodes[idx].extensions.clone();
+        let count = if extensions.is_empty() {
+            1
+        } else {
+            extensions
+                .map(|extension| self.count_through(extension, counts))
+                .sum()
+        };
+        counts[idx] = Some(count);
+        count
+    }
+
+    // walks down the tree, skipping over the nodes whose mappings all come before the index
+    fn mapping_at(&self, counts: &[usize], mut index: usize) -> Furigana<'a> {
+        let weights = AccuracyWeights::default();
+        let mut furigana = Segments::default();
+        let mut accuracy = 0;
+        let mut nodes = self.roots.clone();
+        while !nodes.is_empty() {
+            for idx in nodes.clone() {
+                if index < counts[idx] {
+                    let node = &self.nodes[idx];
+                    furigana.push(FuriganaSegment::from_node(node));
+                    accuracy += weights.weight(node.kanji_accurate);
+                    nodes = node.extensions.clone();
+                    break;
+                }
+                index -= counts[idx];
+            }
+        }
+        Furigana { furigana, accuracy }
+    }
+
+    fn count_paths(&self, nodes: &[FuriganaNode<'a>]) -> usize {
+        nodes
+            .iter()
+            .map(|node| {
+                let extensions = self.extensions(node);
+                if extensions.is_empty() {
+                    1
+                } else {
+                    self.count_paths(extensions)
+                }
+            })
+            .sum()
+    }
+}
+
+/// A reading of a segment that appears in at least one of the mappings in a `FuriganaTree`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SegmentCandidate<'a> {
+    /// The byte offset of the segment in the word.
+    pub start: usize,
+    pub segment: &'a str,
+    pub reading: &'a str,
+    pub kanji_accuracy: Option<KanjiAccuracy>,
+    /// The accuracy of the best mapping that reads the segment this way.
+    pub score: i32,
+    /// The number of mappings that read the segment this way.
+    pub count: usize,
+    /// The number of mappings in the tree.
+    pub total: usize,
+}
+
+impl SegmentCandidate<'_> {
+    /// The fraction of all mappings that read the segment this way, from 0.0 to 1.0.
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.count as f64 / self.total as f64
+        }
+    }
+}
+
+// what segment_candidates knows about each node, indexed like the nodes
+struct Totals<'t> {
+    best: &'t [i32],
+    counts: &'t [usize],
+    total: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FuriganaNode<'a> {
     /// The corresponding segment from the original word.
     pub segment: Segment<'a>,
     /// The reading of the segment.
     pub reading: &'a str,
-    /// Possible ways to continue after this point.
-    pub extensions: Vec<FuriganaNode<'a>>,
+    /// Possible ways to continue after this point, as a range of `FuriganaTree::nodes`.
+    pub extensions: Range<usize>,
     /// The accuracy of this reading according to known kanji readings. None when inapplicable, such as for kana segments.
     pub kanji_accurate: Option<KanjiAccuracy>,
+    /// The index of the `ReadingSource` that validated the reading, if any.
+    pub source: Option<usize>,
 }
 
 /// The accuracy of a given reading for a kanji.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[non_exhaustive]
 pub enum KanjiAccuracy {
     Accurate,
     AccurateWithRendaku,
     AccurateWithSokuonbin,
+    /// The reading turns the h sound of a known reading into a p sound after a reading that ends in っ,
+    /// like ぱい for 杯 (はい) in 一杯 (いっぱい), so both kanji changed at their boundary.
+    AccurateWithSokuonbinAndRendaku,
+    /// The reading lengthens the last vowel of a known reading, like にょう for 女 (にょ) in 女房.
+    /// Only used with `MapConfig::vowel_lengthening`.
+    AccurateWithLengthening,
+    /// The reading is a known irregular reading of the word as a whole, such as おとな for 大人,
+    /// rather than one built from the readings of its kanji.
+    Irregular,
+    /// The reading only matches when ignoring voicing, such as さんせん for 3000 (さんぜん).
+    VoicingMismatch,
     Inaccurate,
 }
+
+impl KanjiAccuracy {
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Accurate => "dictionary match",
+            Self::Irregular => "known irregular reading",
+            Self::AccurateWithRendaku => "dictionary match with rendaku",
+            Self::AccurateWithSokuonbin => "dictionary match with sokuonbin",
+            Self::AccurateWithSokuonbinAndRendaku => {
+                "dictionary match with rendaku after sokuonbin"
+            }
+            Self::AccurateWithLengthening => "dictionary match with vowel lengthening",
+            Self::VoicingMismatch => "voicing mismatch",
+            Self::Inaccurate => "not in dictionary",
+        }
+    }
+
+    // the name used in standoff annotations
+    pub(crate) fn key(self) -> &'static str {
+        match self {
+            Self::Accurate => "accurate",
+            Self::AccurateWithRendaku => "accurate_with_rendaku",
+            Self::AccurateWithSokuonbin => "accurate_with_sokuonbin",
+            Self::AccurateWithSokuonbinAndRendaku => "accurate_with_sokuonbin_and_rendaku",
+            Self::AccurateWithLengthening => "accurate_with_lengthening",
+            Self::Irregular => "irregular",
+            Self::VoicingMismatch => "voicing_mismatch",
+            Self::Inaccurate => "inaccurate",
+        }
+    }
+
+    // the class used by Furigana::to_html_with_classes
+    fn css_class(accuracy: Option<Self>) -> &'static str {
+        match accuracy {
+            Some(
+                Self::Accurate
+                | Self::AccurateWithRendaku
+                | Self::AccurateWithSokuonbin
+                | Self::AccurateWithSokuonbinAndRendaku
+                | Self::AccurateWithLengthening
+                | Self::Irregular,
+            ) => "furigana-accurate",
+            Some(Self::VoicingMismatch | Self::Inaccurate) | None => "furigana-guess",
+        }
+    }
+}
+
+/// How much each kind of reading affects the accuracy of a mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccuracyWeights {
+    pub accurate: i32,
+    pub irregular: i32,
+    pub accurate_with_rendaku: i32,
+    pub accurate_with_sokuonbin: i32,
+    pub accurate_with_sokuonbin_and_rendaku: i32,
+    pub accurate_with_lengthening: i32,
+    pub voicing_mismatch: i32,
+    pub inaccurate: i32,
+    /// Used for segments without reading information, such as kana.
+    pub unknown: i32,
+}
+
+impl AccuracyWeights {
+    /// Returns the weight of the given accuracy.
+    pub fn weight(&self, accuracy: Option<KanjiAccuracy>) -> i32 {
+        match accuracy {
+            Some(KanjiAccuracy::Accurate) => self.accurate,
+            Some(KanjiAccuracy::Irregular) => self.irregular,
+            Some(KanjiAccuracy::AccurateWithRendaku) => self.accurate_with_rendaku,
+            Some(KanjiAccuracy::AccurateWithSokuonbin) => self.accurate_with_sokuonbin,
+            Some(KanjiAccuracy::AccurateWithSokuonbinAndRendaku) => {
+                self.accurate_with_sokuonbin_and_rendaku
+            }
+            Some(KanjiAccuracy::AccurateWithLengthening) => self.accurate_with_lengthening,
+            Some(KanjiAccuracy::VoicingMismatch) => self.voicing_mismatch,
+            Some(KanjiAccuracy::Inaccurate) => self.inaccurate,
+            None => self.unknown,
+        }
+    }
+}
+
+impl Default for AccuracyWeights {
+    fn default() -> Self {
+        Self {
+            accurate: 2,
+            irregular: 2,
+            accurate_with_rendaku: 1,
+            accurate_with_sokuonbin: 1,
+            accurate_with_sokuonbin_and_rendaku: 1,
+            accurate_with_lengthening: 1,
+            voicing_mismatch: -1,
+            inaccurate: -2,
+            unknown: 0,
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! Contains a generator of readings for words that don't have one.
+
+use crate::{furigana::AccuracyWeights, irregular, utils};
+use std::collections::HashMap;
+
+// a possible reading of a part of the word and how plausible it is
+type Candidate = (String, i32);
+
+/// Generates plausible readings for the word by combining the readings of its kanji,
+/// including their variants with rendaku and sokuonbin, for when no reading is available at all.
+/// Returns at most `limit` readings in hiragana along with their plausibility, from the most plausible to the least.
+/// The plausibility is scored like the accuracy of a mapping, so readings made of the kanji's own readings rank above their variants.
+/// Returns nothing if the word contains something other than kana and kanji, or kanji without readings.
+///
+/// Kana in the word are kept as they are, and kanji readings in the KANJIDIC format such as まな.ぶ
+/// are only used when the word continues with their okurigana, like in 学ぶ.
+/// Combinations that can't make it into the most plausible readings are skipped early, so compounds of several kanji stay fast.
+pub fn generate_readings(
+    word: &str,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+    limit: usize,
+) -> Vec<(String, i32)> {
+    let weights = AccuracyWeights::default();
+    let chars = word.chars().collect::<Vec<_>>();
+    let kanji_count = chars.iter().filter(|c| utils::is_kanji(**c)).count();
+
+    let mut top = TopReadings {
+        readings: vec![],
+        limit,
+    };
+    // readings of the whole word, like おとな for 大人, count as if each kanji was read accurately
+    if chars.len() > 1 {
+        let whole_readings = kanji_to_readings
+            .get(word)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .chain(
+                irregular::readings(word)
+                    .unwrap_or_default()
+                    .iter()
+                    .copied(),
+            );
+        for reading in whole_readings.filter_map(utils::normalize_reading) {
+            top.insert(reading, weights.accurate * kanji_count as i32);
+        }
+    }
+
+    let parts = chars
+        .iter()
+        .enumerate()
+        .map(|(idx, c)| part_candidates(&chars, idx, *c, kanji_to_readings, &weights))
+        .collect::<Option<Vec<_>>>();
+    if let Some(parts) = parts {
+        // the highest score the parts from each index onwards can add
+        let mut best_rest = vec![0; parts.len() + 1];
+        for (idx, candidates) in parts.iter().enumerate().rev() {
+            let best = candidates
+                .iter()
+                .map(|(_, score)| *score)
+                .max()
+                .unwrap_or_default();
+            best_rest[idx] = best_rest[idx + 1] + best;
+        }
+        combine(&parts, &best_rest, String::new(), 0, &mut top);
+    }
+    top.readings
+}
+
+// the most plausible readings found so far, from the most plausible to the least
+struct TopReadings {
+    readings: Vec<Candidate>,
+    limit: usize,
+}
+
+impl TopReadings {
+    // checks whether a reading with the score could still be added
+    fn accepts(&self, score: i32) -> bool {
+        self.readings.len() < self.limit
+            || self
+                .readings
+                .last()
+                .map_or(false, |(_, worst)| score > *worst)
+    }
+
+    // adds the reading after the ones that are at least as plausible, keeping the best score of each reading
+    fn insert(&mut self, reading: String, score: i32) {
+        if let Some(idx) = self
+            .readings
+            .iter()
+            .position(|(existing, _)| *existing == reading)
+        {
+            if self.readings[idx].1 >= score {
+                return;
+            }
+            self.readings.remove(idx);
+        }
+        if !self.accepts(score) {
+            return;
+        }
+        let idx = self
+            .readings
+            .iter()
+            .position(|(_, existing)| *existing < score)
+            .unwrap_or(self.readings.len());
+        self.readings.insert(idx, (reading, score));
+        self.readings.truncate(self.limit);
+    }
+}
+
+// the possible readings of the char at the index from the most plausible to the least, or None if it can't be read
+fn part_candidates(
+    chars: &[char],
+    idx: usize,
+    c: char,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+    weights: &AccuracyWeights,
+) -> Option<Vec<Candidate>> {
+    if utils::is_kana(c) {
+        return Some(vec![(crate::to_hiragana(c).to_string(), 0)]);
+    }
+    if !utils::is_kanji(c) {
+        return None;
+    }
+
+    let following_kana = chars[idx + 1..]
+        .iter()
+        .take_while(|c| utils::is_kana(**c))
+        .map(|c| crate::to_hiragana(*c))
+        .collect::<String>();
+    let readings = kanji_to_readings
+        .get(c.encode_utf8(&mut [0; 4]) as &str)?
+        .iter()
+        .filter(|reading| okurigana_matches(reading, &following_kana))
+        .filter_map(|reading| utils::normalize_reading(reading))
+        .collect::<Vec<_>>();
+    let can_be_rendaku = idx > 0;
+    let can_be_sokuonbin = chars
+        .get(idx + 1)
+        .map_or(false, |next| utils::is_kanji(*next));
+    let mut candidates = vec![];
+    for reading in &readings {
+        candidates.push((reading.clone(), weights.accurate));
+    }
+    for reading in &readings {
+        let mut reading_chars = reading.chars();
+        let first = reading_chars.next();
+        // by Lyman's law, readings that already contain a voiced kana don't undergo rendaku
+        let voiced = first
+            .and_then(utils::voiced)
+            .filter(|_| can_be_rendaku && !reading_chars.any(utils::is_voiced_obstruent));
+        if let (Some(first), Some(voiced)) = (first, voiced) {
+            let rendaku = format!("{voiced}{}", &reading[first.len_utf8()..]);
+            candidates.push((rendaku, weights.accurate_with_rendaku));
+        }
+        if can_be_sokuonbin {
+            if let Some(last) = reading
+                .chars()
+                .last()
+                .filter(|last| "くちつき".contains(*last))
+            {
+                let sokuonbin = format!("{}っ", &reading[..reading.len() - last.len_utf8()]);
+                candidates.push((sokuonbin, weights.accurate_with_sokuonbin));
+            }
+        }
+    }
+    candidates.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    if candidates.is_empty() {
+        None
+    } else {
+        Some(candidates)
+    }
+}
+
+// checks whether the word continues with the okurigana of a reading like た.べる,
+// ignoring the last kana of the okurigana as it changes when the word is conjugated, like in 食べた
+fn okurigana_matches(reading: &str, following_kana: &str) -> bool {
+    let okurigana = match reading.split_once('.') {
+        Some((_, okurigana)) => okurigana,
+        None => return true,
+    };
+    let okurigana = okurigana
+        .chars()
+        .map(crate::to_hiragana)
+        .collect::<String>();
+    let stem_len = okurigana.chars().count().saturating_sub(1);
+    let stem = okurigana.chars().take(stem_len).collect::<String>();
+    !following_kana.is_empty() && following_kana.starts_with(&stem)
+}
+
+fn combine(
+    parts: &[Vec<Candidate>],
+    best_rest: &[i32],
+    reading: String,
+    score: i32,
+    top: &mut TopReadings,
+) {
+    let (first, rest) = match parts.split_first() {
+        Some(split) => split,
+        None => {
+            top.insert(reading, score);
+            return;
+        }
+    };
+    for (part, part_score) in first {
+        let score = score + part_score;
+        // the candidates are sorted, so none of the rest can do better either
+        if !top.accepts(score + best_rest[1]) {
+            break;
+        }
+        combine(rest, &best_rest[1..], reading.clone() + part, score, top);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generates_readings() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert(
+            "学".to_string(),
+            vec!["ガク".to_string(), "まな.ぶ".to_string()],
+        );
+        kanji_to_readings.insert("校".to_string(), vec!["コウ".to_string()]);
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        kanji_to_readings.insert(
+            "語".to_string(),
+            vec!["かたり".to_string(), "ゴ".to_string()],
+        );
+
+        let readings = generate_readings("学校", &kanji_to_readings, 10);
+        assert_eq!(readings[0], ("がくこう".to_string(), 4));
+        assert!(readings.contains(&("がっこう".to_string(), 3)));
+        // まな.ぶ needs okurigana
+        assert!(!readings
+            .iter()
+            .any(|(reading, _)| reading.starts_with("まな")));
+
+        let readings = generate_readings("物語", &kanji_to_readings, 2);
+        assert_eq!(
+            readings,
+            vec![("ものかたり".to_string(), 4), ("ものご".to_string(), 4)]
+        );
+        let readings = generate_readings("物語", &kanji_to_readings, 10);
+        assert!(readings.contains(&("ものがたり".to_string(), 3)));
+
+        let readings = generate_readings("大人", &kanji_to_readings, 10);
+        assert_eq!(readings, vec![("おとな".to_string(), 4)]);
+        assert!(generate_readings("物A", &kanji_to_readings, 10).is_empty());
+    }
+
+    #[test]
+    fn prunes_with_okurigana() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert(
+            "学".to_string(),
+            vec!["ガク".to_string(), "まな.ぶ".to_string()],
+        );
+        kanji_to_readings.insert(
+            "食".to_string(),
+            vec!["た.べる".to_string(), "く.う".to_string()],
+        );
+        let readings = generate_readings("学ぶ", &kanji_to_readings, 10);
+        assert_eq!(
+            readings[..2],
+            [("がくぶ".to_string(), 2), ("まなぶ".to_string(), 2)]
+        );
+        let readings = generate_readings("学食", &kanji_to_readings, 10);
+        assert_eq!(readings, vec![]);
+        let readings = generate_readings("食べた", &kanji_to_readings, 10);
+        // the last kana of the okurigana may change, so く.う can't be ruled out
+        assert_eq!(
+            readings,
+            vec![("たべた".to_string(), 2), ("くべた".to_string(), 2)]
+        );
+
+        // many kanji with many readings each still only keep the best few
+        let many = ('あ'..='と').map(|c| format!("よ{c}")).collect::<Vec<_>>();
+        for kanji in ["一", "二", "三", "四"] {
+            kanji_to_readings.insert(kanji.to_string(), many.clone());
+        }
+        let readings = generate_readings("一二三四", &kanji_to_readings, 3);
+        assert_eq!(readings.len(), 3);
+        assert!(readings.iter().all(|(_, score)| *score == 8));
+    }
+}
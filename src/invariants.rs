@@ -0,0 +1,192 @@
+//! Contains checks for the properties every mapping should have, for use in tests.
+
+use crate::{
+    config::{MapConfig, WhitespacePolicy},
+    furigana::Furigana,
+    kana_matches,
+    segmentation::{self, Char},
+    utils,
+};
+use std::{error::Error, fmt::Display};
+
+/// A property that a mapping didn't have.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum InvariantError {
+    /// The segments don't add up to the text.
+    Text { segments: String },
+    /// The readings of the segments don't add up to the reading.
+    Reading { readings: String },
+    /// The furigana of the segment at the index is different from its reading.
+    Furigana { index: usize },
+    /// The segment at the index is kana but its reading isn't the same kana.
+    Kana { index: usize },
+}
+
+impl Display for InvariantError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text { segments } => write!(f, "the segments add up to {segments}"),
+            Self::Reading { readings } => write!(f, "the readings add up to {readings}"),
+            Self::Furigana { index } => {
+                write!(
+                    f,
+                    "the furigana of segment {index} differs from its reading"
+                )
+            }
+            Self::Kana { index } => write!(f, "segment {index} is kana with a different reading"),
+        }
+    }
+}
+
+impl Error for InvariantError {}
+
+/// Checks that the mapping is consistent with the text and reading it was created from:
+/// the segments and their readings must add up to the text and reading,
+/// the furigana of each segment must be its reading,
+/// and kana segments must be read as written, ignoring the difference between hiragana and katakana,
+/// or not at all when they are okurigana left out of the reading.
+/// ヶ is not held to this since it can stand for a kanji, as in 関ヶ原.
+///
+/// Every mapping returned by this crate with the default options should pass the check, which makes it useful for property based tests.
+/// Mappings created with other options are checked with `check_invariants_with`.
+pub fn check_invariants(
+    furigana: &Furigana,
+    text: &str,
+    reading: &str,
+) -> Result<(), InvariantError> {
+    check_invariants_with(furigana, text, reading, &MapConfig::default())
+}
+
+/// Works like `check_invariants` for mappings created with the given options,
+/// comparing kana segments to their readings after repairing them the way the options do,
+/// and not counting a ー that `MapConfig::dashes_by_context` reads as 一 as kana.
+/// Every mapping returned by this crate should pass the check with the options it was created with.
+pub fn check_invariants_with(
+    furigana: &Furigana,
+    text: &str,
+    reading: &str,
+    config: &MapConfig,
+) -> Result<(), InvariantError> {
+    let segments = furigana
+        .furigana
+        .iter()
+        .map(|segment| segment.segment)
+        .collect::<String>();
+    if segments != text {
+        return Err(InvariantError::Text { segments });
+    }
+    let readings = furigana
+        .furigana
+        .iter()
+        .map(|segment| segment.reading)
+        .collect::<String>();
+    if readings != reading {
+        return Err(InvariantError::Reading { readings });
+    }
+
+    // the mapper classifies dashes within the word without the whitespace around it
+    let word = match config.whitespace_policy {
+        WhitespacePolicy::Keep => 0..text.len(),
+        WhitespacePolicy::Trim | WhitespacePolicy::Skip => {
+            let start = text.len() - text.trim_start().len();
+            start..start + text.trim().len()
+        }
+    };
+    let is_kana_at = |idx: usize, c: char| {
+        if !utils::is_kana(c) {
+            return false;
+        }
+        if !config.dashes_by_context || !word.contains(&idx) {
+            return true;
+        }
+        let previous = text[word.start..idx].chars().next_back();
+        let next = text[idx + c.len_utf8()..word.end].chars().next();
+        segmentation::classify_in_context(previous, c, next) == Char::Kana
+    };
+    let mut start = 0;
+    for (index, segment) in furigana.furigana.iter().enumerate() {
+        if segment
+            .furigana
+            .map_or(false, |furigana| furigana != segment.reading)
+        {
+            return Err(InvariantError::Furigana { index });
+        }
+        if !segment.segment.is_empty()
+            && segment
+                .segment
+                .char_indices()
+                .all(|(idx, c)| is_kana_at(start + idx, c))
+            && segment.segment != "ヶ"
+            && !segment.reading.is_empty()
+            && !kana_matches(segment.reading, segment.segment, config)
+        {
+            return Err(InvariantError::Kana { index });
+        }
+        start += segment.segment.len();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FuriganaSegment;
+    use std::collections::HashMap;
+
+    #[test]
+    fn checks_invariants() {
+        for (word, reading) in [
+            ("物の怪", "もののけ"),
+            ("大人しい", "おとなしい"),
+            ("3000円", "さんぜんえん"),
+            ("Aランク", "エーランク"),
+            ("コーヒー", "こうひい"),
+        ] {
+            let mappings = crate::map_naive(word, reading);
+            assert!(!mappings.is_empty(), "{word}");
+            for mapping in &mappings {
+                assert_eq!(check_invariants(mapping, word, reading), Ok(()), "{word}");
+            }
+        }
+
+        let mapping = Furigana::new(
+            vec![
+                FuriganaSegment::new("物", Some("もの"), "もの", None),
+                FuriganaSegment::new("の", None, "が", None),
+            ],
+            0,
+        );
+        assert_eq!(
+            check_invariants(&mapping, "物の", "ものが"),
+            Err(InvariantError::Kana { index: 1 })
+        );
+        assert_eq!(
+            check_invariants(&mapping, "物の怪", "ものが"),
+            Err(InvariantError::Text {
+                segments: "物の".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn checks_invariants_with_config() {
+        let config = MapConfig::builder().dashes_by_context(true).build();
+        let kanji_to_readings = HashMap::new();
+        for (word, reading) in [
+            ("ー人", "いちにん"),
+            ("ー人", "゛ー"),
+            ("ラ一メン", "らーめん"),
+        ] {
+            let mappings = crate::map_with(word, reading, &kanji_to_readings, &config);
+            assert!(!mappings.is_empty(), "{word}");
+            for mapping in &mappings {
+                assert_eq!(
+                    check_invariants_with(mapping, word, reading, &config),
+                    Ok(()),
+                    "{word}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Contains readings for words that can't be mapped to their kanji individually.
+
+/// Common words whose reading belongs to the word as a whole (jukujikun and other irregular readings).
+const IRREGULAR_READINGS: &[(&str, &[&str])] = &[
+    ("一昨日", &["おととい", "いっさくじつ"]),
+    ("一人", &["ひとり"]),
+    ("一日", &["ついたち"]),
+    ("七夕", &["たなばた"]),
+    ("上手", &["じょうず"]),
+    ("下手", &["へた"]),
+    ("二人", &["ふたり"]),
+    ("二十日", &["はつか"]),
+    ("二十歳", &["はたち"]),
+    ("今年", &["ことし"]),
+    ("今日", &["きょう"]),
+    ("今朝", &["けさ"]),
+    ("八百屋", &["やおや"]),
+    ("博士", &["はかせ"]),
+    ("土産", &["みやげ"]),
+    ("大人", &["おとな"]),
+    ("大和", &["やまと"]),
+    ("太刀", &["たち"]),
+    ("息子", &["むすこ"]),
+    ("日和", &["ひより"]),
+    ("明日", &["あした", "あす"]),
+    ("明後日", &["あさって"]),
+    ("昨日", &["きのう"]),
+    ("時計", &["とけい"]),
+    ("景色", &["けしき"]),
+    ("果物", &["くだもの"]),
+    ("梅雨", &["つゆ"]),
+    ("浴衣", &["ゆかた"]),
+    ("海老", &["えび"]),
+    ("清水", &["しみず"]),
+    ("田舎", &["いなか"]),
+    ("相撲", &["すもう"]),
+    ("眼鏡", &["めがね"]),
+    ("真面目", &["まじめ"]),
+    ("素人", &["しろうと"]),
+    ("紅葉", &["もみじ"]),
+    ("部屋", &["へや"]),
+    ("迷子", &["まいご"]),
+    ("雪崩", &["なだれ"]),
+];
+
+/// Returns the built-in irregular readings for the given word, if any.
+pub fn readings(word: &str) -> Option<&'static [&'static str]> {
+    IRREGULAR_READINGS
+        .iter()
+        .find(|(irregular, _)| *irregular == word)
+        .map(|(_, readings)| *readings)
+}
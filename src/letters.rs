@@ -0,0 +1,112 @@
+//! Contains the readings of letters like the A in Aランク.
+
+use std::collections::HashMap;
+
+const ENGLISH_READINGS: &[(char, &str)] = &[
+    ('A', "エー"),
+    ('B', "ビー"),
+    ('C', "シー"),
+    ('D', "ディー"),
+    ('E', "イー"),
+    ('F', "エフ"),
+    ('G', "ギー"),
+    ('H', "エイチ"),
+    ('I', "アイ"),
+    ('J', "ジェー"),
+    ('K', "ケー"),
+    ('L', "エル"),
+    ('M', "エム"),
+    ('N', "エヌ"),
+    ('O', "オー"),
+    ('P', "ピー"),
+    ('Q', "キュー"),
+    ('R', "アール"),
+    ('S', "エス"),
+    ('T', "ティー"),
+    ('U', "ユー"),
+    ('V', "ブイ"),
+    ('W', "ダブルユー"),
+    ('X', "エックス"),
+    ('Y', "ワイ"),
+    ('Z', "ゼット"),
+];
+
+/// Table of the readings of letters.
+/// The default table contains the English readings of A to Z.
+/// Lower case and full-width letters share the readings of their upper case counterparts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LetterReadings {
+    readings: HashMap<char, Vec<String>>,
+}
+
+impl LetterReadings {
+    /// Creates a table without any readings.
+    pub fn empty() -> Self {
+        Self {
+            readings: HashMap::new(),
+        }
+    }
+
+    /// Adds a reading for the letter, keeping its existing readings.
+    pub fn insert(&mut self, letter: char, reading: impl Into<String>) {
+        let reading = reading.into();
+        let readings = self.readings.entry(normalize(letter)).or_default();
+        if !readings.contains(&reading) {
+            readings.push(reading);
+        }
+    }
+
+    /// Replaces the readings of the letter.
+    pub fn set(&mut self, letter: char, readings: Vec<String>) {
+        self.readings.insert(normalize(letter), readings);
+    }
+
+    /// Returns the readings of the letter.
+    pub fn get(&self, letter: char) -> &[String] {
+        self.readings
+            .get(&normalize(letter))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+impl Default for LetterReadings {
+    fn default() -> Self {
+        let mut letter_readings = Self::empty();
+        for (letter, reading) in ENGLISH_READINGS {
+            letter_readings.insert(*letter, *reading);
+        }
+        letter_readings
+    }
+}
+
+// full-width and lower case letters are looked up as their upper case counterparts
+fn normalize(letter: char) -> char {
+    let letter = match letter {
+        'Ａ'..='Ｚ' | 'ａ'..='ｚ' => {
+            char::from_u32(letter as u32 - 'Ａ' as u32 + 'A' as u32).unwrap_or(letter)
+        }
+        _ => letter,
+    };
+    let mut upper = letter.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(upper), None) => upper,
+        _ => letter,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn looks_up_letters() {
+        let mut letter_readings = LetterReadings::default();
+        assert_eq!(letter_readings.get('g'), ["ギー"]);
+        assert_eq!(letter_readings.get('ｇ'), ["ギー"]);
+        letter_readings.insert('G', "ヂー");
+        assert_eq!(letter_readings.get('Ｇ'), ["ギー", "ヂー"]);
+        letter_readings.set('α', vec!["アルファ".to_string()]);
+        assert_eq!(letter_readings.get('α'), ["アルファ"]);
+        assert!(letter_readings.get('ア').is_empty());
+    }
+}
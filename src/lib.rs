@@ -1,47 +1,85 @@
 #![doc = include_str!("../README.md")]
 
+mod affixes;
+mod annotate;
+#[cfg(feature = "async")]
+mod async_mapper;
+mod calibrate;
+mod config;
+mod corpus;
+mod counters;
+mod dict;
+mod frequency;
 mod furigana;
+mod generate;
+mod invariants;
+mod irregular;
+mod letters;
+mod mapper;
+mod mappings;
+#[cfg(feature = "model")]
+mod model;
+pub mod numbers;
+#[cfg(feature = "overrides")]
+mod overrides;
+mod owned;
+mod pipeline;
+mod render;
+mod rules;
 mod segmentation;
+mod segments;
+mod sources;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 mod utils;
+mod validation;
 
-pub use self::furigana::{Furigana, FuriganaNode, FuriganaSegment};
-use self::furigana::{FuriganaTree, KanjiAccuracy};
-use segmentation::{CoarseSegmentation, FineSegmentation, Segment};
-use std::{collections::HashMap, iter::Peekable};
+#[cfg(feature = "async")]
+pub use self::async_mapper::AsyncMapper;
+#[cfg(feature = "model")]
+pub use self::model::map_naive_ranked;
+#[cfg(feature = "overrides")]
+pub use self::overrides::{OverrideError, OverrideErrorKind, Overrides};
+pub use self::{
+    annotate::{
+        annotate_corpus, annotate_corpus_parallel, annotate_corpus_report, annotate_plain,
+        annotate_plain_with, annotate_standoff, map_partially_annotated, standoff_to_json,
+        AnnotationReport, Failure, LowConfidence, StandoffAnnotation,
+    },
+    calibrate::{calibrate, Calibration},
+    config::{MapConfig, MapConfigBuilder, NumberStyle, OtherPolicy, WhitespacePolicy},
+    corpus::{AnnotatedStream, CorpusAnnotator, Throughput},
+    dict::{Conflict, DictChange, KanjiDict, MergeStrategy},
+    frequency::FrequencyList,
+    furigana::{
+        AccuracyWeights, Furigana, FuriganaNode, FuriganaSegment, FuriganaTree, KanjiAccuracy,
+        SegmentCandidate,
+    },
+    generate::generate_readings,
+    invariants::{check_invariants, check_invariants_with, InvariantError},
+    letters::LetterReadings,
+    mapper::Mapper,
+    mappings::Mappings,
+    owned::{OwnedFurigana, OwnedFuriganaSegment},
+    pipeline::{MapPipeline, StagedMappings},
+    render::RenderOptions,
+    rules::{ContextRule, Neighbor},
+    segmentation::{
+        CoarseSegmentation, FineSegmentation, ReadingSegmentation, Segment, SegmentKind,
+        SegmentationOptions,
+    },
+    sources::ReadingSource,
+    validation::{why_not, Violation},
+};
+use segmentation::Char;
+use std::{borrow::Cow, collections::HashMap, iter::Peekable, ops::Range};
 
 /// Returns a list of all possible ways to map the reading to the word, matching the kana in the reading to the ones in the word.
+/// Kana are matched regardless of whether they're written in hiragana or katakana on either side,
+/// so okurigana written in katakana like in 食ベル match the reading たべる.
 /// Returns an empty list if the segments and readings are impossible to match.
-pub fn map_naive<'a>(word: &'a str, reading: &'a str) -> Vec<Furigana<'a>> {
-    // no need to do work for kana words
-    if word.chars().all(utils::is_kana) {
-        return vec![Furigana {
-            accuracy: 1,
-            furigana: vec![FuriganaSegment {
-                segment: word,
-                furigana: None,
-            }],
-        }];
-    }
-    // no need to do work for single character words
-    if word.chars().count() == 1 {
-        return vec![Furigana {
-            accuracy: 1,
-            furigana: vec![FuriganaSegment {
-                segment: word,
-                furigana: Some(reading),
-            }],
-        }];
-    }
-
-    // need kanji information to assign readings to each individual kanji, so use coarse segmentation here
-    let segments = CoarseSegmentation::new(word);
-    let nodes = map_inner(segments.peekable(), reading, None, None, false).unwrap_or_default();
-    let tree = FuriganaTree {
-        word,
-        reading,
-        nodes,
-    };
-    Furigana::from_tree(&tree)
+pub fn map_naive<'a>(word: &'a str, reading: &'a str) -> Mappings<'a> {
+    map_configured(word, reading, None, &MapConfig::default())
 }
 
 /// Returns a list of all possible ways to map the reading to the word, matching the kana in the reading to the ones in the word.
@@ -51,188 +89,1055 @@ pub fn map<'a>(
     word: &'a str,
     reading: &'a str,
     kanji_to_readings: &HashMap<String, Vec<String>>,
-) -> Vec<Furigana<'a>> {
+) -> Mappings<'a> {
+    map_with(word, reading, kanji_to_readings, &MapConfig::default())
+}
+
+/// Maps each candidate reading to the word and returns the candidates ranked by the accuracy of their best mapping,
+/// for choosing between the kana forms a dictionary like JMdict lists for a word.
+/// Candidates that can't be mapped to the word at all come last with None. Candidates that rank the same keep their order.
+pub fn best_reading_for<'r>(
+    word: &str,
+    candidate_readings: &[&'r str],
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+) -> Vec<(&'r str, Option<i32>)> {
+    let mut ranked = candidate_readings
+        .iter()
+        .map(|reading| {
+            let accuracy = map(word, reading, kanji_to_readings)
+                .best()
+                .map(|best| best.accuracy);
+            (*reading, accuracy)
+        })
+        .collect::<Vec<_>>();
+    // None sorts before any accuracy, so reversing puts it last
+    ranked.sort_by_key(|(_, accuracy)| std::cmp::Reverse(*accuracy));
+    ranked
+}
+
+/// Maps each of the readings to the word and merges the results, for words listed with several kana forms such as 鼻血 (はなぢ, はなじ).
+/// Returns each mapping with the reading it came from, from the most accurate to the least accurate.
+/// Mappings whose furigana only differ in hiragana and katakana from a more accurate one, or from one for an earlier reading, are left out.
+pub fn map_multi<'a>(
+    word: &'a str,
+    readings: &[&'a str],
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+) -> Vec<(&'a str, Furigana<'a>)> {
+    let mut merged = readings
+        .iter()
+        .flat_map(|reading| {
+            map(word, reading, kanji_to_readings)
+                .into_vec()
+                .into_iter()
+                .map(move |mapping| (*reading, mapping))
+        })
+        .collect::<Vec<_>>();
+    // the sort is stable, so the readings keep their order when the accuracy is the same
+    merged.sort_by_key(|(_, mapping)| std::cmp::Reverse(mapping.accuracy));
+    let mut deduped: Vec<(&'a str, Furigana<'a>)> = vec![];
+    for (reading, mapping) in merged {
+        if !deduped
+            .iter()
+            .any(|(_, kept)| mappings::same_in_kana(kept, &mapping))
+        {
+            deduped.push((reading, mapping));
+        }
+    }
+    deduped
+}
+
+/// Works like `map`, ranking the mappings by the scores `rerank` gives them instead of by their accuracy,
+/// for applying an external model such as a language model to the candidates.
+/// Returns the mappings with their scores from the highest to the lowest score.
+/// Mappings with the same score are ranked by accuracy, and NaN scores rank last.
+pub fn map_reranked<'a>(
+    word: &'a str,
+    reading: &'a str,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+    rerank: impl Fn(&Furigana) -> f64,
+) -> Vec<(Furigana<'a>, f64)> {
+    let mut reranked = map(word, reading, kanji_to_readings)
+        .iter_ranked()
+        .map(|mapping| {
+            let score = rerank(mapping);
+            let score = if score.is_nan() {
+                f64::NEG_INFINITY
+            } else {
+                score
+            };
+            (mapping.clone(), score)
+        })
+        .collect::<Vec<_>>();
+    // the sort is stable and no score is NaN, so ties keep their ranking by accuracy
+    reranked.sort_by(|(_, l), (_, r)| r.partial_cmp(l).unwrap_or(std::cmp::Ordering::Equal));
+    reranked
+}
+
+/// Works like `map`, using the given options.
+pub fn map_with<'a>(
+    word: &'a str,
+    reading: &'a str,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+    config: &MapConfig,
+) -> Mappings<'a> {
+    let sources = [ReadingSource::new("kanji_to_readings", kanji_to_readings)];
+    map_configured(word, reading, Some(&sources), config)
+}
+
+/// Works like `map_with`, grading the readings with several sources of kanji readings in order of priority.
+/// Each segment records the index of the source that validated its reading in `FuriganaSegment::source`.
+pub fn map_with_sources<'a>(
+    word: &'a str,
+    reading: &'a str,
+    sources: &[ReadingSource],
+    config: &MapConfig,
+) -> Mappings<'a> {
+    map_configured(word, reading, Some(sources), config)
+}
+
+// all of the public mapping functions end up here
+fn map_configured<'a>(
+    word: &'a str,
+    reading: &'a str,
+    sources: Option<&[ReadingSource]>,
+    config: &MapConfig,
+) -> Mappings<'a> {
+    let mappings = match config.whitespace_policy {
+        WhitespacePolicy::Keep => map_untrimmed(word, reading, sources, config),
+        WhitespacePolicy::Trim | WhitespacePolicy::Skip => {
+            map_trimmed(word, reading, sources, config)
+        }
+    };
+    debug_assert!(
+        mappings.iter().all(|mapping| mapping.points_into(word, reading)),
+        "the mappings of {word} ({reading}) contain strings that aren't part of the word and reading"
+    );
+    mappings
+}
+
+// maps the word without the whitespace around it and the reading,
+// adding the whitespace back as segments without furigana so that the mappings still cover the whole input
+fn map_trimmed<'a>(
+    word: &'a str,
+    reading: &'a str,
+    sources: Option<&[ReadingSource]>,
+    config: &MapConfig,
+) -> Mappings<'a> {
+    let (word_start, trimmed_word, word_end) = split_whitespace(word);
+    let (reading_start, trimmed_reading, reading_end) = split_whitespace(reading);
+    let whitespace = |segment, reading| FuriganaSegment {
+        segment,
+        furigana: None,
+        reading,
+        kanji_accuracy: None,
+        source: None,
+    };
+    let mappings = map_untrimmed(trimmed_word, trimmed_reading, sources, config)
+        .into_iter()
+        .map(|mut mapping| {
+            if !word_start.is_empty() || !reading_start.is_empty() {
+                mapping
+                    .furigana
+                    .insert(0, whitespace(word_start, reading_start));
+            }
+            if !word_end.is_empty() || !reading_end.is_empty() {
+                mapping.furigana.push(whitespace(word_end, reading_end));
+            }
+            mapping
+        })
+        .collect();
+    Mappings::new(mappings)
+}
+
+// splits the string into the whitespace at its start, the rest of the string and the whitespace at its end
+fn split_whitespace(s: &str) -> (&str, &str, &str) {
+    let trimmed_start = s.trim_start();
+    let trimmed = trimmed_start.trim_end();
+    (
+        &s[..s.len() - trimmed_start.len()],
+        trimmed,
+        &trimmed_start[trimmed.len()..],
+    )
+}
+
+fn map_untrimmed<'a>(
+    word: &'a str,
+    reading: &'a str,
+    sources: Option<&[ReadingSource]>,
+    config: &MapConfig,
+) -> Mappings<'a> {
     // no need to do work for kana words
-    if word.chars().all(utils::is_kana) {
-        return vec![Furigana {
+    // callers that only need to know this can use is_kana_only and is_transcription to skip creating the mapping
+    if is_kana_only(word) {
+        if !kana_matches(reading, word, config) {
+            return Mappings::default();
+        }
+        return Mappings::new(vec![Furigana {
             accuracy: 1,
             furigana: vec![FuriganaSegment {
                 segment: word,
                 furigana: None,
-            }],
-        }];
+                reading,
+                kanji_accuracy: None,
+                source: None,
+            }]
+            .into(),
+        }]);
     }
     // no need to do work for single character words
     if word.chars().count() == 1 {
-        return vec![Furigana {
+        return Mappings::new(vec![Furigana {
             accuracy: 1,
             furigana: vec![FuriganaSegment {
                 segment: word,
                 furigana: Some(reading),
-            }],
-        }];
+                reading,
+                kanji_accuracy: None,
+                source: None,
+            }]
+            .into(),
+        }]);
+    }
+
+    let tree = search(word, reading, sources, config);
+    Mappings::new(Furigana::from_tree(&tree))
+}
+
+/// The circumstances of a kanji that affect which readings are accepted for it by `candidate_readings`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CandidateFlags {
+    /// The kanji comes after another part of a compound, so its reading may start with a voiced kana, like the が in 物語.
+    /// Even then, readings that already contain a voiced kana are only graded as a voicing mismatch, following Lyman's law.
+    pub can_be_rendaku: bool,
+    /// The kanji is followed by something, so its reading may end in っ, like the がっ in 学校.
+    pub can_be_sokuonbin: bool,
+    /// Same as `MapConfig::merge_yotsugana`.
+    pub merge_yotsugana: bool,
+    /// Same as `MapConfig::vowel_lengthening`.
+    pub vowel_lengthening: bool,
+}
+
+/// Returns each reading the engine would try for the kanji at the start of the remaining reading,
+/// from shortest to longest, along with the accuracy it would give to the mapping.
+/// Like the engine, only readings made of whole mora are listed, so none split a small kana like the ょ in きょう
+/// or start with っ.
+/// Readings that only match the dictionary with rendaku or sokuonbin are graded as such.
+/// The accuracy is None when the dictionary has no readings for the kanji.
+/// Useful for showing what was considered when debugging a mapping.
+pub fn candidate_readings<'a>(
+    kanji: &str,
+    remaining_reading: &'a str,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+    flags: CandidateFlags,
+) -> Vec<(&'a str, Option<KanjiAccuracy>)> {
+    let kanji_readings = kanji_to_readings.get(kanji).map(Vec::as_slice);
+    mora_prefix_lens(remaining_reading)
+        .into_iter()
+        .map(|len| &remaining_reading[..len])
+        .map(|reading| {
+            let accuracy = check_kanji_accuracy(kanji_readings, reading, flags);
+            (reading, accuracy)
+        })
+        .collect()
+}
+
+/// Checks whether the word is written entirely in kana, like ビール or ありがとう.
+/// Such words never need furigana, so the mapping functions return them as a single segment without searching.
+pub fn is_kana_only(word: &str) -> bool {
+    word.chars().all(utils::is_kana)
+}
+
+/// Checks whether the reading is only a transcription of the word, as with ビール and びーる,
+/// meaning that the word is kana only and the reading matches it when ignoring the difference between hiragana and katakana.
+pub fn is_transcription(word: &str, reading: &str) -> bool {
+    is_kana_only(word) && kana_equivalent(word, reading)
+}
+
+/// Checks whether the reading is consistent with the word, meaning that there's at least one way to map it to the word.
+/// Faster than mapping the word, as the search stops at the first valid mapping without storing any of it.
+/// Agrees with `count_mappings(word, reading) > 0`.
+pub fn is_mappable(word: &str, reading: &str) -> bool {
+    if is_kana_only(word) {
+        return kana_matches(reading, word, &MapConfig::default());
+    }
+    if word.chars().count() == 1 {
+        return true;
     }
 
-    // trying to assign a reading to each individual kanji, so use fine segmentation
-    let segments = FineSegmentation::new(word);
-    let nodes = map_inner(
-        segments.peekable(),
+    let context = Context {
+        word,
         reading,
-        Some(kanji_to_readings),
-        None,
-        false,
-    )
-    .unwrap_or_default();
-    let tree = FuriganaTree {
+        sources: None,
+        config: &MapConfig::default(),
+        first_only: true,
+    };
+    // the search doesn't store any nodes when looking for the first mapping, so the arena stays empty
+    SegmentationOptions::default()
+        .alternatives(word)
+        .into_iter()
+        .any(|options| {
+            map_anchored(
+                &mut Vec::new(),
+                word,
+                reading,
+                |word, _, _| CoarseSegmentation::with_options(word, options),
+                context,
+            )
+            .is_some()
+        })
+}
+
+/// Returns the number of mappings `map_naive` would return for the word and reading without creating them.
+pub fn count_mappings(word: &str, reading: &str) -> usize {
+    if is_kana_only(word) {
+        return usize::from(kana_matches(reading, word, &MapConfig::default()));
+    }
+    if word.chars().count() == 1 {
+        return 1;
+    }
+
+    search(word, reading, None, &MapConfig::default()).count_mappings()
+}
+
+/// Searches for the possible ways to map the reading to the word without flattening them into mappings,
+/// so that the result can be flattened with different weights using `Furigana::from_tree_with`.
+/// Uses the same information and options as `map_with`, with two differences:
+/// with `WhitespacePolicy::Trim` or `WhitespacePolicy::Skip` the tree only covers the word and reading without the whitespace around them,
+/// which `map_with` adds back as segments without furigana, and kana-only and single character words are searched like any other word,
+/// so their segment is graded against the kanji readings instead of being given the fixed accuracy `map_with` gives them.
+pub fn map_tree<'a>(
+    word: &'a str,
+    reading: &'a str,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+    config: &MapConfig,
+) -> FuriganaTree<'a> {
+    let (word, reading) = match config.whitespace_policy {
+        WhitespacePolicy::Keep => (word, reading),
+        WhitespacePolicy::Trim | WhitespacePolicy::Skip => {
+            (split_whitespace(word).1, split_whitespace(reading).1)
+        }
+    };
+    let sources = [ReadingSource::new("kanji_to_readings", kanji_to_readings)];
+    search(word, reading, Some(&sources), config)
+}
+
+fn search<'a>(
+    word: &'a str,
+    reading: &'a str,
+    sources: Option<&[ReadingSource]>,
+    config: &MapConfig,
+) -> FuriganaTree<'a> {
+    let context = Context {
+        word,
+        reading,
+        sources,
+        config,
+        first_only: false,
+    };
+    let mut arena = vec![];
+    let dashes = config.dashes_by_context;
+    // marks like ヶ may be read as marks or as kanji, so each way of segmenting the word is tried
+    let alternatives = SegmentationOptions::default().alternatives(word);
+    let mut root_ranges = vec![];
+    for options in alternatives {
+        let roots = if sources.is_some() {
+            // trying to assign a reading to each individual kanji, so use fine segmentation
+            let segment = |word, before, after| {
+                let segmentation = FineSegmentation::with_options(word, options);
+                if dashes {
+                    segmentation.dashes_by_context(before, after)
+                } else {
+                    segmentation
+                }
+            };
+            map_anchored(&mut arena, word, reading, segment, context)
+        } else {
+            // need kanji information to assign readings to each individual kanji, so use coarse segmentation here
+            let segment = |word, before, after| {
+                let segmentation = CoarseSegmentation::with_options(word, options);
+                if dashes {
+                    segmentation.dashes_by_context(before, after)
+                } else {
+                    segmentation
+                }
+            };
+            map_anchored(&mut arena, word, reading, segment, context)
+        };
+        root_ranges.extend(roots);
+    }
+    // the roots of every segmentation have to be next to each other in the arena
+    let roots = if root_ranges.len() > 1 {
+        let roots = root_ranges
+            .into_iter()
+            .flat_map(|range| arena[range].to_vec())
+            .collect::<Vec<_>>();
+        let start = arena.len();
+        arena.extend(roots);
+        start..arena.len()
+    } else {
+        root_ranges.pop().unwrap_or(0..0)
+    };
+    FuriganaTree {
         word,
         reading,
-        nodes,
+        nodes: arena,
+        roots,
+    }
+}
+
+// the kana at the start and end of the word have to match the start and end of the reading,
+// so they are matched up front and only the rest of the word is searched
+// short-circuits by returning None if the kana don't match
+fn map_anchored<'a, F, I>(
+    arena: &mut Vec<FuriganaNode<'a>>,
+    word: &'a str,
+    reading: &'a str,
+    segment: F,
+    context: Context,
+) -> Option<Range<usize>>
+where
+    F: Fn(&'a str, Option<char>, Option<char>) -> I,
+    I: Iterator<Item = Segment<'a>> + Clone,
+{
+    let is_anchor = |idx: usize, c: char| {
+        if c == 'ヶ' {
+            // ヶ is handled separately from other kana
+            false
+        } else if context.config.dashes_by_context {
+            let previous = word[..idx].chars().next_back();
+            let next = word[idx + c.len_utf8()..].chars().next();
+            (utils::is_kana(c) || c == '一')
+                && segmentation::classify_in_context(previous, c, next) == Char::Kana
+        } else {
+            utils::is_kana(c)
+        }
     };
-    Furigana::from_tree(&tree)
+    let prefix_len = word
+        .char_indices()
+        .find(|(idx, c)| !is_anchor(*idx, *c))
+        .map_or(word.len(), |(idx, _)| idx);
+    let (word_prefix, word_rest) = word.split_at(prefix_len);
+    let suffix_start = word_rest
+        .char_indices()
+        .rev()
+        .find(|(idx, c)| !is_anchor(prefix_len + idx, *c))
+        .map_or(0, |(idx, c)| idx + c.len_utf8());
+    let (word_middle, word_suffix) = word_rest.split_at(suffix_start);
+    let around_middle = (word_prefix.chars().next_back(), word_suffix.chars().next());
+
+    // invalid mapping if the word and reading start with different kana
+    let (reading_prefix, reading_rest) = split_kana(reading, word_prefix, context.config)?;
+
+    // normally the reading has to end with all of the kana at the end of the word,
+    // but with omitted okurigana it may only cover some of them, preferring to cover as many as possible
+    let suffix_chars = word_suffix.chars().count();
+    let min_covered = if context.config.omitted_okurigana {
+        0
+    } else {
+        suffix_chars
+    };
+    // the arena may hold nodes from earlier searches, like those of other segmentations of the word
+    let arena_start = arena.len();
+    let mut anchored = None;
+    for covered in (min_covered..=suffix_chars).rev() {
+        let (word_covered, word_omitted) = utils::split_at_char(word_suffix, covered)?;
+        let Some((reading_middle, reading_suffix)) =
+            split_kana_end(reading_rest, word_covered, context.config)
+        else {
+            // invalid mapping: the word and reading end with different kana
+            continue;
+        };
+        if let Some(roots) = map_inner(
+            arena,
+            segment(word_middle, around_middle.0, around_middle.1).peekable(),
+            reading_middle,
+            word_rest,
+            context,
+            None,
+            // rendaku happens between the parts of a compound,
+            // so prefixes like the お in お茶 don't voice the kanji after them
+            false,
+        ) {
+            // the omitted kana get the empty string at the end of the reading
+            let omitted_reading = &reading_suffix[reading_suffix.len()..];
+            anchored = Some((
+                roots,
+                [
+                    (word_covered, reading_suffix),
+                    (word_omitted, omitted_reading),
+                ],
+            ));
+            break;
+        }
+    }
+    let (mut roots, suffix) = anchored?;
+
+    let suffix = suffix
+        .iter()
+        .filter(|(kana, _)| !kana.is_empty())
+        .collect::<Vec<_>>();
+    if !suffix.is_empty() && !context.first_only {
+        // every node created by this search is part of a mapping, so all their leaves can share the suffix
+        let first_suffix = arena.len()..arena.len() + 1;
+        for node in &mut arena[arena_start..] {
+            if node.extensions.is_empty() {
+                node.extensions = first_suffix.clone();
+            }
+        }
+        if roots.is_empty() {
+            roots = first_suffix;
+        }
+        for (idx, &&(kana, reading)) in suffix.iter().enumerate() {
+            // the kana left out of the reading come after the ones covered by it
+            let next = arena.len() + 1;
+            arena.push(FuriganaNode {
+                segment: Segment::Kana(kana),
+                reading,
+                extensions: if idx + 1 < suffix.len() {
+                    next..next + 1
+                } else {
+                    0..0
+                },
+                kanji_accurate: None,
+                source: None,
+            });
+        }
+    }
+    if !word_prefix.is_empty() {
+        roots = context
+            .sibling(FuriganaNode {
+                segment: Segment::Kana(word_prefix),
+                reading: reading_prefix,
+                extensions: roots,
+                kanji_accurate: None,
+                source: None,
+            })
+            .store(arena)?;
+    }
+    Some(roots)
+}
+
+// the nodes found for the same part of the word and reading
+// when only checking whether there's a mapping, the nodes are noted without being kept
+struct Siblings<'a> {
+    nodes: Vec<FuriganaNode<'a>>,
+    found: bool,
+    first_only: bool,
+}
+
+impl<'a> Siblings<'a> {
+    fn push(&mut self, node: FuriganaNode<'a>) {
+        self.found = true;
+        if !self.first_only {
+            self.nodes.push(node);
+        }
+    }
+
+    fn extend(&mut self, other: Siblings<'a>) {
+        self.found |= other.found;
+        self.nodes.extend(other.nodes);
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.found
+    }
+
+    // the search can stop at the first node when only one mapping is needed
+    fn found_one(&self) -> bool {
+        self.first_only && self.found
+    }
+
+    // stores the nodes next to each other so that they can be the extensions of a single node
+    // returns None if there are no nodes, which means the mapping is invalid
+    fn store(self, arena: &mut Vec<FuriganaNode<'a>>) -> Option<Range<usize>> {
+        if self.is_empty() {
+            return None;
+        }
+        let start = arena.len();
+        arena.extend(self.nodes);
+        Some(start..arena.len())
+    }
+}
+
+// the information that stays the same throughout a mapping
+#[derive(Clone, Copy)]
+struct Context<'c> {
+    // the whole word and reading, for looking at the characters around the segments and their readings
+    word: &'c str,
+    reading: &'c str,
+    // None when there's no reading information at all, as in map_naive
+    sources: Option<&'c [ReadingSource<'c>]>,
+    config: &'c MapConfig,
+    // only whether there's a valid mapping is needed, so the search can stop as soon as one is found
+    // and no nodes are stored
+    first_only: bool,
+}
+
+impl<'c> Context<'c> {
+    fn siblings<'a>(&self) -> Siblings<'a> {
+        Siblings {
+            nodes: vec![],
+            found: false,
+            first_only: self.first_only,
+        }
+    }
+
+    fn sibling<'a>(&self, node: FuriganaNode<'a>) -> Siblings<'a> {
+        let mut siblings = self.siblings();
+        siblings.push(node);
+        siblings
+    }
+
+    // the characters before and after the part of the word at the start of word_rest with the given length
+    fn neighbors(&self, word_rest: &str, len: usize) -> (Option<char>, Option<char>) {
+        match utils::subslice_range(self.word, word_rest) {
+            Some(range) => (
+                self.word[..range.start].chars().next_back(),
+                self.word[range.start + len..].chars().next(),
+            ),
+            None => (None, word_rest[len..].chars().next()),
+        }
+    }
+
+    // the kana before reading_rest in the reading
+    fn previous_kana(&self, reading_rest: &str) -> Option<char> {
+        let range = utils::subslice_range(self.reading, reading_rest)?;
+        self.reading[..range.start].chars().next_back()
+    }
+
+    // the readings of the context rules for the part of the word at the start of word_rest that apply there
+    fn rule_readings(&self, word_rest: &str, part: &str) -> Vec<&'c str> {
+        let (previous, next) = self.neighbors(word_rest, part.len());
+        self.config
+            .context_rules
+            .iter()
+            .filter(|rule| rule.word == part && rule.applies(previous, next))
+            .flat_map(|rule| rule.readings.iter().map(String::as_str))
+            .collect()
+    }
 }
 
 // short-circuits by returning None on invalid mappings
+// word_rest contains the rest of the whole word, which may continue past the segments
 fn map_inner<'a, I>(
+    arena: &mut Vec<FuriganaNode<'a>>,
     mut segments_rest: Peekable<I>,
     reading_rest: &'a str,
-    kanji_to_readings: Option<&HashMap<String, Vec<String>>>,
-    previous_kanji: Option<&'a str>,
+    word_rest: &'a str,
+    context: Context,
+    previous: Option<Segment<'a>>,
     can_be_rendaku: bool,
-) -> Option<Vec<FuriganaNode<'a>>>
+) -> Option<Range<usize>>
 where
     I: Iterator<Item = Segment<'a>> + Clone,
 {
+    let special_cases = !context.config.disable_special_cases;
     match segments_rest.next() {
         Some(segment @ Segment::Kana(kana)) => {
-            // try to get matching kana from reading
-            let reading = reading_rest.get(0..kana.len())?;
-            if !kana_equivalent(reading, kana) {
-                // invalid mapping: segment and reading don't match
-                return None;
-            }
-            let reading_rest = &reading_rest[kana.len()..];
-            let extensions = map_inner(segments_rest, reading_rest, kanji_to_readings, None, true)?;
-            Some(vec![FuriganaNode {
-                segment,
-                reading,
-                extensions,
-                kanji_accurate: None,
-            }])
+            // try to get matching kana from reading, or return None if the segment and reading don't match
+            let (reading, reading_rest) = split_kana(reading_rest, kana, context.config)?;
+            let word_rest = &word_rest[kana.len()..];
+            let extensions = map_inner(
+                arena,
+                segments_rest,
+                reading_rest,
+                word_rest,
+                context,
+                Some(segment),
+                true,
+            )?;
+            context
+                .sibling(FuriganaNode {
+                    segment,
+                    reading,
+                    extensions,
+                    kanji_accurate: None,
+                    source: None,
+                })
+                .store(arena)
         }
+        // ヶ as a mark already takes any reading without special cases
+        Some(Segment::Kanji("ヶ")) if !special_cases => None,
         Some(segment @ Segment::Kanji(kanji)) => {
-            let kanji_readings = kanji_to_readings
-                .and_then(|km| km.get(kanji))
-                .map(Vec::as_slice);
+            // with dashes_by_context, the kanji may be a ー that stands for 一
+            let graded_kanji = if context.config.dashes_by_context && kanji.contains('ー') {
+                Cow::Owned(kanji.replace('ー', "一"))
+            } else {
+                Cow::Borrowed(kanji)
+            };
+            let graded_kanji = graded_kanji.as_ref();
+            let context_readings = if special_cases {
+                let next = peek_next(&mut segments_rest, &word_rest[kanji.len()..]);
+                counters::readings(previous, graded_kanji, next)
+            } else {
+                &[]
+            };
+            let mut nodes = context.siblings();
+            if kanji.chars().count() == 1 {
+                // words like 大人 can't be split between their kanji, so try known readings for the whole word first
+                for (compound, compound_readings) in compound_readings(word_rest, context) {
+                    let last_kanji_idx = compound.char_indices().last().map_or(0, |(idx, _)| idx);
+                    for (compound_reading, accuracy, source) in compound_readings {
+                        if nodes.found_one() {
+                            break;
+                        }
+                        let Some(reading) =
+                            reading_prefix(reading_rest, compound_reading, context.config)
+                        else {
+                            continue;
+                        };
+                        let mut segments_rest = segments_rest.clone();
+                        // the first kanji was already consumed
+                        for _ in compound.chars().skip(1) {
+                            segments_rest.next();
+                        }
+                        if let Some(extensions) = map_inner(
+                            arena,
+                            segments_rest,
+                            &reading_rest[reading.len()..],
+                            &word_rest[compound.len()..],
+                            context,
+                            Some(Segment::Kanji(&compound[last_kanji_idx..])),
+                            true,
+                        ) {
+                            nodes.push(FuriganaNode {
+                                segment: Segment::Kanji(compound),
+                                reading,
+                                extensions,
+                                kanji_accurate: Some(accuracy),
+                                source,
+                            })
+                        }
+                    }
+                }
+            }
+
+            // the kana following the kanji (okurigana etc.) have to come right after the kanji's reading
+            let next_kana = match segments_rest.peek() {
+                Some(Segment::Kana(kana)) => Some(*kana),
+                _ => None,
+            };
+            // readings are only graded with kanji reading data
+            let rule_readings = if context.sources.is_some() {
+                context.rule_readings(word_rest, kanji)
+            } else {
+                vec![]
+            };
+            let affix = if special_cases && context.sources.is_some() {
+                let (before, after) = context.neighbors(word_rest, kanji.len());
+                affixes::readings(before, kanji, after).map(|(position, readings)| {
+                    let readings = readings.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+                    (position, readings)
+                })
+            } else {
+                None
+            };
             // try matching different lengths of the reading to the word
-            let mut nodes = vec![];
-            let chars = reading_rest.chars().count();
-            if chars == 0 {
+            if reading_rest.is_empty() {
                 return None;
             }
-            for chars in 1..=chars {
-                let chars_len = reading_rest.chars().take(chars).map(char::len_utf8).sum();
-                let reading = &reading_rest[..chars_len];
+            for reading_len in mora_prefix_lens(reading_rest) {
+                if nodes.found_one() {
+                    break;
+                }
+                let reading = &reading_rest[..reading_len];
+                if kanji == "ヶ" && reading == "か" {
+                    // ヶ as a mark already reads か
+                    continue;
+                }
 
-                let mut segments_rest = segments_rest.clone();
-                let can_be_sokuonbin = segments_rest.peek().is_some();
-                let reading_rest = &reading_rest[chars_len..];
+                let segments_rest = segments_rest.clone();
+                let can_be_sokuonbin = word_rest.len() > kanji.len();
+                let reading_rest = &reading_rest[reading_len..];
+                if let Some(kana) = next_kana {
+                    let anchored = split_kana(reading_rest, kana, context.config).is_some();
+                    if !anchored {
+                        // this reading would leave the following kana without a match
+                        continue;
+                    }
+                }
                 if let Some(extensions) = map_inner(
+                    arena,
                     segments_rest,
                     reading_rest,
-                    kanji_to_readings,
-                    Some(kanji),
+                    &word_rest[kanji.len()..],
+                    context,
+                    Some(segment),
                     true,
                 ) {
-                    let kanji_accurate = check_kanji_accuracy(
-                        kanji_readings,
-                        reading,
-                        can_be_rendaku,
-                        can_be_sokuonbin,
-                    );
+                    let graded_reading = normalize_kana(reading, context.config);
+                    let graded_reading = graded_reading.as_ref();
+                    let is_context_reading = context_readings
+                        .iter()
+                        .any(|cr| kana_equivalent(cr, graded_reading));
+                    let is_rule_reading = rule_readings
+                        .iter()
+                        .any(|rr| kana_equivalent(rr, graded_reading));
+                    let (kanji_accurate, source) = if is_rule_reading
+                        || is_context_reading
+                            && context.sources.map_or(false, |sources| {
+                                sources
+                                    .iter()
+                                    .any(|source| source.get(graded_kanji).is_some())
+                            }) {
+                        (Some(KanjiAccuracy::Accurate), None)
+                    } else {
+                        grade_affix_reading(
+                            affix.as_ref(),
+                            context,
+                            graded_reading,
+                            can_be_sokuonbin,
+                        )
+                        .unwrap_or_else(|| {
+                            grade_reading(
+                                context,
+                                graded_kanji,
+                                graded_reading,
+                                can_be_rendaku,
+                                can_be_sokuonbin,
+                            )
+                        })
+                    };
+                    let after_sokuon =
+                        matches!(previous, Some(Segment::Kanji(_) | Segment::Numeric(_)))
+                            && matches!(context.previous_kana(reading), Some('っ' | 'ッ'));
+                    let kanji_accurate = if after_sokuon {
+                        grade_after_sokuon(kanji_accurate, graded_reading)
+                    } else {
+                        kanji_accurate
+                    };
                     nodes.push(FuriganaNode {
                         segment,
                         reading,
                         extensions,
                         kanji_accurate,
+                        source,
                     })
                 }
             }
-            Some(nodes)
-        }
-        Some(segment @ Segment::Alphanumeric(alpha)) => {
-            let alpha_readings: &[&str] = match alpha {
-                "A" | "a" | "Ａ" | "ａ" => &["エー"],
-                "B" | "b" | "Ｂ" | "ｂ" => &["ビー"],
-                "C" | "c" | "Ｃ" | "ｃ" => &["シー"],
-                "D" | "d" | "Ｄ" | "ｄ" => &["ディー"],
-                "E" | "e" | "Ｅ" | "ｅ" => &["イー"],
-                "F" | "f" | "Ｆ" | "ｆ" => &["エフ"],
-                "G" | "g" | "Ｇ" | "ｇ" => &["ギー"],
-                "H" | "h" | "Ｈ" | "ｈ" => &["エイチ"],
-                "I" | "i" | "Ｉ" | "ｉ" => &["アイ"],
-                "J" | "j" | "Ｊ" | "ｊ" => &["ジェー"],
-                "K" | "k" | "Ｋ" | "ｋ" => &["ケー"],
-                "L" | "l" | "Ｌ" | "ｌ" => &["エル"],
-                "M" | "m" | "Ｍ" | "ｍ" => &["エム"],
-                "N" | "n" | "Ｎ" | "ｎ" => &["エヌ"],
-                "O" | "o" | "Ｏ" | "ｏ" => &["オー"],
-                "P" | "p" | "Ｐ" | "ｐ" => &["ピー"],
-                "Q" | "q" | "Ｑ" | "ｑ" => &["キュー"],
-                "R" | "r" | "Ｒ" | "ｒ" => &["アール"],
-                "S" | "s" | "Ｓ" | "ｓ" => &["エス"],
-                "T" | "t" | "Ｔ" | "ｔ" => &["ティー"],
-                "U" | "u" | "Ｕ" | "ｕ" => &["ユー"],
-                "V" | "v" | "Ｖ" | "ｖ" => &["ブイ"],
-                "W" | "w" | "Ｗ" | "ｗ" => &["ダブルユー"],
-                "X" | "x" | "Ｘ" | "ｘ" => &["エックス"],
-                "Y" | "y" | "Ｙ" | "ｙ" => &["ワイ"],
-                "Z" | "z" | "Ｚ" | "ｚ" => &["ゼット"],
-                "0" | "０" => &["ぜろ", "れい"],
-                "1" | "１" => &["いち"],
-                "2" | "２" => &["に"],
-                "3" | "３" => &["さん"],
-                "4" | "４" => &["よん"],
-                "5" | "５" => &["ご"],
-                "6" | "６" => &["ろく"],
-                "7" | "７" => &["しち", "なな"],
-                "8" | "８" => &["はち"],
-                "9" | "９" => &["きゅう"],
-                _ => unreachable!("unexpected alphanumeric {alpha}"),
+            // invalid mapping if no reading length worked out
+            nodes.store(arena)
+        }
+        Some(segment @ Segment::Numeric(number)) => {
+            let value = numbers::parse(number);
+            let counter = match peek_next(&mut segments_rest, &word_rest[number.len()..]) {
+                Some(Segment::Kanji(counter)) => Some(counter),
+                // counting with つ as in 3つ
+                Some(Segment::Kana(kana)) if kana.starts_with(['つ', 'ツ']) => Some("つ"),
+                _ => None,
             };
-            for alpha_reading in alpha_readings {
-                let corresponding_reading = reading_rest.get(..alpha_reading.len())?;
-                if kana_equivalent(corresponding_reading, alpha_reading) {
-                    let reading_rest = &reading_rest[alpha_reading.len()..];
-                    let extensions =
-                        map_inner(segments_rest, reading_rest, kanji_to_readings, None, true)?;
-                    return Some(vec![FuriganaNode {
+            let mut nodes = context.siblings();
+            let mut readings = numbers::match_prefixes(number, reading_rest, context.config);
+            if counter.map_or(false, |counter| counter != "つ") {
+                // a number followed by a kanji may end in っ depending on how the kanji is read, as in 1回 (いっかい)
+                for reading in numbers::assimilated_prefixes(number, reading_rest, context.config) {
+                    if !readings.contains(&reading) {
+                        readings.push(reading);
+                    }
+                }
+            }
+            if let (true, Some(value), Some(counter)) = (special_cases, value, counter) {
+                for counter_number_reading in counters::number_readings(value, counter) {
+                    if let Some(reading) =
+                        reading_prefix(reading_rest, &counter_number_reading, context.config)
+                    {
+                        if !readings.contains(&reading) {
+                            readings.push(reading);
+                        }
+                    }
+                }
+
+                // some numbers are read together with their counter, like 20歳 (はたち)
+                let combined = &word_rest[..number.len() + counter.len()];
+                for combined_reading in counters::combined_readings(value, counter) {
+                    if nodes.found_one() {
+                        break;
+                    }
+                    let Some(reading) =
+                        reading_prefix(reading_rest, combined_reading, context.config)
+                    else {
+                        continue;
+                    };
+                    let mut segments_rest = segments_rest.clone();
+                    segments_rest.next();
+                    if let Some(extensions) = map_inner(
+                        arena,
+                        segments_rest,
+                        &reading_rest[reading.len()..],
+                        &word_rest[combined.len()..],
+                        context,
+                        Some(Segment::Kanji(counter)),
+                        true,
+                    ) {
+                        nodes.push(FuriganaNode {
+                            segment: Segment::Numeric(combined),
+                            reading,
+                            extensions,
+                            kanji_accurate: context.sources.map(|_| KanjiAccuracy::Irregular),
+                            source: None,
+                        });
+                    }
+                }
+            }
+            let mut fuzzy_readings = vec![];
+            if context.config.fuzzy_numbers {
+                for reading in
+                    numbers::match_prefixes_ignoring_voicing(number, reading_rest, context.config)
+                {
+                    if !readings.contains(&reading) {
+                        fuzzy_readings.push(reading);
+                    }
+                }
+            }
+            let readings = readings.into_iter().map(|reading| (reading, None)).chain(
+                fuzzy_readings
+                    .into_iter()
+                    .map(|reading| (reading, Some(KanjiAccuracy::VoicingMismatch))),
+            );
+            for (reading, kanji_accurate) in readings {
+                if nodes.found_one() {
+                    break;
+                }
+                if let Some(extensions) = map_inner(
+                    arena,
+                    segments_rest.clone(),
+                    &reading_rest[reading.len()..],
+                    &word_rest[number.len()..],
+                    context,
+                    Some(segment),
+                    true,
+                ) {
+                    nodes.push(FuriganaNode {
                         segment,
-                        reading: alpha_reading,
+                        reading,
                         extensions,
-                        kanji_accurate: None,
-                    }]);
+                        kanji_accurate,
+                        source: None,
+                    });
+                }
+            }
+            // invalid mapping if the number can't be read in a way that matches the reading
+            nodes.store(arena)
+        }
+        Some(segment @ (Segment::Alphabetic(_) | Segment::Exception(_))) if !special_cases => {
+            // without the built-in readings of letters and ヶ, any reading may belong to them
+            map_any_reading(
+                arena,
+                segment,
+                segments_rest,
+                reading_rest,
+                word_rest,
+                context,
+            )
+        }
+        Some(segment @ Segment::Alphabetic(_)) => {
+            let mut nodes = context.siblings();
+            // words like Suica may have a known reading as a whole
+            if !matches!(previous, Some(Segment::Alphabetic(_))) {
+                let run_len = word_rest
+                    .find(|c| !utils::is_alphabetic(c))
+                    .unwrap_or(word_rest.len());
+                let run = &word_rest[..run_len];
+                let run_readings = context
+                    .config
+                    .latin_words
+                    .iter()
+                    .filter(|(word, _)| word.eq_ignore_ascii_case(run))
+                    .flat_map(|(_, readings)| readings);
+                for run_reading in run_readings {
+                    if nodes.found_one() {
+                        break;
+                    }
+                    let Some(reading) = reading_prefix(reading_rest, run_reading, context.config)
+                    else {
+                        continue;
+                    };
+                    let mut segments_rest = segments_rest.clone();
+                    // the first letter was already consumed
+                    for _ in run.chars().skip(1) {
+                        segments_rest.next();
+                    }
+                    if let Some(extensions) = map_inner(
+                        arena,
+                        segments_rest,
+                        &reading_rest[reading.len()..],
+                        &word_rest[run.len()..],
+                        context,
+                        Some(Segment::Alphabetic(run)),
+                        true,
+                    ) {
+                        nodes.push(FuriganaNode {
+                            segment: Segment::Alphabetic(run),
+                            reading,
+                            extensions,
+                            kanji_accurate: None,
+                            source: None,
+                        });
+                    }
                 }
             }
-            None
+            // letters like the II in World War II may be a roman numeral instead
+            if let Some(numeral) = numbers::roman_numeral_prefix(word_rest) {
+                let mut segments_rest = segments_rest.clone();
+                // the first letter was already consumed
+                for _ in numeral.chars().skip(1) {
+                    segments_rest.next();
+                }
+                for reading in numbers::match_prefixes(numeral, reading_rest, context.config) {
+                    if nodes.found_one() {
+                        break;
+                    }
+                    if let Some(extensions) = map_inner(
+                        arena,
+                        segments_rest.clone(),
+                        &reading_rest[reading.len()..],
+                        &word_rest[numeral.len()..],
+                        context,
+                        Some(Segment::Numeric(numeral)),
+                        true,
+                    ) {
+                        nodes.push(FuriganaNode {
+                            segment: Segment::Numeric(numeral),
+                            reading,
+                            extensions,
+                            kanji_accurate: None,
+                            source: None,
+                        });
+                    }
+                }
+            }
+            nodes.extend(map_letter(
+                arena,
+                segment,
+                segments_rest,
+                reading_rest,
+                word_rest,
+                context,
+            ));
+            nodes.store(arena)
         }
         Some(segment @ Segment::Exception(exception)) => match exception {
             "ヶ" => {
-                let corresponding_reading_len = 'か'.len_utf8();
-                let reading = reading_rest.get(..corresponding_reading_len)?;
+                let (reading, reading_rest) = utils::split_at_char(reading_rest, 1)?;
                 if reading == "か" {
                     let extensions = map_inner(
+                        arena,
                         segments_rest,
-                        &reading_rest[corresponding_reading_len..],
-                        kanji_to_readings,
-                        previous_kanji,
+                        reading_rest,
+                        &word_rest[exception.len()..],
+                        context,
+                        previous,
                         can_be_rendaku,
                     )?;
-                    Some(vec![FuriganaNode {
-                        segment,
-                        reading,
-                        extensions,
-                        kanji_accurate: None,
-                    }])
+                    context
+                        .sibling(FuriganaNode {
+                            segment,
+                            reading,
+                            extensions,
+                            kanji_accurate: None,
+                            source: None,
+                        })
+                        .store(arena)
                 } else {
                     None
                 }
@@ -242,33 +1147,33 @@ where
         Some(segment @ Segment::Other(other)) => {
             match other {
                 "々" => {
-                    if let Some(kanji) = previous_kanji {
-                        let kanji_readings = kanji_to_readings
-                            .and_then(|km| km.get(kanji))
-                            .map(Vec::as_slice);
+                    if let Some(Segment::Kanji(kanji)) = previous {
                         // try matching different lengths of the reading to the word
-                        let mut nodes = vec![];
-                        let chars = reading_rest.chars().count();
-                        if chars == 0 {
+                        let mut nodes = context.siblings();
+                        if reading_rest.is_empty() {
                             return None;
                         }
-                        for chars in 1..=chars {
-                            let chars_len =
-                                reading_rest.chars().take(chars).map(char::len_utf8).sum();
-                            let reading = &reading_rest[..chars_len];
-
-                            let mut segments_rest = segments_rest.clone();
-                            let can_be_sokuonbin = segments_rest.peek().is_some();
-                            let reading_rest = &reading_rest[chars_len..];
+                        for reading_len in mora_prefix_lens(reading_rest) {
+                            if nodes.found_one() {
+                                break;
+                            }
+                            let reading = &reading_rest[..reading_len];
+
+                            let segments_rest = segments_rest.clone();
+                            let can_be_sokuonbin = word_rest.len() > other.len();
+                            let reading_rest = &reading_rest[reading_len..];
                             if let Some(extensions) = map_inner(
+                                arena,
                                 segments_rest,
                                 reading_rest,
-                                kanji_to_readings,
-                                Some(kanji),
+                                &word_rest[other.len()..],
+                                context,
+                                previous,
                                 true,
                             ) {
-                                let kanji_accurate = check_kanji_accuracy(
-                                    kanji_readings,
+                                let (kanji_accurate, source) = grade_reading(
+                                    context,
+                                    kanji,
                                     reading,
                                     can_be_rendaku,
                                     can_be_sokuonbin,
@@ -279,22 +1184,93 @@ where
                                     reading,
                                     extensions,
                                     kanji_accurate,
+                                    source,
                                 })
                             }
                         }
-                        Some(nodes)
+                        nodes.store(arena)
                     } else {
                         None
                     }
                 }
-                _ => None,
+                // ranges like 2〜3日 are read either with から or with just the numbers, ratios like 2:1 with たい
+                "〜" | "～" | ":" | "：" if special_cases => {
+                    let between_numbers = matches!(previous, Some(Segment::Numeric(_)))
+                        && matches!(segments_rest.peek(), Some(Segment::Numeric(_)));
+                    if !between_numbers {
+                        let nodes = map_other(
+                            arena,
+                            segment,
+                            segments_rest,
+                            reading_rest,
+                            word_rest,
+                            context,
+                        );
+                        return nodes.store(arena);
+                    }
+                    let symbol_readings: &[&str] = match other {
+                        ":" | "：" => &["たい"],
+                        _ => &["から", ""],
+                    };
+                    let mut nodes = context.siblings();
+                    for symbol_reading in symbol_readings {
+                        if nodes.found_one() {
+                            break;
+                        }
+                        let Some(reading) =
+                            reading_prefix(reading_rest, symbol_reading, context.config)
+                        else {
+                            continue;
+                        };
+                        if let Some(extensions) = map_inner(
+                            arena,
+                            segments_rest.clone(),
+                            &reading_rest[reading.len()..],
+                            &word_rest[other.len()..],
+                            context,
+                            Some(segment),
+                            true,
+                        ) {
+                            nodes.push(FuriganaNode {
+                                segment,
+                                reading,
+                                extensions,
+                                kanji_accurate: None,
+                                source: None,
+                            });
+                        }
+                    }
+                    nodes.store(arena)
+                }
+                // letters outside of A to Z, like α, may still have readings in the letter table
+                _ => {
+                    let mut nodes = map_letter(
+                        arena,
+                        segment,
+                        segments_rest.clone(),
+                        reading_rest,
+                        word_rest,
+                        context,
+                    );
+                    if nodes.is_empty() {
+                        nodes = map_other(
+                            arena,
+                            segment,
+                            segments_rest,
+                            reading_rest,
+                            word_rest,
+                            context,
+                        );
+                    }
+                    nodes.store(arena)
+                }
             }
         }
         None => {
             // out of segments
             if reading_rest.is_empty() {
                 // valid: out of input
-                Some(vec![])
+                Some(arena.len()..arena.len())
             } else {
                 // invalid: remaining readings can't be mapped to anything
                 None
@@ -303,51 +1279,464 @@ where
     }
 }
 
+// returns the next segment, falling back to the rest of the word for the kana at its end which were matched up front
+fn peek_next<'a, I>(segments_rest: &mut Peekable<I>, word_rest: &'a str) -> Option<Segment<'a>>
+where
+    I: Iterator<Item = Segment<'a>> + Clone,
+{
+    segments_rest
+        .peek()
+        .copied()
+        .or_else(|| FineSegmentation::new(word_rest).next())
+}
+
+// maps the segment to the readings in the letter table
+fn map_letter<'a, I>(
+    arena: &mut Vec<FuriganaNode<'a>>,
+    segment: Segment<'a>,
+    segments_rest: Peekable<I>,
+    reading_rest: &'a str,
+    word_rest: &'a str,
+    context: Context,
+) -> Siblings<'a>
+where
+    I: Iterator<Item = Segment<'a>> + Clone,
+{
+    let letter = segment.inner();
+    let mut chars = letter.chars();
+    let letter_readings = match (chars.next(), chars.next()) {
+        (Some(c), None) => context.config.letter_readings.get(c),
+        // grapheme clusters made up of several chars aren't letters
+        _ => &[],
+    };
+    let mut nodes = context.siblings();
+    for letter_reading in letter_readings {
+        if nodes.found_one() {
+            break;
+        }
+        let Some(reading) = reading_prefix(reading_rest, letter_reading, context.config) else {
+            continue;
+        };
+        if let Some(extensions) = map_inner(
+            arena,
+            segments_rest.clone(),
+            &reading_rest[reading.len()..],
+            &word_rest[letter.len()..],
+            context,
+            Some(segment),
+            true,
+        ) {
+            nodes.push(FuriganaNode {
+                segment,
+                reading,
+                extensions,
+                kanji_accurate: None,
+                source: None,
+            });
+        }
+    }
+    nodes
+}
+
+// maps a character that can't be read according to the configured policy
+fn map_other<'a, I>(
+    arena: &mut Vec<FuriganaNode<'a>>,
+    segment: Segment<'a>,
+    segments_rest: Peekable<I>,
+    reading_rest: &'a str,
+    word_rest: &'a str,
+    context: Context,
+) -> Siblings<'a>
+where
+    I: Iterator<Item = Segment<'a>> + Clone,
+{
+    let other = segment.inner();
+    // an empty reading still points into the reading, like every other string in the mappings
+    let no_reading = &reading_rest[..0];
+    let readings = match context.config.other_policy {
+        OtherPolicy::Fail => vec![],
+        OtherPolicy::PassThroughNoReading => vec![no_reading],
+        OtherPolicy::PassThroughConsumeMatchingKana => {
+            match reading_prefix(reading_rest, other, context.config) {
+                Some(matching) => vec![matching, no_reading],
+                None => vec![no_reading],
+            }
+        }
+    };
+    let mut nodes = context.siblings();
+    for reading in readings {
+        if nodes.found_one() {
+            break;
+        }
+        if let Some(extensions) = map_inner(
+            arena,
+            segments_rest.clone(),
+            &reading_rest[reading.len()..],
+            &word_rest[other.len()..],
+            context,
+            Some(segment),
+            true,
+        ) {
+            nodes.push(FuriganaNode {
+                segment,
+                reading,
+                extensions,
+                kanji_accurate: None,
+                source: None,
+            });
+        }
+    }
+    nodes
+}
+
+// maps the segment to every possible length of the reading
+fn map_any_reading<'a, I>(
+    arena: &mut Vec<FuriganaNode<'a>>,
+    segment: Segment<'a>,
+    segments_rest: Peekable<I>,
+    reading_rest: &'a str,
+    word_rest: &'a str,
+    context: Context,
+) -> Option<Range<usize>>
+where
+    I: Iterator<Item = Segment<'a>> + Clone,
+{
+    let mut nodes = context.siblings();
+    for (idx, c) in reading_rest.char_indices() {
+        if nodes.found_one() {
+            break;
+        }
+        let reading = &reading_rest[..idx + c.len_utf8()];
+        if let Some(extensions) = map_inner(
+            arena,
+            segments_rest.clone(),
+            &reading_rest[reading.len()..],
+            &word_rest[segment.inner().len()..],
+            context,
+            Some(segment),
+            true,
+        ) {
+            nodes.push(FuriganaNode {
+                segment,
+                reading,
+                extensions,
+                kanji_accurate: None,
+                source: None,
+            });
+        }
+    }
+    nodes.store(arena)
+}
+
+// the lengths of the prefixes of the reading that a kanji can take, which are made up of whole mora
+// so that small kana like the ょ in きょ stay with the kana before them
+// none if the reading starts with っ, which always belongs to the reading before it
+fn mora_prefix_lens(reading: &str) -> Vec<usize> {
+    if reading.starts_with(['っ', 'ッ', 'ｯ']) {
+        return vec![];
+    }
+    ReadingSegmentation::new(reading)
+        .scan(0, |len, mora| {
+            *len += mora.len();
+            Some(*len)
+        })
+        .collect()
+}
+
+// a reading of a compound with the accuracy it gives to the mapping and the index of the source it came from
+type CompoundReading<'b> = (&'b str, KanjiAccuracy, Option<usize>);
+
+// returns the words at the start of word_rest made up of multiple kanji that have known readings, longest first
+// readings from the context rules that apply take priority over the ones from the sources,
+// which take priority over the built-in irregular readings that are only used without disable_special_cases
+fn compound_readings<'a, 'b>(
+    word_rest: &'a str,
+    context: Context<'b>,
+) -> Vec<(&'a str, Vec<CompoundReading<'b>>)> {
+    let kanji_run_ends = word_rest
+        .char_indices()
+        .take_while(|(_, c)| utils::is_kanji(*c) || *c == '々')
+        .map(|(idx, c)| idx + c.len_utf8())
+        .skip(1)
+        .collect::<Vec<_>>();
+
+    let mut compounds = vec![];
+    for end in kanji_run_ends.into_iter().rev() {
+        let compound = &word_rest[..end];
+        let mut readings: Vec<CompoundReading> = vec![];
+        for reading in context.rule_readings(word_rest, compound) {
+            if !readings.iter().any(|(r, _, _)| *r == reading) {
+                readings.push((reading, KanjiAccuracy::Accurate, None));
+            }
+        }
+        for (idx, source) in context.sources.unwrap_or_default().iter().enumerate() {
+            for reading in source.get(compound).unwrap_or_default() {
+                if !readings.iter().any(|(r, _, _)| r == reading) {
+                    readings.push((reading.as_str(), KanjiAccuracy::Accurate, Some(idx)));
+                }
+            }
+        }
+        let irregular_readings = if !context.config.disable_special_cases {
+            irregular::readings(compound).unwrap_or_default()
+        } else {
+            &[]
+        };
+        for irregular in irregular_readings {
+            if !readings.iter().any(|(reading, _, _)| reading == irregular) {
+                readings.push((irregular, KanjiAccuracy::Irregular, None));
+            }
+        }
+        if !readings.is_empty() {
+            compounds.push((compound, readings));
+        }
+    }
+    compounds
+}
+
+// after a reading that ends in っ, h sounds turn into p sounds but rendaku doesn't happen,
+// so ぱい in 一杯 (いっぱい) changed along with the kanji before it while a reading like がい would be implausible
+fn grade_after_sokuon(accuracy: Option<KanjiAccuracy>, reading: &str) -> Option<KanjiAccuracy> {
+    match (accuracy, reading.chars().next()) {
+        (Some(KanjiAccuracy::AccurateWithRendaku), Some(c))
+            if "ぱぴぷぺぽパピプペポ".contains(c) =>
+        {
+            Some(KanjiAccuracy::AccurateWithSokuonbinAndRendaku)
+        }
+        (Some(KanjiAccuracy::AccurateWithRendaku), _) => Some(KanjiAccuracy::VoicingMismatch),
+        _ => accuracy,
+    }
+}
+
+// grades the reading with the readings of the kanji as a prefix or suffix
+// returns None if the kanji isn't one or the reading isn't accurate for it, so that the sources can grade it instead
+fn grade_affix_reading(
+    affix: Option<&(affixes::Position, Vec<String>)>,
+    context: Context,
+    reading: &str,
+    can_be_sokuonbin: bool,
+) -> Option<(Option<KanjiAccuracy>, Option<usize>)> {
+    let (position, readings) = affix?;
+    let flags = CandidateFlags {
+        // suffixes always follow a kanji, so they're expected to undergo rendaku
+        can_be_rendaku: *position == affixes::Position::Suffix,
+        can_be_sokuonbin,
+        merge_yotsugana: context.config.merge_yotsugana,
+        vowel_lengthening: context.config.vowel_lengthening,
+    };
+    match check_kanji_accuracy(Some(readings), reading, flags)? {
+        KanjiAccuracy::Inaccurate | KanjiAccuracy::VoicingMismatch => None,
+        accuracy => Some((Some(accuracy), None)),
+    }
+}
+
+// grades the reading of the kanji with each source in order of priority
+// returns the index of the first source that accepts the reading along with its accuracy
+fn grade_reading(
+    context: Context,
+    kanji: &str,
+    reading: &str,
+    can_be_rendaku: bool,
+    can_be_sokuonbin: bool,
+) -> (Option<KanjiAccuracy>, Option<usize>) {
+    let flags = CandidateFlags {
+        can_be_rendaku,
+        can_be_sokuonbin,
+        merge_yotsugana: context.config.merge_yotsugana,
+        vowel_lengthening: context.config.vowel_lengthening,
+    };
+    let mut accuracy = None;
+    for (idx, source) in context.sources.unwrap_or_default().iter().enumerate() {
+        match check_kanji_accuracy(source.get(kanji), reading, flags) {
+            None => {}
+            Some(KanjiAccuracy::Inaccurate) => accuracy = Some(KanjiAccuracy::Inaccurate),
+            accepted => return (accepted, Some(idx)),
+        }
+    }
+    (accuracy, None)
+}
+
+// returns the start of the reading if it's equivalent to the expected reading
+fn reading_prefix<'a>(reading: &'a str, expected: &str, config: &MapConfig) -> Option<&'a str> {
+    kana_lengths(reading, expected, config)
+        .filter_map(|len| utils::split_at_char(reading, len))
+        .map(|(prefix, _)| prefix)
+        .find(|prefix| kana_matches(prefix, expected, config))
+}
+
+// splits off the start of the reading that's equivalent to the kana,
+// which is shorter or longer than the kana if one of them leaves out a ー or has standalone dakuten
+fn split_kana<'a>(reading: &'a str, kana: &str, config: &MapConfig) -> Option<(&'a str, &'a str)> {
+    if kana.is_empty() {
+        // ignored chars like ・ are left to the segment next to the missing kana rather than dropped with them
+        return Some(reading.split_at(0));
+    }
+    kana_lengths(reading, kana, config)
+        .filter_map(|len| utils::split_at_char(reading, len))
+        // the kana are followed by something other than kana, whose reading can't start with ー
+        .find(|(prefix, rest)| !rest.starts_with('ー') && kana_matches(prefix, kana, config))
+}
+
+// splits off the end of the reading that's equivalent to the kana, like split_kana
+fn split_kana_end<'a>(
+    reading: &'a str,
+    kana: &str,
+    config: &MapConfig,
+) -> Option<(&'a str, &'a str)> {
+    if kana.is_empty() {
+        return Some(reading.split_at(reading.len()));
+    }
+    let reading_chars = reading.chars().count();
+    kana_lengths(reading, kana, config)
+        .filter_map(|len| reading_chars.checked_sub(len))
+        .filter_map(|idx| utils::split_at_char(reading, idx))
+        .find(|(_, suffix)| kana_matches(suffix, kana, config))
+}
+
+// the numbers of chars of the reading that may match the kana, from the longest to the shortest
+// so that the kana take the ー and other optional chars at the boundary rather than leaving them to what follows
+fn kana_lengths(reading: &str, kana: &str, config: &MapConfig) -> impl Iterator<Item = usize> {
+    let optional = |c: char| {
+        c == 'ー'
+            || (config.combine_dakuten || config.halfwidth_kana) && utils::is_standalone_dakuten(c)
+            || config.ignore_middle_dots && utils::is_middle_dot(c)
+            || config.whitespace_policy == WhitespacePolicy::Skip && c.is_whitespace()
+    };
+    let chars = kana.chars().count();
+    let shortest = chars - kana.chars().filter(|c| optional(*c)).count();
+    let longest = chars + reading.chars().filter(|c| optional(*c)).count();
+    (shortest..=longest.min(reading.chars().count())).rev()
+}
+
+// kana_equivalent for kana from the word and reading, which may need to be normalized first
+fn kana_matches(reading: &str, kana: &str, config: &MapConfig) -> bool {
+    kana_equivalent(
+        &normalize_kana(reading, config),
+        &normalize_kana(kana, config),
+    )
+}
+
+// applies the options that repair damaged kana, only allocating if there's something to repair
+fn normalize_kana<'s>(kana: &'s str, config: &MapConfig) -> Cow<'s, str> {
+    if config.ignore_middle_dots && kana.contains(utils::is_middle_dot) {
+        let without_dots = kana.replace(utils::is_middle_dot, "");
+        return Cow::Owned(normalize_kana(&without_dots, config).into_owned());
+    }
+    if config.whitespace_policy == WhitespacePolicy::Skip && kana.contains(char::is_whitespace) {
+        let without_whitespace = kana.replace(char::is_whitespace, "");
+        return Cow::Owned(normalize_kana(&without_whitespace, config).into_owned());
+    }
+    if config.dashes_by_context && kana.contains('一') {
+        // only kana segments and readings get here, so 一 can only be a misplaced ー
+        let dashes = kana.replace('一', "ー");
+        return Cow::Owned(normalize_kana(&dashes, config).into_owned());
+    }
+    if config.halfwidth_kana && kana.contains(utils::is_halfwidth_katakana) {
+        Cow::Owned(utils::widen_katakana(kana))
+    } else if config.combine_dakuten && kana.contains(utils::is_standalone_dakuten) {
+        Cow::Owned(utils::combine_dakuten(kana))
+    } else {
+        Cow::Borrowed(kana)
+    }
+}
+
+// checks whether the strings are equivalent if also ignoring the difference between voiced and unvoiced kana
+fn voicing_equivalent(left: &str, right: &str) -> bool {
+    let left = left.chars().map(utils::unvoiced).collect::<String>();
+    let right = right.chars().map(utils::unvoiced).collect::<String>();
+    kana_equivalent(&left, &right)
+}
+
 // checks whether the strings are equivalent if ignoring the difference between hiragana and katakana
+// ー on either side matches the vowel it extends on the other side, as in コーヒー and こうひい,
+// or nothing at all when it's left out on the other side, as in コンピューター and こんぴゅうた
 fn kana_equivalent(left: &str, right: &str) -> bool {
-    let unicode_table_distance = 96;
-    let mut previous_left = None;
-    let mut previous_right = None;
+    if left.chars().count() != right.chars().count() {
+        return (left.contains('ー') || right.contains('ー'))
+            && flexible_kana_equivalent(
+                &left.chars().map(to_hiragana).collect::<Vec<_>>(),
+                &right.chars().map(to_hiragana).collect::<Vec<_>>(),
+                None,
+            );
+    }
+    // the last character that wasn't ー, which determines the vowel ー extends
+    let mut previous = None;
     for (left, right) in left.chars().zip(right.chars()) {
-        if left == 'ー' && right != 'ー' {
-            let Some(previous_right) = previous_right  else {
-                return false
-            };
-            if !is_extension(previous_right, right) {
-                return false;
-            }
-        } else if right == 'ー' && left != 'ー' {
-            let Some(previous_left) = previous_left  else {
-                return false
-            };
-            if !is_extension(previous_left, left) {
-                return false;
+        let left = to_hiragana(left);
+        let right = to_hiragana(right);
+        let spelled_out = match (left, right) {
+            ('ー', 'ー') => continue,
+            ('ー', spelled_out) | (spelled_out, 'ー') => spelled_out,
+            _ if left == right => {
+                previous = Some(left);
+                continue;
             }
+            _ => return false,
+        };
+        if !previous.map_or(false, |previous| is_extension(previous, spelled_out)) {
+            return false;
         }
+    }
+    true
+}
 
-        let leftu32 = left as u32;
-        let rightu32 = right as u32;
-        let equivalent = leftu32 == rightu32
-            || leftu32 == rightu32 + unicode_table_distance
-            || leftu32 == rightu32 - unicode_table_distance;
-        if !equivalent {
-            return false;
+// the slower version of kana_equivalent for strings of different lengths, which tries leaving out each ー
+fn flexible_kana_equivalent(left: &[char], right: &[char], previous: Option<char>) -> bool {
+    if previous.is_some() {
+        // a ー that extends the previous kana may be missing from the other side
+        if left.first() == Some(&'ー') && flexible_kana_equivalent(&left[1..], right, previous) {
+            return true;
+        }
+        if right.first() == Some(&'ー') && flexible_kana_equivalent(left, &right[1..], previous) {
+            return true;
+        }
+    }
+    match (left, right) {
+        ([], []) => true,
+        (['ー', left_rest @ ..], ['ー', right_rest @ ..]) => {
+            flexible_kana_equivalent(left_rest, right_rest, previous)
+        }
+        (['ー', left_rest @ ..], [spelled_out, right_rest @ ..])
+        | ([spelled_out, left_rest @ ..], ['ー', right_rest @ ..]) => {
+            previous.map_or(false, |previous| is_extension(previous, *spelled_out))
+                && flexible_kana_equivalent(left_rest, right_rest, previous)
         }
-        previous_left = Some(left);
-        previous_right = Some(right);
+        ([left, left_rest @ ..], [right, right_rest @ ..]) => {
+            left == right && flexible_kana_equivalent(left_rest, right_rest, Some(*left))
+        }
+        _ => false,
+    }
+}
+
+// converts katakana to hiragana, leaving other characters as they are
+fn to_hiragana(c: char) -> char {
+    let unicode_table_distance = 96;
+    if ('ァ'..='ヶ').contains(&c) {
+        char::from_u32(c as u32 - unicode_table_distance).unwrap_or(c)
+    } else {
+        c
     }
-    left.chars().count() == right.chars().count()
 }
 
 // checks if the next char can be an "extension" of the previous char the same way ー is used for katakana.
+// expects hiragana
 fn is_extension(previous: char, next: char) -> bool {
+    let vowel = if "あかさたなはまやらわがざだばぱぁゃゎ".contains(previous) {
+        'あ'
+    } else if "いきしちにひみりぎじぢびぴぃ".contains(previous) {
+        'い'
+    } else if "うくすつぬふむゆるぐずづぶぷぅゅゔ".contains(previous) {
+        'う'
+    } else if "えけせてねへめれげぜでべぺぇ".contains(previous) {
+        'え'
+    } else if "おこそとのほもよろをごぞどぼぽぉょ".contains(previous) {
+        'お'
+    } else {
+        return false;
+    };
+    // long e and o sounds are usually written with い and う
     matches!(
-        (previous, next),
-        ('あ' | 'か' | 'さ' | 'た' | 'な' | 'は' | 'ま', 'あ' | 'ア')
-            | ('い' | 'き' | 'し' | 'ち' | 'に' | 'ひ' | 'み', 'い' | 'イ')
-            | ('う' | 'く' | 'す' | 'つ' | 'ぬ' | 'ふ' | 'む', 'う' | 'ウ')
-            | ('え' | 'け' | 'せ' | 'て' | 'ね' | 'へ' | 'め', 'え' | 'エ')
-            | ('お' | 'こ' | 'そ' | 'と' | 'の' | 'ほ' | 'も', 'お' | 'オ')
+        (vowel, next),
+        ('あ', 'あ') | ('い', 'い') | ('う', 'う') | ('え', 'え' | 'い') | ('お', 'お' | 'う')
     )
 }
 
@@ -413,7 +1802,13 @@ fn sokuonbin_equivalent(ideal_reading: &str, actual_reading: &str) -> bool {
     let last_chars_sokuonbin_accurate = if ideal_char == actual_char {
         true
     } else {
-        matches!((ideal_char, actual_char), ('く' | 'ち' | 'つ', 'っ'))
+        matches!(
+            (ideal_char, actual_char),
+            (
+                'く' | 'ち' | 'つ' | 'き' | 'ク' | 'チ' | 'ツ' | 'キ',
+                'っ' | 'ッ'
+            )
+        )
     };
     last_chars_sokuonbin_accurate
         && kana_equivalent(
@@ -422,12 +1817,23 @@ fn sokuonbin_equivalent(ideal_reading: &str, actual_reading: &str) -> bool {
         )
 }
 
+// checks if the actual reading could be the ideal reading with its last vowel lengthened, like にょう for にょ
+fn lengthening_equivalent(ideal_reading: &str, actual_reading: &str) -> bool {
+    let (Some(ideal_char), Some(actual_char)) =
+        (ideal_reading.chars().last(), actual_reading.chars().last())
+    else {
+        return false;
+    };
+    let actual_rest = &actual_reading[..actual_reading.len() - actual_char.len_utf8()];
+    kana_equivalent(ideal_reading, actual_rest)
+        && (actual_char == 'ー' || is_extension(to_hiragana(ideal_char), to_hiragana(actual_char)))
+}
+
 // checks if the kanji reading is accurate according to the possible kanji readings
 fn check_kanji_accuracy(
     kanji_readings: Option<&[String]>,
     kanji_reading: &str,
-    can_be_rendaku: bool,
-    can_be_sokuonbin: bool,
+    flags: CandidateFlags,
 ) -> Option<KanjiAccuracy> {
     let kanji_readings = kanji_readings?;
     let kanji_accurate = kanji_readings
@@ -437,15 +1843,16 @@ fn check_kanji_accuracy(
         return Some(KanjiAccuracy::Accurate);
     }
 
-    let rendaku_accurate = can_be_rendaku
-        && kanji_readings
-            .iter()
-            .any(|kr| rendaku_equivalent(kr, kanji_reading));
-    if rendaku_accurate {
+    // by Lyman's law, readings that already contain a voiced kana like the げ in とかげ don't undergo rendaku
+    let (rendaku_readings, implausible_rendaku_readings): (Vec<_>, Vec<_>) = kanji_readings
+        .iter()
+        .filter(|kr| flags.can_be_rendaku && rendaku_equivalent(kr, kanji_reading))
+        .partition(|kr| !kr.chars().skip(1).any(utils::is_voiced_obstruent));
+    if !rendaku_readings.is_empty() {
         return Some(KanjiAccuracy::AccurateWithRendaku);
     }
 
-    let sokuonbin_accurate = can_be_sokuonbin
+    let sokuonbin_accurate = flags.can_be_sokuonbin
         && kanji_readings
             .iter()
             .any(|kr| sokuonbin_equivalent(kr, kanji_reading));
@@ -453,14 +1860,63 @@ fn check_kanji_accuracy(
         return Some(KanjiAccuracy::AccurateWithSokuonbin);
     }
 
+    // like sokuonbin, lengthening happens at the boundary with what follows the kanji
+    let lengthening_accurate = flags.vowel_lengthening
+        && flags.can_be_sokuonbin
+        && kanji_readings
+            .iter()
+            .any(|kr| lengthening_equivalent(kr, kanji_reading));
+    if lengthening_accurate {
+        return Some(KanjiAccuracy::AccurateWithLengthening);
+    }
+
+    if flags.merge_yotsugana {
+        // try again with spellings like ぢゃ turned into じゃ on both sides
+        let merged_readings = kanji_readings
+            .iter()
+            .map(|kr| merged_yotsugana(kr))
+            .collect::<Vec<_>>();
+        let merged_reading = merged_yotsugana(kanji_reading);
+        if merged_readings != kanji_readings || merged_reading != kanji_reading {
+            return check_kanji_accuracy(
+                Some(&merged_readings),
+                &merged_reading,
+                CandidateFlags {
+                    merge_yotsugana: false,
+                    ..flags
+                },
+            );
+        }
+    }
+
+    if !implausible_rendaku_readings.is_empty() {
+        // the reading only differs in voicing, so it's not entirely inaccurate either
+        return Some(KanjiAccuracy::VoicingMismatch);
+    }
     Some(KanjiAccuracy::Inaccurate)
 }
 
+// replaces ぢ and づ with じ and ず, which are pronounced the same
+fn merged_yotsugana(reading: &str) -> String {
+    reading
+        .chars()
+        .map(|c| match c {
+            'ぢ' => 'じ',
+            'ヂ' => 'ジ',
+            'づ' => 'ず',
+            'ヅ' => 'ズ',
+            c => c,
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
-    fn prepare_furigana(furigana: Vec<Furigana<'_>>) -> Vec<(i32, Vec<(&str, Option<&str>)>)> {
+    type PreparedFurigana<'a> = (i32, Vec<(&'a str, Option<&'a str>)>);
+
+    fn prepare_furigana(furigana: Mappings<'_>) -> Vec<PreparedFurigana<'_>> {
         furigana
             .into_iter()
             .map(|f| {
@@ -491,22 +1947,936 @@ mod test {
     }
 
     #[test]
-    fn segments_with_kanji() {
+    fn handles_arbitrary_unicode() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        let inputs = [
+            "物の怪",
+            "もののけ",
+            "ｶﾀｶﾅ",
+            "e\u{301}",
+            "🦀",
+            "物🦀の",
+            "の🦀",
+            "ヶ月",
+            "一ヶ🦀",
+            "ー",
+            "3ー",
+            "Ⅻ",
+            "",
+        ];
+        for word in inputs {
+            for reading in inputs {
+                for mappings in [
+                    crate::map_naive(word, reading),
+                    crate::map(word, reading, &kanji_to_readings),
+                ] {
+                    for mapping in &mappings {
+                        assert_eq!(crate::check_invariants(mapping, word, reading), Ok(()));
+                    }
+                }
+                crate::is_mappable(word, reading);
+                crate::count_mappings(word, reading);
+            }
+        }
+        assert!(crate::map_naive("ビール", "なにか").is_empty());
+    }
+
+    #[test]
+    fn passes_other_characters_through() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("東".to_string(), vec!["ひがし".to_string()]);
+        kanji_to_readings.insert("西".to_string(), vec!["にし".to_string()]);
+        let best = |reading, other_policy| {
+            let config = MapConfig::builder().other_policy(other_policy).build();
+            crate::map_with("東＆西", reading, &kanji_to_readings, &config)
+                .best()
+                .map(|f| {
+                    f.furigana
+                        .iter()
+                        .map(|s| (s.segment, s.reading))
+                        .collect::<Vec<_>>()
+                })
+        };
+        assert_eq!(best("ひがしにし", OtherPolicy::Fail), None);
+        assert_eq!(
+            best("ひがしにし", OtherPolicy::PassThroughNoReading),
+            Some(vec![("東", "ひがし"), ("＆", ""), ("西", "にし")])
+        );
+        assert_eq!(
+            best("ひがし＆にし", OtherPolicy::PassThroughConsumeMatchingKana),
+            Some(vec![("東", "ひがし"), ("＆", "＆"), ("西", "にし")])
+        );
+        assert_eq!(
+            best("ひがしにし", OtherPolicy::PassThroughConsumeMatchingKana),
+            Some(vec![("東", "ひがし"), ("＆", ""), ("西", "にし")])
+        );
+    }
+
+    #[test]
+    fn combines_standalone_dakuten() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("学".to_string(), vec!["がく".to_string()]);
+        kanji_to_readings.insert("校".to_string(), vec!["こう".to_string()]);
+        let default = MapConfig::default();
+        let config = MapConfig::builder().combine_dakuten(true).build();
+        let map = |word, reading, config| {
+            prepare_furigana(crate::map_with(word, reading, &kanji_to_readings, config))
+        };
+        assert!(map("ひらか゛な", "ひらがな", &default).is_empty());
+        assert_eq!(
+            map("ひらか゛な", "ひらがな", &config),
+            vec![(1, vec![("ひらか゛な", None)])]
+        );
+        assert!(map("学校", "か゛っこう", &default)[0].0 < 3);
+        let mappings = map("学校", "か゛っこう", &config);
+        assert_eq!(mappings.len(), 2);
+        assert!(mappings.contains(&(3, vec![("学", Some("か゛っ")), ("校", Some("こう"))])));
+        assert_eq!(
+            crate::utils::combine_dakuten("は゜ーと゛ウ\u{3099}゛"),
+            "ぱーどヴ゛"
+        );
+    }
+
+    #[test]
+    fn maps_ocr_text() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("食".to_string(), vec!["た".to_string()]);
+        let default = MapConfig::default();
+        let ocr = MapConfig::ocr();
+        let map = |word, reading, config| {
+            prepare_furigana(crate::map_with(word, reading, &kanji_to_readings, config))
+        };
+        for (word, reading) in [
+            ("ｶﾞｯｺｳ", "がっこう"),
+            ("食ﾍﾞﾙ", "たべる"),
+            ("と゛こ！", "どこ"),
+        ] {
+            assert!(map(word, reading, &default).is_empty(), "{word}");
+            assert_eq!(map(word, reading, &ocr).len(), 1, "{word}");
+        }
+        assert_eq!(
+            map("食べる", "ﾀﾍﾞﾙ", &ocr),
+            vec![(2, vec![("食", Some("ﾀ")), ("べる", Some("ﾍﾞﾙ"))])]
+        );
+        assert_eq!(crate::utils::widen_katakana("ｶﾞｯｺｳﾊﾟﾝ"), "ガッコウパン");
+    }
+
+    #[test]
+    fn classifies_dashes_by_context() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("統".to_string(), vec!["とう".to_string()]);
+        kanji_to_readings.insert(
+            "一".to_string(),
+            vec!["いち".to_string(), "いつ".to_string()],
+        );
+        kanji_to_readings.insert("屋".to_string(), vec!["や".to_string()]);
+        let default = MapConfig::default();
+        let config = MapConfig::builder().dashes_by_context(true).build();
+        let map = |word, reading, config| {
+            prepare_furigana(crate::map_with(word, reading, &kanji_to_readings, config))
+        };
+        assert!(map("ラ一メン屋", "らーめんや", &default).is_empty());
+        assert_eq!(
+            map("ラ一メン屋", "らーめんや", &config),
+            vec![(2, vec![("ラ一メン", Some("らーめん")), ("屋", Some("や"))])]
+        );
+        assert!(map("統ー", "とういつ", &default).is_empty());
+        assert!(map("統ー", "とういつ", &config)
+            .contains(&(4, vec![("統", Some("とう")), ("ー", Some("いつ"))])));
+        // 一 stays a kanji when it's followed by something other than katakana
+        assert_eq!(
+            map("ラーメン一つ", "らーめんひとつ", &config),
+            map("ラーメン一つ", "らーめんひとつ", &default)
+        );
+    }
+
+    #[test]
+    fn ignores_middle_dots() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("東".to_string(), vec!["とう".to_string()]);
+        kanji_to_readings.insert("京".to_string(), vec!["きょう".to_string()]);
+        let default = MapConfig::default();
+        let config = MapConfig::builder().ignore_middle_dots(true).build();
+        let map = |word, reading, config| {
+            prepare_furigana(crate::map_with(word, reading, &kanji_to_readings, config))
+        };
+        assert!(map("CD", "シー・ディー", &default).is_empty());
+        assert_eq!(
+            map("CD", "シー・ディー", &config),
+            vec![(0, vec![("C", Some("シー・")), ("D", Some("ディー"))])]
+        );
+        let mappings = map("東京", "とう・きょう", &config);
+        // the dot can go with either kanji
+        assert_eq!(
+            mappings
+                .iter()
+                .filter(|(accuracy, _)| *accuracy == 4)
+                .count(),
+            2
+        );
+        assert_eq!(
+            map("東京", "とうきょう", &config),
+            map("東京", "とうきょう", &default)
+        );
+    }
+
+    #[test]
+    fn keeps_middle_dots_at_the_edges() {
+        let kanji_to_readings = HashMap::new();
+        let config = MapConfig::builder()
+            .ignore_middle_dots(true)
+            .omitted_okurigana(true)
+            .build();
+        for (word, reading) in [
+            ("日か", "・ひ"),
+            ("東京", "・とうきょう"),
+            ("東京", "とうきょう・"),
+            ("歳日", "のひつん・"),
+        ] {
+            let mappings = crate::map_with(word, reading, &kanji_to_readings, &config);
+            assert!(!mappings.is_empty(), "{reading}");
+            for mapping in &mappings {
+                assert_eq!(crate::check_invariants(mapping, word, reading), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn accepts_omitted_okurigana() {
+        let kanji_to_readings = HashMap::new();
+        let default = MapConfig::default();
+        let config = MapConfig::builder().omitted_okurigana(true).build();
+        let map = |word, reading, config| {
+            prepare_furigana(crate::map_with(word, reading, &kanji_to_readings, config))
+        };
+        assert!(map("食べる", "た", &default).is_empty());
+        assert_eq!(
+            map("食べる", "た", &config),
+            vec![(0, vec![("食", Some("た")), ("べる", None)])]
+        );
+        assert_eq!(
+            map("食べる", "たべ", &config),
+            vec![(0, vec![("食", Some("た")), ("べ", None), ("る", None)])]
+        );
+        assert_eq!(
+            map("食べる", "たべる", &config),
+            vec![(0, vec![("食", Some("た")), ("べる", None)])]
+        );
+        for mapping in &crate::map_with("食べる", "たべ", &kanji_to_readings, &config) {
+            assert_eq!(crate::check_invariants(mapping, "食べる", "たべ"), Ok(()));
+        }
+    }
+
+    #[test]
+    fn trims_whitespace() {
+        let kanji_to_readings = HashMap::new();
+        let config = MapConfig::builder()
+            .whitespace_policy(WhitespacePolicy::Trim)
+            .build();
+        let (word, reading) = (" 食べる", "たべる\u{3000}");
+        assert!(crate::map(word, reading, &kanji_to_readings).is_empty());
+        let mappings = crate::map_with(word, reading, &kanji_to_readings, &config);
+        assert_eq!(
+            mappings
+                .iter()
+                .map(|f| f
+                    .furigana
+                    .iter()
+                    .map(|s| (s.segment, s.reading))
+                    .collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            vec![vec![
+                (" ", ""),
+                ("食", "た"),
+                ("べる", "べる"),
+                ("", "\u{3000}")
+            ]]
+        );
+        for mapping in &mappings {
+            assert_eq!(crate::check_invariants(mapping, word, reading), Ok(()));
+        }
+
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("東".to_string(), vec!["とう".to_string()]);
+        kanji_to_readings.insert("京".to_string(), vec!["きょう".to_string()]);
+        let skip = MapConfig::builder()
+            .whitespace_policy(WhitespacePolicy::Skip)
+            .build();
+        let (word, reading) = ("東京タワー", "とう きょう　たわー ");
+        // without skipping, the whitespace makes the readings of the kanji inaccurate
+        let trimmed = crate::map_with(word, reading, &kanji_to_readings, &config);
+        assert!(trimmed.best().unwrap().accuracy < 4);
+        let mappings = crate::map_with(word, reading, &kanji_to_readings, &skip);
+        let best = mappings.best().unwrap();
+        assert_eq!(best.accuracy, 4);
+        assert_eq!(best.furigana.last().unwrap().reading, " ");
+    }
+
+    #[test]
+    fn lists_candidate_readings() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("学".to_string(), vec!["がく".to_string()]);
+        let flags = CandidateFlags {
+            can_be_sokuonbin: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            crate::candidate_readings("学", "がっこう", &kanji_to_readings, flags),
+            vec![
+                ("が", Some(KanjiAccuracy::Inaccurate)),
+                ("がっ", Some(KanjiAccuracy::AccurateWithSokuonbin)),
+                ("がっこ", Some(KanjiAccuracy::Inaccurate)),
+                ("がっこう", Some(KanjiAccuracy::Inaccurate)),
+            ]
+        );
+        assert_eq!(
+            crate::candidate_readings("校", "こう", &kanji_to_readings, flags)[0],
+            ("こ", None)
+        );
+        assert_eq!(
+            crate::candidate_readings("今", "きょう", &kanji_to_readings, flags),
+            vec![("きょ", None), ("きょう", None)]
+        );
+        assert!(crate::candidate_readings("学", "っこう", &kanji_to_readings, flags).is_empty());
+    }
+
+    #[test]
+    fn models_rendaku() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("合".to_string(), vec!["あい".to_string()]);
+        kanji_to_readings.insert("鍵".to_string(), vec!["かぎ".to_string()]);
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        kanji_to_readings.insert("語".to_string(), vec!["かたり".to_string()]);
+        kanji_to_readings.insert("金".to_string(), vec!["かね".to_string()]);
+        let accuracy_of_last = |word, reading| {
+            crate::map(word, reading, &kanji_to_readings)
+                .best()
+                .and_then(|f| f.furigana.last().unwrap().kanji_accuracy)
+        };
+        assert_eq!(
+            accuracy_of_last("物語", "ものがたり"),
+            Some(KanjiAccuracy::AccurateWithRendaku)
+        );
+        // Lyman's law: かぎ already contains a voiced kana
+        assert_eq!(
+            accuracy_of_last("合鍵", "あいがぎ"),
+            Some(KanjiAccuracy::VoicingMismatch)
+        );
+        // the お is a prefix rather than part of a compound
+        assert_eq!(
+            accuracy_of_last("お金", "おがね"),
+            Some(KanjiAccuracy::Inaccurate)
+        );
+    }
+
+    #[test]
+    fn detects_transcriptions() {
+        assert!(crate::is_kana_only("ビール"));
+        assert!(!crate::is_kana_only("生ビール"));
+        assert!(crate::is_transcription("ビール", "びーる"));
+        assert!(crate::is_transcription("コーヒー", "こうひい"));
+        assert!(!crate::is_transcription("ビール", "びある"));
+        assert!(!crate::is_transcription("生ビール", "なまびーる"));
+    }
+
+    #[test]
+    fn grades_with_multiple_sources() {
+        let mut kanjidic = HashMap::new();
+        kanjidic.insert("物".to_string(), vec!["もの".to_string()]);
+        kanjidic.insert("怪".to_string(), vec!["カイ".to_string()]);
+        let mut overrides = HashMap::new();
+        overrides.insert("怪".to_string(), vec!["け".to_string()]);
+        let sources = [
+            ReadingSource::new("kanjidic", &kanjidic),
+            ReadingSource::new("overrides", &overrides),
+        ];
+        let mappings =
+            crate::map_with_sources("物の怪", "もののけ", &sources, &MapConfig::default());
+        let best = mappings.best().unwrap();
+        assert_eq!(best.accuracy, 4);
+        assert_eq!(
+            best.furigana
+                .iter()
+                .map(|f| (f.segment, f.source))
+                .collect::<Vec<_>>(),
+            vec![("物", Some(0)), ("の", None), ("怪", Some(1))]
+        );
+    }
+
+    #[test]
+    fn segments_with_kanji() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        kanji_to_readings.insert("怪".to_string(), vec!["け".to_string()]);
+        let furigana = prepare_furigana(crate::map("物の怪", "もののけ", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(
+            4,
+            vec![("物", Some("もの")), ("の", None), ("怪", Some("け"))],
+        )));
+        assert!(furigana.contains(&(
+            -4,
+            vec![("物", Some("も")), ("の", None), ("怪", Some("のけ"))],
+        )));
+        assert_eq!(furigana.len(), 2);
+    }
+
+    #[test]
+    fn handles_dates() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("三".to_string(), vec!["サン".to_string(), "み".to_string()]);
+        kanji_to_readings.insert(
+            "月".to_string(),
+            vec!["ゲツ".to_string(), "つき".to_string()],
+        );
+        kanji_to_readings.insert("日".to_string(), vec!["ニチ".to_string(), "ひ".to_string()]);
+        kanji_to_readings.insert("曜".to_string(), vec!["ヨウ".to_string()]);
+        let furigana = prepare_furigana(crate::map("三日", "みっか", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        let best = furigana.iter().max_by_key(|f| f.0).unwrap();
+        assert_eq!(best, &(4, vec![("三", Some("みっ")), ("日", Some("か"))]));
+
+        let furigana = prepare_furigana(crate::map("三月", "さんがつ", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        let best = furigana.iter().max_by_key(|f| f.0).unwrap();
+        assert_eq!(best, &(4, vec![("三", Some("さん")), ("月", Some("がつ"))]));
+
+        let furigana = prepare_furigana(crate::map("4日", "よっか", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(2, vec![("4", Some("よっ")), ("日", Some("か"))])));
+
+        let furigana = prepare_furigana(crate::map("月曜日", "げつようび", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        let best = furigana.iter().max_by_key(|f| f.0).unwrap();
+        assert_eq!(
+            best,
+            &(
+                6,
+                vec![
+                    ("月", Some("げつ")),
+                    ("曜", Some("よう")),
+                    ("日", Some("び"))
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn handles_ordinals() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("第".to_string(), vec!["ダイ".to_string()]);
+        kanji_to_readings.insert(
+            "回".to_string(),
+            vec!["カイ".to_string(), "まわ".to_string()],
+        );
+        let furigana = prepare_furigana(crate::map("第3回", "だいさんかい", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert_eq!(
+            furigana,
+            vec![(
+                4,
+                vec![
+                    ("第", Some("だい")),
+                    ("3", Some("さん")),
+                    ("回", Some("かい"))
+                ]
+            )]
+        );
+
+        let furigana = prepare_furigana(crate::map("第1回", "だいいっかい", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert_eq!(
+            furigana,
+            vec![(
+                4,
+                vec![
+                    ("第", Some("だい")),
+                    ("1", Some("いっ")),
+                    ("回", Some("かい"))
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn assimilates_one_before_counters() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("本".to_string(), vec!["ホン".to_string()]);
+        kanji_to_readings.insert("冊".to_string(), vec!["サツ".to_string()]);
+        let mappings = crate::map("1冊", "いっさつ", &kanji_to_readings);
+        let best = mappings.best().unwrap();
+        assert_eq!(best.furigana[0].furigana, Some("いっ"));
+        assert_eq!(
+            best.furigana[1].kanji_accuracy,
+            Some(KanjiAccuracy::Accurate)
+        );
+        let mappings = crate::map("1本", "いっぽん", &kanji_to_readings);
+        let best = mappings.best().unwrap();
+        assert_eq!(best.furigana[0].furigana, Some("いっ"));
+        assert_eq!(
+            best.furigana[1].kanji_accuracy,
+            Some(KanjiAccuracy::AccurateWithSokuonbinAndRendaku)
+        );
+
+        kanji_to_readings.insert("十".to_string(), vec!["ジュウ".to_string()]);
+        kanji_to_readings.insert("階".to_string(), vec!["カイ".to_string()]);
+        for (word, reading, expected) in [
+            ("十階", "じゅっかい", "じゅっ"),
+            ("8冊", "はっさつ", "はっ"),
+            ("6本", "ろっぽん", "ろっ"),
+        ] {
+            let mappings = crate::map(word, reading, &kanji_to_readings);
+            let best = mappings.best().unwrap();
+            assert_eq!(best.furigana[0].furigana, Some(expected), "{word}");
+            assert!(best.accuracy > 0, "{word}");
+        }
+    }
+
+    #[test]
+    fn handles_tsu_counter() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("八".to_string(), vec!["ハチ".to_string(), "や".to_string()]);
+        kanji_to_readings.insert("十".to_string(), vec!["ジュウ".to_string()]);
+        let furigana = prepare_furigana(crate::map("八つ", "やっつ", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(2, vec![("八", Some("やっ")), ("つ", None)])));
+
+        let furigana = prepare_furigana(crate::map("十", "とお", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(1, vec![("十", Some("とお"))])));
+
+        let furigana = prepare_furigana(crate::map("3つ", "みっつ", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(0, vec![("3", Some("みっ")), ("つ", None)])));
+    }
+
+    #[test]
+    fn splits_segments_into_kanji() {
+        let mappings = crate::map_naive("日本語を話す", "にほんごをはなす");
+        let segments = &mappings[0].furigana;
+        println!("{segments:?}");
+
+        assert_eq!(
+            segments[0].kanji().collect::<Vec<_>>(),
+            vec![("日", None), ("本", None), ("語", None)]
+        );
+        assert_eq!(
+            segments[2].kanji().collect::<Vec<_>>(),
+            vec![("話", Some("はな"))]
+        );
+        assert!(segments[1].kanji().next().is_none());
+    }
+
+    #[test]
+    fn checks_mappability() {
+        assert!(crate::is_mappable("物の怪", "もののけ"));
+        assert!(crate::is_mappable("第3回", "だいさんかい"));
+        assert!(crate::is_mappable("すし", "スシ"));
+        assert!(!crate::is_mappable("物の怪", "もけ"));
+        assert!(!crate::is_mappable("3回", "よんかい"));
+        assert!(!crate::is_mappable("すし", "すしや"));
+
+        let inputs = [
+            "物の怪",
+            "もののけ",
+            "もけ",
+            "一ヶ月",
+            "いっかげつ",
+            "〆切だ",
+            "しめきりだ",
+            "3回",
+            "さんかい",
+            "日",
+            "ｶﾀｶﾅ",
+            "カタカナ",
+            "🦀",
+            "",
+        ];
+        for word in inputs {
+            for reading in inputs {
+                assert_eq!(
+                    crate::is_mappable(word, reading),
+                    crate::count_mappings(word, reading) > 0,
+                    "{word} {reading}"
+                );
+                assert_eq!(
+                    crate::count_mappings(word, reading),
+                    crate::map_naive(word, reading).len()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn counts_mappings() {
+        for (word, reading) in [
+            ("物の怪", "もののけ"),
+            ("物の怪", "もけ"),
+            ("日本語", "にほんご"),
+            ("お茶わん", "おちゃわん"),
+            ("すし", "すし"),
+        ] {
+            assert_eq!(
+                crate::count_mappings(word, reading),
+                crate::map_naive(word, reading).len(),
+                "{word} {reading}"
+            );
+        }
+        assert_eq!(crate::count_mappings("物の怪", "もののけ"), 2);
+    }
+
+    #[test]
+    fn pages_through_mappings() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        let tree = crate::map_tree(
+            "日本語物",
+            "にほんごもの",
+            &kanji_to_readings,
+            &MapConfig::default(),
+        );
+        let all = Furigana::from_tree(&tree);
+        assert!(all.len() > 3);
+        assert_eq!(tree.mappings_range(0..all.len() + 5), all);
+        assert_eq!(tree.mappings_range(1..3), all[1..3]);
+        assert_eq!(tree.nth_mapping(2).as_ref(), all.get(2));
+        assert_eq!(tree.nth_mapping(all.len()), None);
+        assert_eq!(tree.nth_mapping(usize::MAX), None);
+    }
+
+    #[test]
+    fn lists_segment_candidates() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        kanji_to_readings.insert("怪".to_string(), vec!["け".to_string()]);
+        let tree = crate::map_tree(
+            "物の怪",
+            "もののけ",
+            &kanji_to_readings,
+            &MapConfig::default(),
+        );
+        let candidates = tree.segment_candidates();
+        let summary = candidates
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|candidate| (candidate.start, candidate.reading, candidate.score))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                vec![(0, "もの", 4), (0, "も", -4)],
+                vec![(6, "け", 4), (6, "のけ", -4)],
+            ]
+        );
+        assert_eq!(candidates[0][0].fraction(), 0.5);
+
+        let tree = crate::map_tree(
+            "日本語物",
+            "にほんごもの",
+            &kanji_to_readings,
+            &MapConfig::default(),
+        );
+        let candidates = tree.segment_candidates();
+        // every mapping has a segment at the start of the word
+        let total = tree.count_mappings();
+        assert_eq!(candidates[0].iter().map(|c| c.count).sum::<usize>(), total);
+        let last = candidates.last().unwrap();
+        assert_eq!(last[0].reading, "もの");
+        assert!(last[0].count > 1);
+
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("亜".to_string(), vec!["あ".to_string()]);
+        let tree = crate::map_tree(
+            "亜亜亜",
+            "あああああ",
+            &kanji_to_readings,
+            &MapConfig::default(),
+        );
+        for group in tree.segment_candidates() {
+            let mut readings = group.iter().map(|c| c.reading).collect::<Vec<_>>();
+            readings.sort_unstable();
+            readings.dedup();
+            assert_eq!(readings.len(), group.len());
+        }
+        // the second 亜 reads あ after each reading of the first one, in 3 of the 6 mappings
+        let candidates = tree.segment_candidates();
+        let second = candidates[1].iter().find(|c| c.reading == "あ").unwrap();
+        assert_eq!((second.count, second.total), (3, 6));
+        assert_eq!(second.fraction(), 0.5);
+    }
+
+    #[test]
+    fn reweights_trees() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        kanji_to_readings.insert("怪".to_string(), vec!["け".to_string()]);
+        let tree = crate::map_tree(
+            "物の怪",
+            "もののけ",
+            &kanji_to_readings,
+            &MapConfig::default(),
+        );
+        let furigana = Furigana::from_tree(&tree);
+        assert_eq!(
+            furigana,
+            crate::map("物の怪", "もののけ", &kanji_to_readings).into_vec()
+        );
+
+        let weights = AccuracyWeights {
+            accurate: 5,
+            inaccurate: 0,
+            ..AccuracyWeights::default()
+        };
+        let furigana = Furigana::from_tree_with(&tree, &weights);
+        let accuracies = furigana.iter().map(|f| f.accuracy).collect::<Vec<_>>();
+        println!("{accuracies:?}");
+
+        assert_eq!(accuracies, vec![0, 10]);
+
+        let tree = crate::map_tree("すし", "すし", &kanji_to_readings, &MapConfig::default());
+        assert_eq!(Furigana::from_tree(&tree).len(), 1);
+
+        let config = MapConfig::builder()
+            .whitespace_policy(WhitespacePolicy::Trim)
+            .build();
+        let tree = crate::map_tree(" 物の怪", "もののけ　", &kanji_to_readings, &config);
+        assert_eq!((tree.word, tree.reading), ("物の怪", "もののけ"));
+        assert_eq!(tree.count_mappings(), 2);
+    }
+
+    #[test]
+    fn finds_reading_spans() {
+        let mappings = crate::map_naive("物の怪", "もののけ");
+        let mapping = &mappings[1];
+        println!("{mapping}");
+
+        assert_eq!(mapping.reading_span_of(0), 0..6);
+        assert_eq!(mapping.reading_span_of(1), 6..9);
+        assert_eq!(mapping.reading_span_of(2), 9..12);
+
+        let reading = "さんからよっかかん";
+        let mappings = crate::map("3〜4日間", reading, &HashMap::new());
+        let mapping = mappings.best().unwrap();
+        let spans = (0..mapping.furigana.len())
+            .map(|idx| &reading[mapping.reading_span_of(idx)])
+            .collect::<Vec<_>>();
+        assert_eq!(spans, vec!["さん", "から", "よっ", "か", "かん"]);
+    }
+
+    #[test]
+    fn explains_accuracy() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        kanji_to_readings.insert("怪".to_string(), vec!["け".to_string()]);
+        let mappings = crate::map("物の怪", "もののけ", &kanji_to_readings);
+        let explanations = mappings
+            .iter_ranked()
+            .map(Furigana::explain)
+            .collect::<Vec<_>>();
+        println!("{explanations:?}");
+
+        assert_eq!(
+            explanations,
+            vec![
+                "物→もの: dictionary match (+2); 怪→け: dictionary match (+2)",
+                "物→も: not in dictionary (-2); 怪→のけ: not in dictionary (-2)",
+            ]
+        );
+    }
+
+    #[test]
+    fn converts_to_owned_pairs() {
+        let pairs = {
+            let word = String::from("食べる");
+            let reading = String::from("たべる");
+            let mappings = crate::map_naive(&word, &reading);
+            mappings[0].to_pairs()
+        };
+        assert_eq!(
+            pairs,
+            vec![
+                ("食".to_string(), Some("た".to_string())),
+                ("べる".to_string(), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn identifies_mappings() {
+        let mappings = crate::map_naive("物の怪", "もののけ");
+        let ids = mappings.iter().map(Furigana::id).collect::<Vec<_>>();
+        assert_ne!(ids[0], ids[1]);
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        let remapped = crate::map("物の怪", "もののけ", &kanji_to_readings);
+        let found = remapped.find_id(ids[1]).unwrap();
+        assert_eq!(found.furigana[0].furigana, Some("もの"));
+        // the identifiers must not change between versions
+        assert_eq!(ids[1], 10502419582664909871);
+    }
+
+    #[test]
+    fn ranks_candidate_readings() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert(
+            "日".to_string(),
+            vec!["に".to_string(), "にち".to_string(), "ひ".to_string()],
+        );
+        kanji_to_readings.insert("本".to_string(), vec!["ほん".to_string()]);
+        let ranked = crate::best_reading_for(
+            "日本",
+            &["じっぽん", "にっぽん", "にほん", "ひのもと"],
+            &kanji_to_readings,
+        );
+        assert_eq!(ranked[0], ("にほん", Some(4)));
+        assert_eq!(ranked[1].0, "にっぽん");
+        assert!(ranked[2..].iter().all(|(_, accuracy)| accuracy < &Some(3)));
+        let ranked = crate::best_reading_for("物の怪", &["もけ", "もののけ"], &kanji_to_readings);
+        assert_eq!(ranked[1], ("もけ", None));
+    }
+
+    #[test]
+    fn reranks_mappings() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        kanji_to_readings.insert("怪".to_string(), vec!["け".to_string()]);
+        let reranked =
+            crate::map_reranked("物の怪", "もののけ", &kanji_to_readings, |mapping| {
+                // prefers the mapping that reads 物 as も
+                if mapping.furigana[0].reading == "も" {
+                    1.0
+                } else {
+                    f64::NAN
+                }
+            });
+        assert_eq!(reranked.len(), 2);
+        assert_eq!(reranked[0].0.furigana[0].furigana, Some("も"));
+        assert_eq!(reranked[0].1, 1.0);
+        assert_eq!(reranked[1].0.furigana[0].furigana, Some("もの"));
+        assert_eq!(reranked[1].1, f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn maps_multiple_readings() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("鼻".to_string(), vec!["はな".to_string()]);
+        kanji_to_readings.insert("血".to_string(), vec!["ち".to_string()]);
+        let merged = crate::map_multi("鼻血", &["はなじ", "はなぢ", "ハナヂ"], &kanji_to_readings);
+        let readings = merged
+            .iter()
+            .map(|(reading, mapping)| (*reading, mapping.furigana[1].reading))
+            .collect::<Vec<_>>();
+        // the katakana reading only duplicates the mappings of はなぢ
+        assert_eq!(
+            readings,
+            vec![
+                ("はなぢ", "ぢ"),
+                ("はなじ", "じ"),
+                ("はなじ", "なじ"),
+                ("はなぢ", "なぢ")
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_katakana_okurigana() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("食".to_string(), vec!["た".to_string()]);
+        kanji_to_readings.insert("込".to_string(), vec!["こ".to_string()]);
+        let katakana = crate::map("食ベル", "たべる", &kanji_to_readings);
+        let hiragana = crate::map("食べる", "たべる", &kanji_to_readings);
+        assert_eq!(katakana.len(), 1);
+        assert_eq!(katakana[0].accuracy, hiragana[0].accuracy);
+        assert_eq!(katakana[0].furigana[1].furigana, None);
+        assert_eq!(katakana[0].to_brackets(), "食[た]ベル");
+
+        let mappings = crate::map("飛ビ込ム", "とびこむ", &kanji_to_readings);
+        assert_eq!(mappings[0].furigana[3].reading, "む");
+        assert!(crate::why_not(
+            "食ベル",
+            "たべる",
+            &[("食", Some("た")), ("ベル", None)],
+            &kanji_to_readings
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn points_into_word_and_reading() {
+        let config = MapConfig::builder()
+            .omitted_okurigana(true)
+            .other_policy(OtherPolicy::PassThroughNoReading)
+            .build();
+        let (word, reading) = ("物の怪＆食べる", "もののけた");
+        let mappings = crate::map_with(word, reading, &HashMap::new(), &config);
+        assert!(!mappings.is_empty());
+        for mapping in &mappings {
+            for (idx, segment) in mapping.furigana.iter().enumerate() {
+                assert_eq!(
+                    segment.segment_range(word),
+                    Some(mapping.segment_span_of(idx))
+                );
+                assert_eq!(
+                    segment.reading_range(reading),
+                    Some(mapping.reading_span_of(idx))
+                );
+            }
+        }
+        let segment = FuriganaSegment::new("物", Some("もの"), "もの", None);
+        assert_eq!(segment.segment_range(word), None);
+    }
+
+    #[test]
+    fn builtin_readings_point_into_reading() {
+        // letters, numbers, ヶ and special cases are matched against built-in readings,
+        // but the readings of their segments are still taken from the given reading
+        for (word, reading) in [
+            ("Aランク", "エーランク"),
+            ("一ヶ月", "いっかげつ"),
+            ("3000円", "さんぜんえん"),
+            ("大人しい", "おとなしい"),
+        ] {
+            let mappings = crate::map_naive(word, reading);
+            assert!(!mappings.is_empty(), "{word}");
+            for segment in mappings.iter().flat_map(|mapping| &mapping.furigana) {
+                assert!(segment.reading_range(reading).is_some(), "{word}");
+            }
+        }
+    }
+
+    #[test]
+    fn formats_brackets() {
+        let mappings = crate::map_naive("物の怪", "もののけ");
+        assert_eq!(mappings[1].to_brackets(), "物[もの]の 怪[け]");
+        let mappings = crate::map_naive("大学生", "だいがくせい");
+        assert_eq!(mappings[0].to_brackets(), "大学生[だいがくせい]");
+    }
+
+    #[test]
+    fn adds_accuracy_classes() {
         let mut kanji_to_readings = HashMap::new();
         kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
-        kanji_to_readings.insert("怪".to_string(), vec!["け".to_string()]);
-        let furigana = prepare_furigana(crate::map("物の怪", "もののけ", &kanji_to_readings));
-        println!("{furigana:?}");
-
-        assert!(furigana.contains(&(
-            4,
-            vec![("物", Some("もの")), ("の", None), ("怪", Some("け"))],
-        )));
-        assert!(furigana.contains(&(
-            -4,
-            vec![("物", Some("も")), ("の", None), ("怪", Some("のけ"))],
-        )));
-        assert_eq!(furigana.len(), 2);
+        let mappings = crate::map("物の怪", "もののけ", &kanji_to_readings);
+        let html = mappings.best().unwrap().to_html_with_classes();
+        assert_eq!(
+            html,
+            "<ruby>物<rt class=\"furigana-accurate\">もの</rt>の<rt></rt>怪<rt class=\"furigana-guess\">け</rt></ruby>"
+        );
     }
 
     #[test]
@@ -553,6 +2923,340 @@ mod test {
         assert_eq!(furigana.len(), 1);
     }
 
+    #[test]
+    fn handles_numbers() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("円".to_string(), vec!["えん".to_string()]);
+        let furigana = prepare_furigana(crate::map(
+            "１2０円",
+            "ひゃくにじゅうえん",
+            &kanji_to_readings,
+        ));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(
+            2,
+            vec![("１2０", Some("ひゃくにじゅう")), ("円", Some("えん"))]
+        )));
+        assert_eq!(furigana.len(), 1);
+    }
+
+    #[test]
+    fn handles_roman_numerals() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("世".to_string(), vec!["せい".to_string()]);
+        let furigana = prepare_furigana(crate::map("Ⅲ世", "さんせい", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(2, vec![("Ⅲ", Some("さん")), ("世", Some("せい"))])));
+        assert_eq!(furigana.len(), 1);
+
+        let furigana = prepare_furigana(crate::map_naive("ロッキーII", "ロッキーツー"));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(0, vec![("ロッキー", None), ("II", Some("ツー"))])));
+        assert_eq!(furigana.len(), 1);
+    }
+
+    #[test]
+    fn handles_ranges() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("日".to_string(), vec!["にち".to_string()]);
+        let furigana = prepare_furigana(crate::map("2〜3日", "にからさんにち", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(
+            2,
+            vec![
+                ("2", Some("に")),
+                ("〜", Some("から")),
+                ("3", Some("さん")),
+                ("日", Some("にち"))
+            ]
+        )));
+        assert_eq!(furigana.len(), 1);
+
+        let furigana = prepare_furigana(crate::map("2～3日", "にさんにち", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(
+            2,
+            vec![
+                ("2", Some("に")),
+                ("～", None),
+                ("3", Some("さん")),
+                ("日", Some("にち"))
+            ]
+        )));
+        assert_eq!(furigana.len(), 1);
+    }
+
+    #[test]
+    fn handles_fractions_and_ratios() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("分".to_string(), vec!["ふん".to_string(), "ぶ".to_string()]);
+        let furigana = prepare_furigana(crate::map("3分の1", "さんぶんのいち", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        let best = furigana.iter().max_by_key(|f| f.0).unwrap();
+        assert_eq!(
+            best,
+            &(
+                2,
+                vec![
+                    ("3", Some("さん")),
+                    ("分", Some("ぶん")),
+                    ("の", None),
+                    ("1", Some("いち"))
+                ]
+            )
+        );
+
+        let furigana = prepare_furigana(crate::map_naive("2：1", "にたいいち"));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(
+            0,
+            vec![("2", Some("に")), ("：", Some("たい")), ("1", Some("いち"))]
+        )));
+        assert_eq!(furigana.len(), 1);
+    }
+
+    #[test]
+    fn handles_times() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("時".to_string(), vec!["とき".to_string()]);
+        kanji_to_readings.insert("分".to_string(), vec!["ぶ".to_string()]);
+        kanji_to_readings.insert("半".to_string(), vec!["なか".to_string()]);
+        let furigana = prepare_furigana(crate::map("4時半", "よじはん", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        let best = furigana.iter().max_by_key(|f| f.0).unwrap();
+        assert_eq!(
+            best,
+            &(
+                4,
+                vec![("4", Some("よ")), ("時", Some("じ")), ("半", Some("はん"))]
+            )
+        );
+
+        let furigana = prepare_furigana(crate::map(
+            "9時16分",
+            "くじじゅうろっぷん",
+            &kanji_to_readings,
+        ));
+        println!("{furigana:?}");
+
+        let best = furigana.iter().max_by_key(|f| f.0).unwrap();
+        assert_eq!(
+            best,
+            &(
+                4,
+                vec![
+                    ("9", Some("く")),
+                    ("時", Some("じ")),
+                    ("16", Some("じゅうろっ")),
+                    ("分", Some("ぷん"))
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn handles_numbers_with_magnitudes() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("円".to_string(), vec!["えん".to_string()]);
+        let furigana = prepare_furigana(crate::map(
+            "3万5000円",
+            "さんまんごせんえん",
+            &kanji_to_readings,
+        ));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(
+            2,
+            vec![("3万5000", Some("さんまんごせん")), ("円", Some("えん"))]
+        )));
+        assert_eq!(furigana.len(), 1);
+    }
+
+    #[test]
+    fn handles_ages() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("歳".to_string(), vec!["とし".to_string()]);
+        for (word, reading, number_reading) in [
+            ("1歳", "いっさい", "いっ"),
+            ("8才", "はっさい", "はっ"),
+            ("10歳", "じゅっさい", "じゅっ"),
+            ("10歳", "じっさい", "じっ"),
+        ] {
+            let furigana = prepare_furigana(crate::map(word, reading, &kanji_to_readings));
+            println!("{furigana:?}");
+
+            let best = furigana.iter().max_by_key(|f| f.0).unwrap();
+            assert_eq!(
+                best.1,
+                vec![
+                    (&word[..word.len() - 3], Some(number_reading)),
+                    (&word[word.len() - 3..], Some("さい"))
+                ]
+            );
+        }
+
+        let furigana = prepare_furigana(crate::map("20歳", "はたち", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert_eq!(furigana, vec![(2, vec![("20歳", Some("はたち"))])]);
+    }
+
+    #[test]
+    fn handles_fuzzy_numbers() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("円".to_string(), vec!["えん".to_string()]);
+        let furigana = prepare_furigana(crate::map("3000円", "さんせんえん", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert!(furigana.is_empty());
+
+        let config = MapConfig {
+            fuzzy_numbers: true,
+            ..MapConfig::default()
+        };
+        let furigana = prepare_furigana(crate::map_with(
+            "3000円",
+            "さんせんえん",
+            &kanji_to_readings,
+            &config,
+        ));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(1, vec![("3000", Some("さんせん")), ("円", Some("えん"))])));
+        assert_eq!(furigana.len(), 1);
+    }
+
+    #[test]
+    fn handles_full_width_numbers() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("日".to_string(), vec!["か".to_string()]);
+        kanji_to_readings.insert("歳".to_string(), vec!["さい".to_string()]);
+        for (ascii, full_width, reading) in [
+            ("10日", "１０日", "とおか"),
+            ("10日", "1０日", "とおか"),
+            ("20歳", "２０歳", "はたち"),
+            ("3万5000", "３万５０００", "さんまんごせん"),
+        ] {
+            let ascii = prepare_furigana(crate::map(ascii, reading, &kanji_to_readings));
+            let full_width = prepare_furigana(crate::map(full_width, reading, &kanji_to_readings));
+            println!("{ascii:?} {full_width:?}");
+
+            assert!(!full_width.is_empty());
+            assert_eq!(
+                ascii.iter().map(|f| f.0).collect::<Vec<_>>(),
+                full_width.iter().map(|f| f.0).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn handles_latin_words() {
+        let kanji_to_readings = HashMap::new();
+        let mut config = MapConfig::default();
+        config
+            .latin_words
+            .insert("Suica".to_string(), vec!["スイカ".to_string()]);
+        config
+            .latin_words
+            .insert("LINE".to_string(), vec!["ライン".to_string()]);
+        let furigana = prepare_furigana(crate::map_with(
+            "Suicaカード",
+            "スイカカード",
+            &kanji_to_readings,
+            &config,
+        ));
+        println!("{furigana:?}");
+
+        assert_eq!(
+            furigana,
+            vec![(0, vec![("Suica", Some("スイカ")), ("カード", None)])]
+        );
+
+        let furigana = prepare_furigana(crate::map_with(
+            "LINEで",
+            "らいんで",
+            &kanji_to_readings,
+            &config,
+        ));
+        println!("{furigana:?}");
+
+        assert_eq!(
+            furigana,
+            vec![(0, vec![("LINE", Some("らいん")), ("で", None)])]
+        );
+    }
+
+    #[test]
+    fn handles_custom_letter_readings() {
+        let kanji_to_readings = HashMap::new();
+        let mut config = MapConfig::default();
+        config.letter_readings.insert('G', "ヂー");
+        config
+            .letter_readings
+            .set('α', vec!["アルファ".to_string()]);
+        let furigana = prepare_furigana(crate::map_with(
+            "Gパンα",
+            "ヂーパンアルファ",
+            &kanji_to_readings,
+            &config,
+        ));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(
+            0,
+            vec![("G", Some("ヂー")), ("パン", None), ("α", Some("アルファ"))]
+        )));
+    }
+
+    #[test]
+    fn disables_special_cases() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("大".to_string(), vec!["おお".to_string()]);
+        kanji_to_readings.insert("人".to_string(), vec!["ひと".to_string()]);
+        let config = MapConfig {
+            disable_special_cases: true,
+            ..MapConfig::default()
+        };
+        let furigana = prepare_furigana(crate::map_with(
+            "大人",
+            "おとな",
+            &kanji_to_readings,
+            &config,
+        ));
+        println!("{furigana:?}");
+
+        assert!(!furigana.contains(&(2, vec![("大人", Some("おとな"))])));
+        assert!(furigana.iter().all(|f| f.0 < 0));
+
+        let furigana = prepare_furigana(crate::map_with(
+            "10日",
+            "とおか",
+            &kanji_to_readings,
+            &config,
+        ));
+        println!("{furigana:?}");
+
+        assert!(furigana.is_empty());
+
+        let furigana = prepare_furigana(crate::map_with(
+            "Aランク",
+            "エーランク",
+            &kanji_to_readings,
+            &config,
+        ));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(0, vec![("A", Some("エー")), ("ランク", None)])));
+    }
+
     #[test]
     fn handles_々() {
         let furigana = prepare_furigana(crate::map_naive("日々", "ひび"));
@@ -579,6 +3283,93 @@ mod test {
         assert_eq!(furigana.len(), 1);
     }
 
+    #[test]
+    fn merges_yotsugana() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("邪".to_string(), vec!["じゃ".to_string()]);
+        kanji_to_readings.insert("魔".to_string(), vec!["ま".to_string()]);
+        let furigana = prepare_furigana(crate::map("邪魔", "ぢゃま", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(0, vec![("邪", Some("ぢゃ")), ("魔", Some("ま"))])));
+
+        let config = MapConfig {
+            merge_yotsugana: true,
+            ..MapConfig::default()
+        };
+        let furigana = prepare_furigana(crate::map_with(
+            "邪魔",
+            "ぢゃま",
+            &kanji_to_readings,
+            &config,
+        ));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(4, vec![("邪", Some("ぢゃ")), ("魔", Some("ま"))])));
+    }
+
+    #[test]
+    fn matches_long_vowel_marks() {
+        assert!(kana_equivalent("コーヒー", "こうひい"));
+        assert!(kana_equivalent("こうひい", "コーヒー"));
+        assert!(kana_equivalent("すーー", "すうう"));
+        assert!(kana_equivalent("ねえー", "ねーえ"));
+        assert!(kana_equivalent("ケーキ", "けえき"));
+        assert!(!kana_equivalent("ーき", "あき"));
+        assert!(!kana_equivalent("コーヒー", "こあひい"));
+        assert!(kana_equivalent("コンピューター", "こんぴゅうた"));
+        assert!(kana_equivalent("らめん", "ラーメン"));
+        assert!(!kana_equivalent("ーらめん", "らめん"));
+        assert!(!kana_equivalent("ラーメン", "らあめ"));
+
+        let furigana = prepare_furigana(crate::map_naive("コーヒー味", "こうひいあじ"));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(0, vec![("コーヒー", None), ("味", Some("あじ"))])));
+        assert_eq!(furigana.len(), 1);
+
+        let furigana = prepare_furigana(crate::map_naive("ラーメン屋", "らめんや"));
+        assert_eq!(
+            furigana,
+            vec![(0, vec![("ラーメン", None), ("屋", Some("や"))])]
+        );
+        let furigana = prepare_furigana(crate::map_naive("コンピュータ室", "こんぴゅーたーしつ"));
+        assert_eq!(
+            furigana,
+            vec![(0, vec![("コンピュータ", None), ("室", Some("しつ"))])]
+        );
+    }
+
+    #[test]
+    fn anchors_kana() {
+        let furigana = prepare_furigana(crate::map_naive("お茶わん", "おちゃわん"));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(0, vec![("お", None), ("茶", Some("ちゃ")), ("わん", None)])));
+        assert_eq!(furigana.len(), 1);
+
+        assert!(crate::map_naive("お物の", "おもも").is_empty());
+        assert!(crate::map_naive("お物の", "あものの").is_empty());
+    }
+
+    #[test]
+    fn anchors_okurigana() {
+        let furigana = prepare_furigana(crate::map_naive("引っ越し先", "ひっこしさき"));
+        println!("{furigana:?}");
+
+        assert!(furigana.contains(&(
+            0,
+            vec![
+                ("引", Some("ひ")),
+                ("っ", None),
+                ("越", Some("こ")),
+                ("し", None),
+                ("先", Some("さき"))
+            ]
+        )));
+        assert_eq!(furigana.len(), 1);
+    }
+
     #[test]
     fn handles_rendaku() {
         let mut kanji_to_readings = HashMap::new();
@@ -600,7 +3391,86 @@ mod test {
         println!("{furigana:?}");
 
         assert!(furigana.contains(&(3, vec![("格", Some("かっ")), ("好", Some("こう"))])));
-        assert_eq!(furigana.len(), 3);
+        // 好 can't start with the っ that belongs to 格
+        assert_eq!(furigana.len(), 2);
+    }
+
+    #[test]
+    fn gives_kanji_whole_mora() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("切".to_string(), vec!["せつ".to_string()]);
+        kanji_to_readings.insert("手".to_string(), vec!["て".to_string()]);
+        let mappings = crate::map("切手", "きょって", &kanji_to_readings);
+        let furigana = prepare_furigana(mappings);
+        assert!(!furigana.is_empty());
+        for (_, segments) in furigana {
+            for (_, reading) in segments {
+                let reading = reading.unwrap();
+                assert!(!reading.starts_with(['ょ', 'っ']), "{reading}");
+            }
+        }
+    }
+
+    #[test]
+    fn handles_sokuonbin_compounds() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("石".to_string(), vec!["セキ".to_string()]);
+        kanji_to_readings.insert("鹸".to_string(), vec!["ケン".to_string()]);
+        kanji_to_readings.insert("学".to_string(), vec!["ガク".to_string()]);
+        kanji_to_readings.insert("期".to_string(), vec!["キ".to_string()]);
+        kanji_to_readings.insert("日".to_string(), vec!["ニチ".to_string()]);
+        kanji_to_readings.insert("記".to_string(), vec!["キ".to_string()]);
+        for (word, reading, expected) in [
+            (
+                "石鹸",
+                "せっけん",
+                [("石", Some("せっ")), ("鹸", Some("けん"))],
+            ),
+            ("学期", "がっき", [("学", Some("がっ")), ("期", Some("き"))]),
+            ("日記", "にっき", [("日", Some("にっ")), ("記", Some("き"))]),
+        ] {
+            let mappings = crate::map(word, reading, &kanji_to_readings);
+            let best = mappings.best().unwrap();
+            assert_eq!(best.accuracy, 3, "{word}");
+            assert_eq!(
+                best.furigana
+                    .iter()
+                    .map(|f| (f.segment, f.furigana))
+                    .collect::<Vec<_>>(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn handles_vowel_lengthening() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("女".to_string(), vec!["ニョ".to_string()]);
+        kanji_to_readings.insert("房".to_string(), vec!["ボウ".to_string()]);
+        let config = MapConfig::builder().vowel_lengthening(true).build();
+        let best_accuracy = |config| {
+            crate::map_with("女房", "にょうぼう", &kanji_to_readings, config)
+                .best()
+                .map(|f| {
+                    (
+                        f.accuracy,
+                        f.furigana[0].furigana,
+                        f.furigana[0].kanji_accuracy,
+                    )
+                })
+        };
+        assert_eq!(
+            best_accuracy(&config),
+            Some((
+                3,
+                Some("にょう"),
+                Some(KanjiAccuracy::AccurateWithLengthening)
+            ))
+        );
+        assert_eq!(
+            best_accuracy(&MapConfig::default()).map(|(accuracy, _, _)| accuracy),
+            Some(0)
+        );
     }
 
     #[test]
@@ -612,7 +3482,39 @@ mod test {
         println!("{furigana:?}");
 
         assert!(furigana.contains(&(2, vec![("突", Some("とっ")), ("破", Some("ぱ"))])));
-        assert_eq!(furigana.len(), 2);
+        assert_eq!(furigana.len(), 1);
+
+        kanji_to_readings.insert("一".to_string(), vec!["いち".to_string()]);
+        kanji_to_readings.insert("杯".to_string(), vec!["はい".to_string()]);
+        kanji_to_readings.insert("出".to_string(), vec!["しゅつ".to_string()]);
+        kanji_to_readings.insert("張".to_string(), vec!["ちょう".to_string()]);
+        let accuracies = |word, reading| {
+            let mappings = crate::map(word, reading, &kanji_to_readings);
+            let best = mappings.best().unwrap();
+            best.furigana
+                .iter()
+                .map(|f| f.kanji_accuracy)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            accuracies("一杯", "いっぱい"),
+            [
+                Some(KanjiAccuracy::AccurateWithSokuonbin),
+                Some(KanjiAccuracy::AccurateWithSokuonbinAndRendaku)
+            ]
+        );
+        assert_eq!(
+            accuracies("出張", "しゅっちょう"),
+            [
+                Some(KanjiAccuracy::AccurateWithSokuonbin),
+                Some(KanjiAccuracy::Accurate)
+            ]
+        );
+        // rendaku doesn't happen after っ
+        assert_eq!(
+            accuracies("突破", "とっば")[1],
+            Some(KanjiAccuracy::VoicingMismatch)
+        );
     }
 
     #[test]
@@ -628,11 +3530,42 @@ mod test {
                 ("月", Some("げつ"))
             ]
         )));
-        assert_eq!(furigana.len(), 1);
+        // ヶ may also be part of the kanji
+        assert!(furigana.contains(&(0, vec![("一ヶ月", Some("いっかげつ"))])));
+        assert_eq!(furigana.len(), 2);
+    }
+
+    #[test]
+    fn shares_suffix_between_segmentations() {
+        for (word, reading, count) in [("一ヶ月だ", "いっかげつだ", 2), ("〆切だ", "しめきりだ", 1)]
+        {
+            let mappings = crate::map_naive(word, reading);
+            assert_eq!(mappings.len(), count, "{word}");
+            for mapping in &mappings {
+                assert_eq!(crate::check_invariants(mapping, word, reading), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn tries_marks_as_kanji() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("関".to_string(), vec!["せき".to_string()]);
+        kanji_to_readings.insert("原".to_string(), vec!["はら".to_string()]);
+        kanji_to_readings.insert("切".to_string(), vec!["きり".to_string()]);
+        kanji_to_readings.insert("〆".to_string(), vec!["しめ".to_string()]);
+
+        let mappings = crate::map("関ヶ原", "せきがはら", &kanji_to_readings);
+        let best = mappings.best().unwrap();
+        assert_eq!(best.furigana[1].segment(), "ヶ");
+        assert_eq!(best.furigana[1].reading(), "が");
+        let mappings = crate::map("〆切", "しめきり", &kanji_to_readings);
+        let best = mappings.best().unwrap();
+        assert_eq!(best.accuracy, 4);
+        assert_eq!(best.furigana[0].reading(), "しめ");
     }
 
     #[test]
-    #[ignore = "todo?"]
     fn handles_irregular() {
         let mut kanji_to_readings = HashMap::new();
         kanji_to_readings.insert(
@@ -652,7 +3585,27 @@ mod test {
         let furigana = prepare_furigana(crate::map("大人", "おとな", &kanji_to_readings));
         println!("{furigana:?}");
 
-        assert!(furigana.contains(&(2, vec![("突", Some("とっ")), ("破", Some("ぱ"))])));
-        assert_eq!(furigana.len(), 2);
+        let best = furigana.iter().max_by_key(|f| f.0).unwrap();
+        assert_eq!(best, &(2, vec![("大人", Some("おとな"))]));
+
+        let mappings = crate::map("大人", "おとな", &kanji_to_readings);
+        let best = mappings.best().unwrap();
+        assert_eq!(
+            best.furigana[0].kanji_accuracy,
+            Some(KanjiAccuracy::Irregular)
+        );
+    }
+
+    #[test]
+    fn handles_multi_kanji_entries() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("山".to_string(), vec!["やま".to_string()]);
+        kanji_to_readings.insert("羊".to_string(), vec!["ひつじ".to_string()]);
+        kanji_to_readings.insert("山羊".to_string(), vec!["やぎ".to_string()]);
+        let furigana = prepare_furigana(crate::map("子山羊", "こやぎ", &kanji_to_readings));
+        println!("{furigana:?}");
+
+        let best = furigana.iter().max_by_key(|f| f.0).unwrap();
+        assert_eq!(best.1, vec![("子", Some("こ")), ("山羊", Some("やぎ"))]);
     }
 }
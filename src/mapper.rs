@@ -0,0 +1,141 @@
+use crate::{config::MapConfig, furigana::Furigana, mappings::Mappings};
+use std::{
+    collections::HashMap,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// Maps words with a fixed configuration and a dictionary of kanji readings that can be replaced while in use.
+/// Mappings confirmed with `confirm` are preferred over the others the next time the same word is mapped.
+/// Can be shared between threads, for example in an `Arc`.
+#[derive(Debug)]
+pub struct Mapper {
+    kanji_to_readings: RwLock<Arc<HashMap<String, Vec<String>>>>,
+    config: MapConfig,
+    // the id of the confirmed mapping of each (word, reading) pair
+    preferences: RwLock<HashMap<(String, String), u64>>,
+}
+
+impl Mapper {
+    pub fn new(kanji_to_readings: HashMap<String, Vec<String>>, config: MapConfig) -> Self {
+        Self {
+            kanji_to_readings: RwLock::new(Arc::new(kanji_to_readings)),
+            config,
+            preferences: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Works like `map_with`, using the current dictionary.
+    /// If a mapping of the word has been confirmed, it's ranked first and returned by `Mappings::confirmed`,
+    /// keeping its own accuracy.
+    pub fn map<'a>(&self, word: &'a str, reading: &'a str) -> Mappings<'a> {
+        let kanji_to_readings = self.dict();
+        let mappings = crate::map_with(word, reading, &kanji_to_readings, &self.config);
+        let preferences = self
+            .preferences
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        if preferences.is_empty() {
+            return mappings;
+        }
+        let confirmed = match preferences.get(&(word.to_string(), reading.to_string())) {
+            Some(&confirmed) => confirmed,
+            None => return mappings,
+        };
+        mappings.with_confirmed(confirmed)
+    }
+
+    /// Records the mapping as the correct one for its word and reading,
+    /// so that it's ranked first by later calls to `map`, such as after a user has picked it from the alternatives.
+    pub fn confirm(&self, mapping: &Furigana) {
+        let word = mapping.furigana.iter().map(|s| s.segment).collect();
+        let reading = mapping.furigana.iter().map(|s| s.reading).collect();
+        self.preferences
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert((word, reading), mapping.id());
+    }
+
+    /// Forgets the confirmed mapping of the word and reading, if any.
+    pub fn forget(&self, word: &str, reading: &str) {
+        self.preferences
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&(word.to_string(), reading.to_string()));
+    }
+
+    /// Returns the current dictionary.
+    /// Calls to `swap_dict` don't affect dictionaries that have already been returned.
+    pub fn dict(&self) -> Arc<HashMap<String, Vec<String>>> {
+        // the lock only guards replacing the Arc, so a panic while holding it can't leave it in a bad state
+        let kanji_to_readings = self
+            .kanji_to_readings
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(&kanji_to_readings)
+    }
+
+    /// Replaces the dictionary, returning the previous one.
+    /// Mappings that are already in progress finish with the dictionary they started with.
+    pub fn swap_dict(
+        &self,
+        kanji_to_readings: HashMap<String, Vec<String>>,
+    ) -> Arc<HashMap<String, Vec<String>>> {
+        let mut current = self
+            .kanji_to_readings
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *current, Arc::new(kanji_to_readings))
+    }
+
+    pub fn config(&self) -> &MapConfig {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prefers_confirmed_mappings() {
+        let mapper = Mapper::new(HashMap::new(), MapConfig::default());
+        let mappings = mapper.map("物の怪", "もののけ");
+        let other = mappings
+            .iter()
+            .find(|f| Some(*f) != mappings.best())
+            .unwrap()
+            .clone();
+        mapper.confirm(&other);
+        let mappings = mapper.map("物の怪", "もののけ");
+        let best = mappings.best().unwrap();
+        assert_eq!(best.id(), other.id());
+        // the confirmed mapping keeps its accuracy
+        assert_eq!(best.accuracy, other.accuracy);
+        assert_eq!(mappings.confirmed(), Some(best));
+        mapper.forget("物の怪", "もののけ");
+        let best = mapper.map("物の怪", "もののけ").best().unwrap().id();
+        assert_ne!(best, other.id());
+    }
+
+    #[test]
+    fn swaps_dict() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        let mapper = Arc::new(Mapper::new(kanji_to_readings, MapConfig::default()));
+        let before = mapper.map("物の怪", "もののけ").best().unwrap().accuracy;
+
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        kanji_to_readings.insert("怪".to_string(), vec!["け".to_string()]);
+        let old = std::thread::spawn({
+            let mapper = Arc::clone(&mapper);
+            move || mapper.swap_dict(kanji_to_readings)
+        })
+        .join()
+        .unwrap();
+        assert!(!old.contains_key("怪"));
+
+        let after = mapper.map("物の怪", "もののけ").best().unwrap().accuracy;
+        assert!(after > before);
+    }
+}
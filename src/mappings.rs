@@ -0,0 +1,175 @@
+use crate::{furigana::Furigana, kana_equivalent};
+use std::{cmp::Reverse, ops::Deref};
+
+/// The possible mappings of furigana to a word.
+/// Derefs to a slice of the mappings in the order they were found.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Mappings<'a> {
+    mappings: Vec<Furigana<'a>>,
+    // the id of the mapping confirmed with `Mapper::confirm`, if it's one of the mappings
+    confirmed: Option<u64>,
+}
+
+impl<'a> Mappings<'a> {
+    pub(crate) fn new(mappings: Vec<Furigana<'a>>) -> Self {
+        Self {
+            mappings,
+            confirmed: None,
+        }
+    }
+
+    pub(crate) fn with_confirmed(mut self, id: u64) -> Self {
+        if self.mappings.iter().any(|f| f.id() == id) {
+            self.confirmed = Some(id);
+        }
+        self
+    }
+
+    /// Returns the confirmed mapping if there is one, and otherwise the most accurate mapping,
+    /// or the first one found if there are several.
+    pub fn best(&self) -> Option<&Furigana<'a>> {
+        self.iter_ranked().next()
+    }
+
+    /// Returns the mapping confirmed with `Mapper::confirm`, if the mappings were returned by `Mapper::map`.
+    pub fn confirmed(&self) -> Option<&Furigana<'a>> {
+        self.confirmed.and_then(|id| self.find_id(id))
+    }
+
+    /// Iterates over the mappings from the most accurate to the least accurate, starting with the confirmed mapping if there is one.
+    /// Mappings with the same accuracy are in the order they were found.
+    pub fn iter_ranked(&self) -> impl Iterator<Item = &Furigana<'a>> {
+        let mut ranked = self.mappings.iter().collect::<Vec<_>>();
+        ranked.sort_by_key(|f| Reverse(f.accuracy));
+        if let Some(confirmed) = self.confirmed() {
+            ranked.retain(|f| !std::ptr::eq(*f, confirmed));
+            ranked.insert(0, confirmed);
+        }
+        ranked.into_iter()
+    }
+
+    /// Returns the mapping with the identifier given by `Furigana::id`, if any.
+    pub fn find_id(&self, id: u64) -> Option<&Furigana<'a>> {
+        self.mappings.iter().find(|f| f.id() == id)
+    }
+
+    /// Keeps only the mappings that are as accurate as the most accurate one, along with the confirmed mapping.
+    pub fn filter_accurate(mut self) -> Self {
+        if let Some(best) = self.mappings.iter().map(|f| f.accuracy).max() {
+            let confirmed = self.confirmed;
+            self.mappings
+                .retain(|f| f.accuracy == best || confirmed == Some(f.id()));
+        }
+        self
+    }
+
+    /// Removes mappings whose furigana only differ in hiragana and katakana from a more accurate mapping.
+    pub fn dedup_kana_equivalent(self) -> Self {
+        let mut deduped: Vec<Furigana<'a>> = vec![];
+        for mapping in self.iter_ranked() {
+            if !deduped.iter().any(|d| same_in_kana(d, mapping)) {
+                deduped.push(mapping.clone());
+            }
+        }
+        // restore the original order
+        let mut mappings = self.mappings;
+        mappings.retain(|f| deduped.contains(f));
+        Self {
+            mappings,
+            confirmed: self.confirmed,
+        }
+    }
+
+    /// Returns the mappings in the order they were found.
+    pub fn into_vec(self) -> Vec<Furigana<'a>> {
+        self.mappings
+    }
+}
+
+// checks whether the mappings split the word the same way and have equivalent furigana
+pub(crate) fn same_in_kana(left: &Furigana, right: &Furigana) -> bool {
+    left.furigana.len() == right.furigana.len()
+        && left.furigana.iter().zip(&right.furigana).all(|(l, r)| {
+            l.segment == r.segment
+                && match (l.furigana, r.furigana) {
+                    (Some(l), Some(r)) => kana_equivalent(l, r),
+                    (l, r) => l == r,
+                }
+        })
+}
+
+impl<'a> Deref for Mappings<'a> {
+    type Target = [Furigana<'a>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.mappings
+    }
+}
+
+impl<'a> IntoIterator for Mappings<'a> {
+    type Item = Furigana<'a>;
+    type IntoIter = std::vec::IntoIter<Furigana<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.mappings.into_iter()
+    }
+}
+
+impl<'a, 'm> IntoIterator for &'m Mappings<'a> {
+    type Item = &'m Furigana<'a>;
+    type IntoIter = std::slice::Iter<'m, Furigana<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.mappings.iter()
+    }
+}
+
+impl<'a> From<Mappings<'a>> for Vec<Furigana<'a>> {
+    fn from(mappings: Mappings<'a>) -> Self {
+        mappings.mappings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FuriganaSegment;
+
+    fn mapping<'a>(accuracy: i32, furigana: &[(&'a str, Option<&'a str>)]) -> Furigana<'a> {
+        Furigana {
+            furigana: furigana
+                .iter()
+                .map(|&(segment, furigana)| FuriganaSegment {
+                    segment,
+                    furigana,
+                    reading: furigana.unwrap_or(segment),
+                    kanji_accuracy: None,
+                    source: None,
+                })
+                .collect(),
+            accuracy,
+        }
+    }
+
+    #[test]
+    fn ranks_mappings() {
+        let mappings = Mappings::new(vec![
+            mapping(0, &[("物", Some("も")), ("の", None), ("怪", Some("のけ"))]),
+            mapping(4, &[("物", Some("もの")), ("の", None), ("怪", Some("け"))]),
+            mapping(4, &[("物", Some("モノ")), ("の", None), ("怪", Some("ケ"))]),
+        ]);
+        assert_eq!(mappings.best(), Some(&mappings[1]));
+        assert_eq!(
+            mappings
+                .iter_ranked()
+                .map(|f| f.accuracy)
+                .collect::<Vec<_>>(),
+            vec![4, 4, 0]
+        );
+        assert_eq!(mappings.clone().filter_accurate().len(), 2);
+        assert_eq!(
+            mappings.clone().dedup_kana_equivalent().into_vec(),
+            vec![mappings[0].clone(), mappings[1].clone()]
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Contains a small hand-written table of kanji readings for ranking mappings when no kanji readings are available.
+
+use crate::{mappings::Mappings, utils};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// Common readings of common kanji, listed by hand.
+const READING_PRIORS: &[(&str, &[&str])] = &[
+    ("一", &["いち", "ひと", "いつ"]),
+    ("二", &["に", "ふた"]),
+    ("三", &["さん", "みっ", "み"]),
+    ("人", &["じん", "にん", "ひと"]),
+    ("日", &["にち", "ひ", "じつ", "か"]),
+    ("月", &["げつ", "がつ", "つき"]),
+    ("年", &["ねん", "とし"]),
+    ("大", &["だい", "たい", "おお"]),
+    ("小", &["しょう", "ちい", "こ"]),
+    ("中", &["ちゅう", "なか"]),
+    ("上", &["じょう", "うえ", "あ", "うわ"]),
+    ("下", &["か", "げ", "した", "さ", "くだ"]),
+    ("出", &["しゅつ", "で", "だ"]),
+    ("入", &["にゅう", "はい", "い"]),
+    ("本", &["ほん", "もと"]),
+    ("国", &["こく", "くに"]),
+    ("生", &["せい", "しょう", "い", "う", "なま"]),
+    ("学", &["がく", "まな"]),
+    ("会", &["かい", "あ"]),
+    ("社", &["しゃ", "やしろ"]),
+    ("時", &["じ", "とき"]),
+    ("間", &["かん", "けん", "あいだ", "ま"]),
+    ("分", &["ぶん", "ふん", "わ"]),
+    ("行", &["こう", "ぎょう", "い", "おこな"]),
+    ("見", &["けん", "み"]),
+    ("言", &["げん", "ごん", "い", "こと"]),
+    ("手", &["しゅ", "て"]),
+    ("自", &["じ", "し", "みずか"]),
+    ("事", &["じ", "こと"]),
+    ("物", &["ぶつ", "もつ", "もの"]),
+    ("者", &["しゃ", "もの"]),
+    ("子", &["し", "こ"]),
+    ("女", &["じょ", "にょ", "おんな", "め"]),
+    ("男", &["だん", "なん", "おとこ"]),
+    ("先", &["せん", "さき"]),
+    ("前", &["ぜん", "まえ"]),
+    ("後", &["ご", "こう", "あと", "うし"]),
+    ("外", &["がい", "げ", "そと", "ほか"]),
+    ("内", &["ない", "うち"]),
+    ("方", &["ほう", "かた"]),
+    ("新", &["しん", "あたら", "にい"]),
+    ("高", &["こう", "たか"]),
+    ("長", &["ちょう", "なが"]),
+    ("気", &["き", "け"]),
+    ("水", &["すい", "みず"]),
+    ("火", &["か", "ひ"]),
+    ("木", &["もく", "ぼく", "き"]),
+    ("金", &["きん", "こん", "かね"]),
+    ("土", &["ど", "と", "つち"]),
+    ("山", &["さん", "やま"]),
+    ("川", &["せん", "かわ"]),
+    ("田", &["でん", "た"]),
+    ("口", &["こう", "く", "くち"]),
+    ("目", &["もく", "め"]),
+    ("耳", &["じ", "みみ"]),
+    ("心", &["しん", "こころ"]),
+    ("名", &["めい", "みょう", "な"]),
+    ("文", &["ぶん", "もん", "ふみ"]),
+    ("語", &["ご", "かた"]),
+    ("話", &["わ", "はな", "はなし"]),
+    ("食", &["しょく", "た", "く"]),
+    ("書", &["しょ", "か"]),
+    ("読", &["どく", "とく", "よ"]),
+    ("車", &["しゃ", "くるま"]),
+    ("電", &["でん"]),
+    ("道", &["どう", "みち"]),
+    ("家", &["か", "け", "いえ", "や"]),
+    ("店", &["てん", "みせ"]),
+    ("校", &["こう"]),
+    ("東", &["とう", "ひがし"]),
+    ("西", &["せい", "さい", "にし"]),
+    ("南", &["なん", "みなみ"]),
+    ("北", &["ほく", "きた"]),
+    ("京", &["きょう", "けい"]),
+    ("都", &["と", "つ", "みやこ"]),
+    ("地", &["ち", "じ"]),
+    ("天", &["てん", "あま"]),
+    ("雨", &["う", "あめ", "あま"]),
+    ("花", &["か", "はな"]),
+    ("怪", &["かい", "け"]),
+    ("明", &["めい", "みょう", "あか", "あ"]),
+    ("今", &["こん", "きん", "いま"]),
+    ("来", &["らい", "く", "き"]),
+    ("思", &["し", "おも"]),
+    ("何", &["か", "なに", "なん"]),
+    ("私", &["し", "わたし", "わたくし"]),
+];
+
+type Priors = Arc<HashMap<String, Vec<String>>>;
+
+// READING_PRIORS as a dictionary, built on first use
+static PRIORS: Mutex<Option<Priors>> = Mutex::new(None);
+
+/// Works like `map`, using a small built-in table of common readings of common kanji
+/// instead of a dictionary, for when no kanji readings are available.
+/// Kanji outside of the table are scored by how plausible the length and boundaries of their readings are,
+/// so that mappings like 物 (もの) and 怪 (け) for 物の怪 rank above ones like 物 (も) and 怪 (のけ).
+pub fn map_naive_ranked<'a>(word: &'a str, reading: &'a str) -> Mappings<'a> {
+    let mut mappings = crate::map(word, reading, &priors()).into_vec();
+    for mapping in &mut mappings {
+        let last = mapping.furigana.len().saturating_sub(1);
+        let plausibility = mapping
+            .furigana
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| {
+                segment.kanji_accuracy.is_none() && segment.segment.chars().all(utils::is_kanji)
+            })
+            .map(|(idx, segment)| reading_plausibility(segment.reading, idx == last))
+            .sum::<i32>();
+        mapping.accuracy += plausibility;
+    }
+    Mappings::new(mappings)
+}
+
+fn priors() -> Priors {
+    let mut priors = PRIORS.lock().unwrap_or_else(PoisonError::into_inner);
+    let priors = priors.get_or_insert_with(|| {
+        let priors = READING_PRIORS
+            .iter()
+            .map(|(kanji, readings)| {
+                let readings = readings.iter().map(|r| r.to_string()).collect();
+                (kanji.to_string(), readings)
+            })
+            .collect();
+        Arc::new(priors)
+    });
+    Arc::clone(priors)
+}
+
+// scores a reading of a kanji without known readings by its length in morae and by how it starts and ends
+fn reading_plausibility(reading: &str, is_last: bool) -> i32 {
+    let mut chars = reading.chars().map(crate::to_hiragana);
+    let first = chars.next();
+    let last = reading.chars().last().map(crate::to_hiragana);
+    if first.map_or(true, |first| "ゃゅょぁぃぅぇぉっんー".contains(first)) {
+        // no reading starts like this
+        return -3;
+    }
+    if is_last && last == Some('っ') {
+        // sokuonbin needs something after the kanji
+        return -3;
+    }
+    let morae = reading
+        .chars()
+        .map(crate::to_hiragana)
+        .filter(|c| !"ゃゅょぁぃぅぇぉ".contains(*c))
+        .count();
+    match morae {
+        1 | 3 => 0,
+        2 => 1,
+        _ => -1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ranks_without_dictionary() {
+        let mappings = map_naive_ranked("物の怪", "もののけ");
+        let best = mappings.best().unwrap();
+        assert_eq!(best.furigana[0].furigana, Some("もの"));
+        assert_eq!(best.furigana[2].furigana, Some("け"));
+
+        // neither kanji is in the table
+        let mappings = map_naive_ranked("鬱陶しい", "うっとうしい");
+        let best = mappings.best().unwrap();
+        assert_eq!(best.furigana[0].furigana, Some("うっ"));
+        assert_eq!(best.furigana[1].furigana, Some("とう"));
+    }
+}
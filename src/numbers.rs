@@ -0,0 +1,566 @@
+//! Contains functionality for reading numbers written with digits or roman numerals.
+
+use crate::{kana_equivalent, utils, voicing_equivalent, MapConfig, NumberStyle};
+
+// converts full-width digits and roman numeral letters to ASCII, leaving other characters as they are
+fn normalize_char(c: char) -> char {
+    let (from, to) = match c {
+        '０'..='９' => ('０', '0'),
+        'Ｉ' | 'Ｖ' | 'Ｘ' => ('Ａ', 'A'),
+        _ => return c,
+    };
+    char::from_u32(c as u32 - from as u32 + to as u32).unwrap_or(c)
+}
+
+// all the reading paths go through the normalized form so that full-width numbers are read exactly like ASCII ones
+fn normalize(digits: &str) -> String {
+    digits.chars().map(normalize_char).collect()
+}
+
+/// Parses a sequence of ASCII and/or full-width digits optionally interleaved with magnitudes like 万, or a roman numeral.
+/// Returns None if the string contains anything else or if the number is too large.
+pub fn parse(digits: &str) -> Option<u64> {
+    let digits = &normalize(digits);
+    if let Some(number) = parse_roman(digits) {
+        return Some(number);
+    }
+    if digits.is_empty() {
+        return None;
+    }
+
+    let mut number = 0u64;
+    let mut group = None;
+    let mut previous_magnitude = u64::MAX;
+    for c in digits.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            let group = group.get_or_insert(0u64);
+            *group = group.checked_mul(10)?.checked_add(u64::from(digit))?;
+        } else {
+            let magnitude = match c {
+                '万' => 10_000,
+                '億' => 100_000_000,
+                '兆' => 1_000_000_000_000,
+                '京' => 10_000_000_000_000_000,
+                _ => return None,
+            };
+            // magnitudes need digits before them and have to be in descending order, as in 1億2000万
+            let group = group.take()?;
+            if magnitude >= previous_magnitude || group >= 10_000 {
+                return None;
+            }
+            previous_magnitude = magnitude;
+            number = number.checked_add(group.checked_mul(magnitude)?)?;
+        }
+    }
+    number.checked_add(group.unwrap_or(0))
+}
+
+// parses either a single character like Ⅲ or a sequence of the letters I, V and X like III
+fn parse_roman(numeral: &str) -> Option<u64> {
+    let mut chars = numeral.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        let c = c as u32;
+        if (0x2160..=0x216B).contains(&c) {
+            return Some(u64::from(c - 0x2160 + 1));
+        }
+        if (0x2170..=0x217B).contains(&c) {
+            return Some(u64::from(c - 0x2170 + 1));
+        }
+    }
+
+    let ascii = numeral
+        .chars()
+        .map(|c| match c {
+            'I' | 'V' | 'X' => Some(c),
+            _ => None,
+        })
+        .collect::<Option<String>>()?;
+    // only numerals written in the standard form are accepted, something like VX is more likely to be letters
+    (1..40).find(|number| to_roman(*number) == ascii)
+}
+
+// writes numbers below 40 as roman numerals
+fn to_roman(number: u64) -> String {
+    let ones = ["", "I", "II", "III", "IV", "V", "VI", "VII", "VIII", "IX"];
+    let tens = "X".repeat((number / 10) as usize);
+    format!("{tens}{}", ones[(number % 10) as usize])
+}
+
+/// Returns the roman numeral at the start of the word, if the word starts with a run of letters that is a roman numeral.
+pub(crate) fn roman_numeral_prefix(word: &str) -> Option<&str> {
+    let idx = word
+        .find(|c| !utils::is_alphabetic(c))
+        .unwrap_or(word.len());
+    let letters = &word[..idx];
+    parse_roman(&normalize(letters)).map(|_| letters)
+}
+
+/// Returns the possible readings of the number, such as さんびゃく for 300.
+pub fn readings_of(number: u64) -> Vec<String> {
+    if number == 0 {
+        return vec!["ぜろ".to_string(), "れい".to_string()];
+    }
+
+    const MAGNITUDES: [(u64, &str); 4] = [
+        (10_000_000_000_000_000, "けい"),
+        (1_000_000_000_000, "ちょう"),
+        (100_000_000, "おく"),
+        (10_000, "まん"),
+    ];
+    let mut readings = vec![String::new()];
+    let mut rest = number;
+    for (magnitude, magnitude_reading) in MAGNITUDES {
+        let group = rest / magnitude;
+        rest %= magnitude;
+        if group == 0 {
+            continue;
+        }
+        let group_readings = group_readings(group, magnitude_reading);
+        readings = combine(&readings, &group_readings);
+    }
+    if rest != 0 {
+        readings = combine(&readings, &group_readings(rest, ""));
+    }
+    readings
+}
+
+// readings of a number from 1 to 9999 followed by the given magnitude (まん, おく...)
+fn group_readings(group: u64, magnitude: &str) -> Vec<String> {
+    let thousands = group / 1000;
+    let hundreds = group / 100 % 10;
+    let tens = group / 10 % 10;
+    let ones = group % 10;
+
+    let mut readings = vec![String::new()];
+    let thousands_readings: &[&str] = match thousands {
+        0 => &[""],
+        // 1000 is just せん on its own but いっせん before a magnitude
+        1 if magnitude.is_empty() => &["せん"],
+        1 => &["いっせん", "せん"],
+        2 => &["にせん"],
+        3 => &["さんぜん"],
+        4 => &["よんせん"],
+        5 => &["ごせん"],
+        6 => &["ろくせん"],
+        7 => &["ななせん"],
+        8 => &["はっせん"],
+        _ => &["きゅうせん"],
+    };
+    readings = combine(&readings, thousands_readings);
+    let hundreds_readings: &[&str] = match hundreds {
+        0 => &[""],
+        1 => &["ひゃく"],
+        2 => &["にひゃく"],
+        3 => &["さんびゃく"],
+        4 => &["よんひゃく"],
+        5 => &["ごひゃく"],
+        6 => &["ろっぴゃく"],
+        7 => &["ななひゃく"],
+        8 => &["はっぴゃく"],
+        _ => &["きゅうひゃく"],
+    };
+    readings = combine(&readings, hundreds_readings);
+    let tens_readings: &[&str] = match tens {
+        0 => &[""],
+        1 => &["じゅう"],
+        2 => &["にじゅう"],
+        3 => &["さんじゅう"],
+        4 => &["よんじゅう"],
+        5 => &["ごじゅう"],
+        6 => &["ろくじゅう"],
+        7 => &["ななじゅう", "しちじゅう"],
+        8 => &["はちじゅう"],
+        _ => &["きゅうじゅう"],
+    };
+    readings = combine(&readings, tens_readings);
+    if ones == 0 && tens != 0 && matches!(magnitude, "ちょう" | "けい") {
+        // じゅう assimilates to the following magnitude, as in じゅっちょう
+        readings = readings
+            .into_iter()
+            .map(|reading| match reading.strip_suffix("じゅう") {
+                Some(stripped) => format!("{stripped}じゅっ"),
+                None => reading,
+            })
+            .collect();
+    }
+
+    // the ones digit may change to fit the magnitude that follows it
+    let ones_readings: &[&str] = match (ones, magnitude) {
+        (0, _) => &[""],
+        (1, "ちょう" | "けい") => &["いっ"],
+        (1, _) => &["いち"],
+        (2, _) => &["に"],
+        (3, _) => &["さん"],
+        (4, "") => &["よん", "し"],
+        (4, _) => &["よん"],
+        (5, _) => &["ご"],
+        (6, "けい") => &["ろっ"],
+        (6, _) => &["ろく"],
+        (7, "") => &["なな", "しち"],
+        (7, _) => &["なな"],
+        (8, "ちょう" | "けい") => &["はっ"],
+        (8, _) => &["はち"],
+        (9, "") => &["きゅう", "く"],
+        _ => &["きゅう"],
+    };
+    readings = combine(&readings, ones_readings);
+    combine(&readings, &[magnitude])
+}
+
+// returns every reading in left followed by every reading in right
+fn combine<S: AsRef<str>>(left: &[String], right: &[S]) -> Vec<String> {
+    left.iter()
+        .flat_map(|left| {
+            right
+                .iter()
+                .map(move |right| format!("{left}{}", right.as_ref()))
+        })
+        .collect()
+}
+
+// readings of a number that may be used when it is an exception to the usual rules
+fn special_readings(number: u64) -> &'static [&'static str] {
+    match number {
+        // native Japanese counting, as in 10日 (とおか)
+        10 => &["とお"],
+        _ => &[],
+    }
+}
+
+/// Returns the native Japanese readings of the numbers from 1 to 10, such as ふた for 2 as in ふたつ.
+/// Includes the forms with a small っ used before some counters, such as みっ for 3 as in みっつ.
+pub fn native_readings(number: u64) -> &'static [&'static str] {
+    match number {
+        1 => &["ひと"],
+        2 => &["ふた"],
+        3 => &["み", "みっ"],
+        4 => &["よ", "よっ"],
+        5 => &["いつ"],
+        6 => &["む", "むっ"],
+        7 => &["なな"],
+        8 => &["や", "やっ"],
+        9 => &["ここの"],
+        10 => &["とお"],
+        _ => &[],
+    }
+}
+
+// English readings of small numbers
+fn english_readings(number: u64) -> &'static [&'static str] {
+    match number {
+        1 => &["ワン"],
+        2 => &["ツー"],
+        3 => &["スリー"],
+        4 => &["フォー"],
+        5 => &["ファイブ", "ファイヴ"],
+        6 => &["シックス"],
+        7 => &["セブン", "セヴン"],
+        8 => &["エイト"],
+        9 => &["ナイン"],
+        10 => &["テン"],
+        11 => &["イレブン", "イレヴン"],
+        12 => &["トゥエルブ", "トゥエルヴ"],
+        _ => &[],
+    }
+}
+
+/// Returns the readings used for the digit when reading a number one digit at a time, such as in phone numbers.
+/// Returns an empty list if the given number is not a single digit.
+pub fn digit_readings(digit: u32) -> &'static [&'static str] {
+    match digit {
+        0 => &["ぜろ", "れい", "まる"],
+        1 => &["いち"],
+        2 => &["に"],
+        3 => &["さん"],
+        4 => &["よん"],
+        5 => &["ご"],
+        6 => &["ろく"],
+        7 => &["なな", "しち"],
+        8 => &["はち"],
+        9 => &["きゅう"],
+        _ => &[],
+    }
+}
+
+/// Checks whether the reading is a valid reading for the number as a whole.
+/// The number is written with digits or roman numerals like in `parse`.
+pub fn match_reading(digits: &str, reading: &str) -> bool {
+    match_prefixes(digits, reading, &MapConfig::default())
+        .iter()
+        .any(|prefix| prefix.len() == reading.len())
+}
+
+/// Returns the prefixes of the reading that are valid readings for the digits in the configured style.
+/// Irregular readings like とお for 10 are only included if special cases are enabled.
+pub(crate) fn match_prefixes<'a>(
+    digits: &str,
+    reading: &'a str,
+    config: &MapConfig,
+) -> Vec<&'a str> {
+    match_prefixes_by(digits, reading, kana_equivalent, config)
+}
+
+/// Works like `match_prefixes`, but also accepts readings that only differ in voicing, such as さんせん for 3000.
+pub(crate) fn match_prefixes_ignoring_voicing<'a>(
+    digits: &str,
+    reading: &'a str,
+    config: &MapConfig,
+) -> Vec<&'a str> {
+    match_prefixes_by(digits, reading, voicing_equivalent, config)
+}
+
+fn match_prefixes_by<'a>(
+    digits: &str,
+    reading: &'a str,
+    equivalent: fn(&str, &str) -> bool,
+    config: &MapConfig,
+) -> Vec<&'a str> {
+    let digits = &normalize(digits);
+    let mut prefixes = vec![];
+    let mut push_matching = |candidate: &str| {
+        if let Some((prefix, _)) = utils::split_like(reading, candidate) {
+            if equivalent(prefix, candidate) && !prefixes.contains(&prefix) {
+                prefixes.push(prefix);
+            }
+        }
+    };
+    if config.number_style == NumberStyle::Native {
+        if let Some(number) = parse(digits) {
+            for candidate in native_readings(number) {
+                push_matching(candidate);
+            }
+        }
+        return prefixes;
+    }
+    if let Some(number) = parse(digits) {
+        for candidate in readings_of(number) {
+            push_matching(&candidate);
+        }
+        if config.number_style == NumberStyle::Any && !config.disable_special_cases {
+            for candidate in special_readings(number) {
+                push_matching(candidate);
+            }
+        }
+        if parse_roman(digits).is_some() {
+            // roman numerals are often read in English, as in World War II
+            for candidate in english_readings(number) {
+                push_matching(candidate);
+            }
+        }
+    }
+    if digits.chars().count() > 1 {
+        for len in match_digits(digits, reading, equivalent) {
+            let prefix = &reading[..len];
+            if !prefixes.contains(&prefix) {
+                prefixes.push(prefix);
+            }
+        }
+    }
+    prefixes
+}
+
+const K_ROW: &str = "かきくけこ";
+const S_ROW: &str = "さしすせそ";
+const T_ROW: &str = "たちつてと";
+const P_ROW: &str = "ぱぴぷぺぽ";
+
+// the endings of number readings that turn into っ before the kana of the given consonant rows
+// the same kind of change inside numbers, like ろっぴゃく for 600, is part of the readings of the numbers themselves
+const ASSIMILATIONS: &[(&str, &str, &[&str])] = &[
+    ("いち", "いっ", &[K_ROW, S_ROW, T_ROW, P_ROW]),
+    ("ろく", "ろっ", &[K_ROW, P_ROW]),
+    ("はち", "はっ", &[K_ROW, S_ROW, T_ROW, P_ROW]),
+    ("じゅう", "じゅっ", &[K_ROW, S_ROW, T_ROW, P_ROW]),
+    ("じゅう", "じっ", &[K_ROW, S_ROW, T_ROW, P_ROW]),
+    ("ひゃく", "ひゃっ", &[K_ROW, P_ROW]),
+];
+
+/// Returns the prefixes of the reading that read the number with its last sound turned into っ,
+/// as in いっかい for 1回, for the readings where the rest of the reading starts with a consonant that causes it.
+/// Only used when a kanji like a counter follows the number.
+pub(crate) fn assimilated_prefixes<'a>(
+    digits: &str,
+    reading: &'a str,
+    config: &MapConfig,
+) -> Vec<&'a str> {
+    let Some(number) = parse(&normalize(digits)) else {
+        return vec![];
+    };
+    let mut prefixes = vec![];
+    for number_reading in readings_of(number) {
+        for (from, to, rows) in ASSIMILATIONS {
+            let Some(stripped) = number_reading.strip_suffix(from) else {
+                continue;
+            };
+            let candidate = format!("{stripped}{to}");
+            let Some((prefix, rest)) = utils::split_like(reading, &candidate) else {
+                continue;
+            };
+            let next = rest.chars().next().map(crate::to_hiragana);
+            let assimilates = next.map_or(false, |next| rows.iter().any(|row| row.contains(next)));
+            if assimilates
+                && crate::kana_matches(prefix, &candidate, config)
+                && !prefixes.contains(&prefix)
+            {
+                prefixes.push(prefix);
+            }
+        }
+    }
+    prefixes
+}
+
+// returns the lengths of the prefixes of the reading that read the digits one at a time
+fn match_digits(digits: &str, reading: &str, equivalent: fn(&str, &str) -> bool) -> Vec<usize> {
+    let Some(digit) = digits.chars().next() else {
+        return vec![0];
+    };
+    let Some(value) = digit.to_digit(10) else {
+        return vec![];
+    };
+    let mut lens = vec![];
+    for digit_reading in digit_readings(value) {
+        let Some((prefix, reading_rest)) = utils::split_like(reading, digit_reading) else {
+            continue;
+        };
+        if equivalent(prefix, digit_reading) {
+            let digits_rest = &digits[digit.len_utf8()..];
+            for len in match_digits(digits_rest, reading_rest, equivalent) {
+                lens.push(prefix.len() + len);
+            }
+        }
+    }
+    lens
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_mixed_width_digits() {
+        assert_eq!(parse("１2０"), Some(120));
+        assert_eq!(parse("12a"), None);
+        assert_eq!(parse("99999999999999999999999"), None);
+        assert_eq!(parse("3万5000"), Some(35_000));
+        assert_eq!(parse("1億2000万"), Some(120_000_000));
+        assert_eq!(parse("万"), None);
+        assert_eq!(parse("1万2億"), None);
+    }
+
+    #[test]
+    fn reads_numbers() {
+        assert_eq!(readings_of(300), vec!["さんびゃく"]);
+        assert_eq!(readings_of(1000), vec!["せん"]);
+        assert_eq!(readings_of(8600), vec!["はっせんろっぴゃく"]);
+        assert!(readings_of(14).contains(&"じゅうよん".to_string()));
+        assert!(readings_of(14).contains(&"じゅうし".to_string()));
+        assert!(readings_of(10_000_000).contains(&"いっせんまん".to_string()));
+        assert_eq!(readings_of(1_0000_0000_0000), vec!["いっちょう"]);
+        assert_eq!(readings_of(10_0000_0000_0000), vec!["じゅっちょう"]);
+        assert_eq!(readings_of(2_0003_0000), vec!["におくさんまん"]);
+    }
+
+    #[test]
+    fn matches_whole_readings() {
+        assert!(match_reading("300", "さんびゃく"));
+        assert!(match_reading("３００", "サンビャク"));
+        assert!(match_reading("300", "さんぜろぜろ"));
+        assert!(!match_reading("300", "さんびゃくえん"));
+        assert!(!match_reading("300", "さんひゃく"));
+    }
+
+    #[test]
+    fn assimilates_before_counters() {
+        let config = MapConfig::default();
+        assert_eq!(assimilated_prefixes("1", "いっかい", &config), vec!["いっ"]);
+        assert_eq!(assimilated_prefixes("1", "いっさい", &config), vec!["いっ"]);
+        assert_eq!(
+            assimilated_prefixes("11", "じゅういっぽん", &config),
+            vec!["じゅういっ"]
+        );
+        assert!(assimilated_prefixes("1", "いっまい", &config).is_empty());
+        assert!(assimilated_prefixes("2", "にかい", &config).is_empty());
+        assert_eq!(assimilated_prefixes("6", "ろっぽん", &config), vec!["ろっ"]);
+        assert!(assimilated_prefixes("6", "ろっさつ", &config).is_empty());
+        assert_eq!(assimilated_prefixes("8", "はっさい", &config), vec!["はっ"]);
+        assert_eq!(
+            assimilated_prefixes("10", "じゅっかい", &config),
+            vec!["じゅっ"]
+        );
+        assert_eq!(
+            assimilated_prefixes("10", "じっかい", &config),
+            vec!["じっ"]
+        );
+        assert_eq!(
+            assimilated_prefixes("100", "ひゃっぽん", &config),
+            vec!["ひゃっ"]
+        );
+    }
+
+    #[test]
+    fn parses_roman_numerals() {
+        assert_eq!(parse("Ⅲ"), Some(3));
+        assert_eq!(parse("ⅻ"), Some(12));
+        assert_eq!(parse("XIV"), Some(14));
+        assert_eq!(parse("ＩＩ"), Some(2));
+        assert_eq!(parse("IIII"), None);
+        assert_eq!(parse("VX"), None);
+        assert_eq!(roman_numeral_prefix("IIの"), Some("II"));
+        assert_eq!(roman_numeral_prefix("IVY"), None);
+    }
+
+    #[test]
+    fn reads_full_width_like_ascii() {
+        for (ascii, full_width) in [
+            ("10", "１０"),
+            ("10", "1０"),
+            ("3万5000", "３万５０００"),
+            ("II", "ＩＩ"),
+        ] {
+            for reading in ["とお", "じゅう", "いちぜろ", "さんまんごせん", "に", "ツー"]
+            {
+                assert_eq!(
+                    match_prefixes(ascii, reading, &MapConfig::default()),
+                    match_prefixes(full_width, reading, &MapConfig::default()),
+                    "{ascii} {full_width} {reading}"
+                );
+            }
+        }
+        assert_eq!(
+            match_prefixes("１0", "とお", &MapConfig::default()),
+            vec!["とお"]
+        );
+        assert_eq!(roman_numeral_prefix("ＩＩの"), Some("ＩＩ"));
+    }
+
+    #[test]
+    fn matches_number_styles() {
+        let native = MapConfig {
+            number_style: NumberStyle::Native,
+            ..MapConfig::default()
+        };
+        let sino_japanese = MapConfig {
+            number_style: NumberStyle::SinoJapanese,
+            ..MapConfig::default()
+        };
+        assert_eq!(match_prefixes("3", "みっつ", &native), vec!["み", "みっ"]);
+        assert!(match_prefixes("3", "さん", &native).is_empty());
+        assert!(match_prefixes("10", "とおか", &sino_japanese).is_empty());
+        assert_eq!(
+            match_prefixes("10", "とおか", &MapConfig::default()),
+            vec!["とお"]
+        );
+    }
+
+    #[test]
+    fn matches_digit_by_digit() {
+        assert_eq!(
+            match_prefixes("１2０", "ひゃくにじゅうえん", &MapConfig::default()),
+            vec!["ひゃくにじゅう"]
+        );
+        assert_eq!(
+            match_prefixes("110", "いちいちぜろ", &MapConfig::default()),
+            vec!["いちいちぜろ"]
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Contains a strict parser for files of reading overrides maintained by users.
+
+use crate::{
+    rules::{ContextRule, Neighbor},
+    utils,
+};
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+/// Readings of words and kanji parsed from an override file, meant to be used as the first `ReadingSource`
+/// so that they take priority over the other sources.
+///
+/// Each line of the file has a word, whitespace and its readings separated by commas, like `今日 きょう,こんにち`.
+/// The readings may be followed by conditions on the characters around the word, separated by whitespace:
+/// `previous:` or `next:` followed by `kanji`, `not-kanji`, `kana` or `edge`, like `生 なま next:not-kanji`.
+/// Rules with conditions are returned by `Overrides::context_rules` instead of `Overrides::readings`.
+/// Empty lines and lines starting with `#` are skipped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overrides {
+    readings: HashMap<String, Vec<String>>,
+    // every rule along with the line it's on
+    rules: Vec<(ContextRule, usize)>,
+}
+
+/// An error in an override file, pointing at the line and column where it was found, both starting from 1.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OverrideError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: OverrideErrorKind,
+}
+
+/// The kind of an `OverrideError`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OverrideErrorKind {
+    /// The line has a word but no readings.
+    MissingReading,
+    /// The word has no kanji, so there's nothing to override.
+    NoKanji,
+    /// One of the readings is empty, as with two commas in a row.
+    EmptyReading,
+    /// The reading contains a char that isn't kana.
+    UnknownKana(char),
+    /// The reading is listed more than once for the word.
+    DuplicateReading(String),
+    /// The word already has readings with the same conditions on an earlier line.
+    DuplicateWord { first_line: usize },
+    /// The condition isn't one of the known ones.
+    UnknownCondition(String),
+    /// A rule on an earlier line can apply in the same place and gives one of the same readings.
+    OverlappingRule { line: usize },
+}
+
+impl Overrides {
+    /// Parses an override file, collecting every error in it rather than stopping at the first one.
+    pub fn parse(text: &str) -> Result<Self, Vec<OverrideError>> {
+        let mut overrides = Self::default();
+        let mut errors = vec![];
+        for (idx, line) in text.lines().enumerate() {
+            if let Err(error) = overrides.parse_line(idx + 1, line) {
+                errors.push(error);
+            }
+        }
+        if errors.is_empty() {
+            Ok(overrides)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns the readings of the rules without conditions, for creating a `ReadingSource`.
+    pub fn readings(&self) -> &HashMap<String, Vec<String>> {
+        &self.readings
+    }
+
+    /// Returns the rules with conditions, for `MapConfigBuilder::context_rules`.
+    pub fn context_rules(&self) -> Vec<ContextRule> {
+        self.rules
+            .iter()
+            .map(|(rule, _)| rule)
+            .filter(|rule| rule.previous != Neighbor::Any || rule.next != Neighbor::Any)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the first line the word's readings were defined on.
+    pub fn line(&self, word: &str) -> Option<usize> {
+        self.rules
+            .iter()
+            .find(|(rule, _)| rule.word == word)
+            .map(|&(_, line)| line)
+    }
+
+    fn parse_line(&mut self, line_number: usize, line: &str) -> Result<(), OverrideError> {
+        let error = |byte_idx: usize, kind| OverrideError {
+            line: line_number,
+            column: line[..byte_idx].chars().count() + 1,
+            kind,
+        };
+
+        let content = line.trim_end();
+        let word_start = content.len() - content.trim_start().len();
+        if word_start == content.len() || content[word_start..].starts_with('#') {
+            return Ok(());
+        }
+        let word_end = content[word_start..]
+            .find(char::is_whitespace)
+            .map_or(content.len(), |idx| word_start + idx);
+        let word = &content[word_start..word_end];
+        if !word.chars().any(utils::is_kanji) {
+            return Err(error(word_start, OverrideErrorKind::NoKanji));
+        }
+        let readings_start = content.len() - content[word_end..].trim_start().len();
+        if readings_start == content.len() {
+            return Err(error(content.len(), OverrideErrorKind::MissingReading));
+        }
+
+        let readings_end = content[readings_start..]
+            .find(char::is_whitespace)
+            .map_or(content.len(), |idx| readings_start + idx);
+        let mut readings: Vec<String> = vec![];
+        let mut reading_start = readings_start;
+        for reading in content[readings_start..readings_end].split(',') {
+            if reading.is_empty() {
+                return Err(error(reading_start, OverrideErrorKind::EmptyReading));
+            }
+            if let Some((idx, c)) = reading.char_indices().find(|(_, c)| !utils::is_kana(*c)) {
+                return Err(error(
+                    reading_start + idx,
+                    OverrideErrorKind::UnknownKana(c),
+                ));
+            }
+            if readings.iter().any(|existing| existing == reading) {
+                return Err(error(
+                    reading_start,
+                    OverrideErrorKind::DuplicateReading(reading.to_string()),
+                ));
+            }
+            readings.push(reading.to_string());
+            reading_start += reading.len() + ','.len_utf8();
+        }
+
+        let mut rule = ContextRule::new(word, readings);
+        let mut condition_start = readings_end;
+        for condition in content[readings_end..].split_whitespace() {
+            condition_start += content[condition_start..].find(condition).unwrap_or(0);
+            let unknown = || {
+                error(
+                    condition_start,
+                    OverrideErrorKind::UnknownCondition(condition.to_string()),
+                )
+            };
+            let (side, class) = condition.split_once(':').ok_or_else(unknown)?;
+            let neighbor = match class {
+                "kanji" => Neighbor::Kanji,
+                "not-kanji" => Neighbor::NotKanji,
+                "kana" => Neighbor::Kana,
+                "edge" => Neighbor::Edge,
+                _ => return Err(unknown()),
+            };
+            match side {
+                "previous" => rule.previous = neighbor,
+                "next" => rule.next = neighbor,
+                _ => return Err(unknown()),
+            }
+            condition_start += condition.len();
+        }
+
+        for (existing, line) in &self.rules {
+            if existing.word != rule.word {
+                continue;
+            }
+            if existing.previous == rule.previous && existing.next == rule.next {
+                return Err(error(
+                    word_start,
+                    OverrideErrorKind::DuplicateWord { first_line: *line },
+                ));
+            }
+            let shares_reading = existing
+                .readings
+                .iter()
+                .any(|reading| rule.readings.contains(reading));
+            if shares_reading && existing.overlaps(&rule) {
+                return Err(error(
+                    word_start,
+                    OverrideErrorKind::OverlappingRule { line: *line },
+                ));
+            }
+        }
+        if rule.previous == Neighbor::Any && rule.next == Neighbor::Any {
+            self.readings
+                .insert(rule.word.clone(), rule.readings.clone());
+        }
+        self.rules.push((rule, line_number));
+        Ok(())
+    }
+}
+
+impl Display for OverrideError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl Error for OverrideError {}
+
+impl Display for OverrideErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingReading => write!(f, "the word has no readings"),
+            Self::NoKanji => write!(f, "the word has no kanji"),
+            Self::EmptyReading => write!(f, "empty reading"),
+            Self::UnknownKana(c) => write!(f, "{c} is not kana"),
+            Self::DuplicateReading(reading) => write!(f, "{reading} is listed more than once"),
+            Self::DuplicateWord { first_line } => {
+                write!(f, "the word was already defined on line {first_line}")
+            }
+            Self::UnknownCondition(condition) => write!(f, "unknown condition {condition}"),
+            Self::OverlappingRule { line } => {
+                write!(f, "the rule overlaps with the one on line {line}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{MapConfig, ReadingSource};
+
+    #[test]
+    fn parses_overrides() {
+        let overrides = Overrides::parse("# overrides\n\n今日 きょう,こんにち\n怪\tけ\n").unwrap();
+        assert_eq!(overrides.readings()["今日"], ["きょう", "こんにち"]);
+        assert_eq!(overrides.line("怪"), Some(4));
+
+        let sources = [ReadingSource::new("overrides", overrides.readings())];
+        let mappings =
+            crate::map_with_sources("物の怪", "もののけ", &sources, &MapConfig::default());
+        let best = mappings.best().unwrap();
+        assert_eq!(best.furigana[2].source(), Some(0));
+    }
+
+    #[test]
+    fn parses_conditions() {
+        let overrides = Overrides::parse(
+            "今日 きょう previous:edge next:edge\n生 なま next:not-kanji\n生 せい,しょう\n",
+        )
+        .unwrap();
+        assert_eq!(overrides.readings()["生"], ["せい", "しょう"]);
+        assert!(!overrides.readings().contains_key("今日"));
+        assert_eq!(
+            overrides.context_rules(),
+            vec![
+                ContextRule::new("今日", vec!["きょう".to_string()])
+                    .previous(Neighbor::Edge)
+                    .next(Neighbor::Edge),
+                ContextRule::new("生", vec!["なま".to_string()]).next(Neighbor::NotKanji),
+            ]
+        );
+
+        let errors = Overrides::parse(
+            "生 なま next:not-kanji\n生 なま,き next:kana\n生 き next:kanji\n生 なま  after:kana\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                OverrideError {
+                    line: 2,
+                    column: 1,
+                    kind: OverrideErrorKind::OverlappingRule { line: 1 },
+                },
+                OverrideError {
+                    line: 4,
+                    column: 7,
+                    kind: OverrideErrorKind::UnknownCondition("after:kana".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_positions() {
+        let errors = Overrides::parse(
+            "今日 きょう\nけ け\n 怪 ke\n物 もの,,\n今日 こんにち\n日 ひ,ひ\n木\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                OverrideError {
+                    line: 2,
+                    column: 1,
+                    kind: OverrideErrorKind::NoKanji,
+                },
+                OverrideError {
+                    line: 3,
+                    column: 4,
+                    kind: OverrideErrorKind::UnknownKana('k'),
+                },
+                OverrideError {
+                    line: 4,
+                    column: 6,
+                    kind: OverrideErrorKind::EmptyReading,
+                },
+                OverrideError {
+                    line: 5,
+                    column: 1,
+                    kind: OverrideErrorKind::DuplicateWord { first_line: 1 },
+                },
+                OverrideError {
+                    line: 6,
+                    column: 5,
+                    kind: OverrideErrorKind::DuplicateReading("ひ".to_string()),
+                },
+                OverrideError {
+                    line: 7,
+                    column: 2,
+                    kind: OverrideErrorKind::MissingReading,
+                },
+            ]
+        );
+        assert_eq!(errors[1].to_string(), "3:4: k is not kana");
+    }
+}
@@ -0,0 +1,109 @@
+//! Contains versions of the mapping results that own their data, for storing them independently of the word and reading.
+
+use crate::furigana::{Furigana, FuriganaSegment, KanjiAccuracy};
+
+/// A `Furigana` that owns its data.
+/// With the `rkyv` feature enabled, it can be serialized with rkyv and accessed without deserializing.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct OwnedFurigana {
+    pub furigana: Vec<OwnedFuriganaSegment>,
+    pub accuracy: i32,
+}
+
+/// A `FuriganaSegment` that owns its data.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct OwnedFuriganaSegment {
+    pub segment: String,
+    pub furigana: Option<String>,
+    pub reading: String,
+    pub kanji_accuracy: Option<KanjiAccuracy>,
+    pub source: Option<u32>,
+}
+
+impl OwnedFurigana {
+    /// Borrows the data as a `Furigana`.
+    pub fn as_furigana(&self) -> Furigana<'_> {
+        Furigana::new(
+            self.furigana
+                .iter()
+                .map(OwnedFuriganaSegment::as_segment)
+                .collect(),
+            self.accuracy,
+        )
+    }
+}
+
+impl OwnedFuriganaSegment {
+    /// Borrows the data as a `FuriganaSegment`.
+    pub fn as_segment(&self) -> FuriganaSegment<'_> {
+        FuriganaSegment::new(
+            &self.segment,
+            self.furigana.as_deref(),
+            &self.reading,
+            self.kanji_accuracy,
+        )
+        .with_source(self.source.map(|source| source as usize))
+    }
+}
+
+impl From<&Furigana<'_>> for OwnedFurigana {
+    fn from(furigana: &Furigana<'_>) -> Self {
+        Self {
+            furigana: furigana
+                .furigana
+                .iter()
+                .map(OwnedFuriganaSegment::from)
+                .collect(),
+            accuracy: furigana.accuracy,
+        }
+    }
+}
+
+impl From<&FuriganaSegment<'_>> for OwnedFuriganaSegment {
+    fn from(segment: &FuriganaSegment<'_>) -> Self {
+        Self {
+            segment: segment.segment.to_string(),
+            furigana: segment.furigana.map(str::to_string),
+            reading: segment.reading.to_string(),
+            kanji_accuracy: segment.kanji_accuracy,
+            // there are never anywhere near u32::MAX sources
+            source: segment.source.map(|source| source as u32),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn converts_to_owned_and_back() {
+        let mappings = crate::map_naive("物の怪", "もののけ");
+        let owned = OwnedFurigana::from(&mappings[0]);
+        assert_eq!(owned.as_furigana(), mappings[0]);
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn roundtrips_with_rkyv() {
+        let mappings = crate::map_naive("物の怪", "もののけ");
+        let owned = mappings.iter().map(OwnedFurigana::from).collect::<Vec<_>>();
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&owned).unwrap();
+        let archived =
+            rkyv::access::<rkyv::Archived<Vec<OwnedFurigana>>, rkyv::rancor::Error>(&bytes)
+                .unwrap();
+        assert_eq!(archived.len(), 2);
+        assert_eq!(archived[1].furigana[0].furigana.as_deref(), Some("もの"));
+        let deserialized =
+            rkyv::deserialize::<Vec<OwnedFurigana>, rkyv::rancor::Error>(archived).unwrap();
+        assert_eq!(deserialized, owned);
+    }
+}
@@ -0,0 +1,101 @@
+//! Contains a combinator for trying several configurations in order.
+
+use crate::{
+    config::{MapConfig, WhitespacePolicy},
+    mappings::Mappings,
+};
+use std::collections::HashMap;
+
+/// A sequence of configurations tried in order until one of them maps the word,
+/// for getting as many words mapped as possible while knowing how much each mapping had to bend the rules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapPipeline {
+    stages: Vec<MapConfig>,
+}
+
+/// The mappings produced by a stage of a `MapPipeline`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StagedMappings<'a> {
+    /// The index of the stage that produced the mappings.
+    pub stage: usize,
+    pub mappings: Mappings<'a>,
+}
+
+impl MapPipeline {
+    /// Creates a pipeline without any stages.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a pipeline that goes from strict to fuzzy: `MapConfig::strict`, `MapConfig::learner`, `MapConfig::lenient`,
+    /// and finally the lenient options with every option that accepts more readings enabled.
+    pub fn strict_to_fuzzy() -> Self {
+        let fuzzy = MapConfig::builder()
+            .fuzzy_numbers(true)
+            .merge_yotsugana(true)
+            .vowel_lengthening(true)
+            .omitted_okurigana(true)
+            .whitespace_policy(WhitespacePolicy::Trim)
+            .build();
+        Self::new()
+            .stage(MapConfig::strict())
+            .stage(MapConfig::learner())
+            .stage(MapConfig::lenient())
+            .stage(fuzzy)
+    }
+
+    /// Adds a stage to the end of the pipeline.
+    pub fn stage(mut self, config: MapConfig) -> Self {
+        self.stages.push(config);
+        self
+    }
+
+    pub fn stages(&self) -> &[MapConfig] {
+        &self.stages
+    }
+
+    /// Maps the reading to the word with each stage in order, returning the first mappings that aren't empty,
+    /// or None if no stage could map the word.
+    pub fn map<'a>(
+        &self,
+        word: &'a str,
+        reading: &'a str,
+        kanji_to_readings: &HashMap<String, Vec<String>>,
+    ) -> Option<StagedMappings<'a>> {
+        self.stages.iter().enumerate().find_map(|(stage, config)| {
+            let mappings = crate::map_with(word, reading, kanji_to_readings, config);
+            if mappings.is_empty() {
+                None
+            } else {
+                Some(StagedMappings { stage, mappings })
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_later_stages() {
+        let kanji_to_readings = HashMap::new();
+        let pipeline = MapPipeline::strict_to_fuzzy();
+        let staged = pipeline
+            .map("物の怪", "もののけ", &kanji_to_readings)
+            .unwrap();
+        assert_eq!(staged.stage, 0);
+        let staged = pipeline.map("10日", "とおか", &kanji_to_readings).unwrap();
+        assert_eq!(staged.stage, 1);
+        let staged = pipeline
+            .map("3000円", "さんせんえん", &kanji_to_readings)
+            .unwrap();
+        assert_eq!(staged.stage, 2);
+        let staged = pipeline.map("食べる", "たべ", &kanji_to_readings).unwrap();
+        assert_eq!(staged.stage, 3);
+        assert!(pipeline.map("の物", "がもの", &kanji_to_readings).is_none());
+        assert!(MapPipeline::new()
+            .map("物", "もの", &kanji_to_readings)
+            .is_none());
+    }
+}
@@ -0,0 +1,174 @@
+//! Contains options for rendering mappings as HTML.
+
+use crate::{
+    dict::KanjiDict,
+    frequency::FrequencyList,
+    furigana::{AccuracyWeights, Furigana, FuriganaSegment},
+    utils,
+};
+use std::collections::HashSet;
+
+/// Options for leaving out some of the furigana when rendering a mapping with `Furigana::render`.
+/// The segments whose furigana is left out are rendered like kana, with an empty `rt` tag.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    threshold: Option<i32>,
+    weights: AccuracyWeights,
+    known: HashSet<char>,
+    frequent: HashSet<String>,
+}
+
+impl RenderOptions {
+    /// Creates options that render all furigana, like the `Display` implementation of `Furigana`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leaves out the furigana of segments whose accuracy weighs less than the threshold,
+    /// so that likely wrong readings aren't published.
+    /// With the default weights, a threshold of 0 drops inaccurate readings and voicing mismatches,
+    /// while a threshold of 1 also drops readings that aren't backed by any reading information.
+    pub fn threshold(mut self, threshold: i32) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Sets the weights used with `threshold`.
+    pub fn weights(mut self, weights: AccuracyWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Leaves out the furigana of kanji whose level in the dictionary is at or below the given level,
+    /// as done in graded readers. Kanji without a level keep their furigana.
+    pub fn above_level(mut self, dict: &KanjiDict, level: u8) -> Self {
+        let known = dict
+            .levels()
+            .filter(|&(_, kanji_level)| kanji_level <= level)
+            .map(|(kanji, _)| kanji);
+        self.known.extend(known);
+        self
+    }
+
+    /// Leaves out the furigana of the given kanji, such as the ones a learner already knows.
+    /// Segments that contain other kanji too keep their furigana, as it can't be split between the kanji.
+    pub fn skip_known(mut self, known: &HashSet<char>) -> Self {
+        self.known.extend(known);
+        self
+    }
+
+    /// Leaves out all furigana of words ranked more common than the given rank in the list,
+    /// for annotating only the less common words of a text. Words not in the list keep their furigana.
+    pub fn skip_frequent(mut self, list: &FrequencyList, rank: usize) -> Self {
+        self.frequent
+            .extend(list.words_above(rank).map(str::to_string));
+        self
+    }
+
+    // checks whether all furigana of the word is left out
+    pub(crate) fn skips_word(&self, mapping: &Furigana) -> bool {
+        if self.frequent.is_empty() {
+            return false;
+        }
+        let word = mapping
+            .furigana
+            .iter()
+            .map(|segment| segment.segment)
+            .collect::<String>();
+        self.frequent.contains(&word)
+    }
+
+    // the furigana to render for the segment
+    pub(crate) fn furigana<'a>(&self, segment: &FuriganaSegment<'a>) -> Option<&'a str> {
+        let weight = self.weights.weight(segment.kanji_accuracy);
+        if self.threshold.map_or(false, |threshold| weight < threshold) {
+            return None;
+        }
+        // a segment can only be read without furigana if every kanji in it is known
+        let mut kanji = segment
+            .segment
+            .chars()
+            .filter(|c| utils::is_kanji(*c))
+            .peekable();
+        if kanji.peek().is_some() && kanji.all(|c| self.known.contains(&c)) {
+            return None;
+        }
+        segment.furigana
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn drops_uncertain_furigana() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        kanji_to_readings.insert("怪".to_string(), vec!["かい".to_string()]);
+        let mappings = crate::map("物の怪", "もののけ", &kanji_to_readings);
+        let best = mappings.best().unwrap();
+        assert_eq!(best.render(&RenderOptions::new()), best.to_string());
+        assert_eq!(
+            best.render(&RenderOptions::new().threshold(0)),
+            "<ruby>物<rt>もの</rt>の<rt></rt>怪<rt></rt></ruby>"
+        );
+        let lenient = AccuracyWeights {
+            inaccurate: 0,
+            ..AccuracyWeights::default()
+        };
+        assert_eq!(
+            best.render(&RenderOptions::new().threshold(0).weights(lenient)),
+            best.to_string()
+        );
+    }
+
+    #[test]
+    fn drops_furigana_of_easy_kanji() {
+        let mut dict = KanjiDict::new();
+        dict.insert("大", "だい");
+        dict.insert("学", "がく");
+        dict.insert("物", "もの");
+        dict.set_level('大', 1);
+        dict.set_level('学', 1);
+        dict.set_level('怪', 8);
+        let options = RenderOptions::new().above_level(&dict, 6);
+        let mappings = crate::map("大学", "だいがく", &dict);
+        assert_eq!(
+            mappings.best().unwrap().render(&options),
+            "<ruby>大<rt></rt>学<rt></rt></ruby>"
+        );
+        let mappings = crate::map("物の怪", "もののけ", &dict);
+        assert_eq!(
+            mappings.best().unwrap().render(&options),
+            "<ruby>物<rt>もの</rt>の<rt></rt>怪<rt>け</rt></ruby>"
+        );
+        // the furigana of 大人 can't be split, so it's kept as 人 is unknown
+        let mappings = crate::map("大人", "おとな", &dict);
+        assert_eq!(
+            mappings.best().unwrap().render(&options),
+            mappings.best().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn skips_frequent_words() {
+        let list = FrequencyList::parse("日本\n物の怪\n");
+        let options = RenderOptions::new().skip_frequent(&list, 1);
+        let mappings = crate::map_naive("日本", "にほん");
+        assert_eq!(mappings[0].render(&options), "<ruby>日本<rt></rt></ruby>");
+        let mappings = crate::map_naive("物の怪", "もののけ");
+        assert_eq!(mappings[1].render(&options), mappings[1].to_string());
+    }
+
+    #[test]
+    fn skips_known_kanji() {
+        let mappings = crate::map_naive("物の怪", "もののけ");
+        let known = ['物'].into_iter().collect::<HashSet<_>>();
+        assert_eq!(
+            mappings[1].render(&RenderOptions::new().skip_known(&known)),
+            "<ruby>物<rt></rt>の<rt></rt>怪<rt>け</rt></ruby>"
+        );
+    }
+}
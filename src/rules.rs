@@ -0,0 +1,127 @@
+//! Contains readings that only apply in certain contexts, like 生 (なま) before kana.
+
+use crate::utils;
+
+/// What the character next to a word has to be for a `ContextRule` to apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Neighbor {
+    /// Anything, including the edge of the word being mapped.
+    #[default]
+    Any,
+    /// A kanji or 々.
+    Kanji,
+    /// Anything but a kanji or 々, including the edge of the word being mapped.
+    NotKanji,
+    /// A hiragana or katakana character.
+    Kana,
+    /// The edge of the word being mapped.
+    Edge,
+}
+
+impl Neighbor {
+    /// Checks whether the character next to the word, or None at the edge of the word, satisfies the condition.
+    pub fn matches(self, c: Option<char>) -> bool {
+        let is_kanji = c.map_or(false, |c| utils::is_kanji(c) || c == '々');
+        match self {
+            Self::Any => true,
+            Self::Kanji => is_kanji,
+            Self::NotKanji => !is_kanji,
+            Self::Kana => c.map_or(false, utils::is_kana),
+            Self::Edge => c.is_none(),
+        }
+    }
+
+    /// Checks whether some character satisfies both conditions.
+    pub fn overlaps(self, other: Self) -> bool {
+        match (self, other) {
+            (Self::Any, _) | (_, Self::Any) => true,
+            (Self::Kanji, other) | (other, Self::Kanji) => other == Self::Kanji,
+            (Self::Kana, Self::Edge) | (Self::Edge, Self::Kana) => false,
+            _ => true,
+        }
+    }
+}
+
+/// Readings of a word or kanji that are only accurate next to certain characters,
+/// such as なま for 生 when it's followed by kana like in 生クリーム, or きょう for 今日 as a word of its own.
+/// Set with `MapConfigBuilder::context_rules` and used when grading the readings with kanji reading data.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ContextRule {
+    pub word: String,
+    pub readings: Vec<String>,
+    /// The condition for the character before the word.
+    pub previous: Neighbor,
+    /// The condition for the character after the word.
+    pub next: Neighbor,
+}
+
+impl ContextRule {
+    /// Creates a rule that applies anywhere until conditions are set with `previous` and `next`.
+    pub fn new(word: impl Into<String>, readings: Vec<String>) -> Self {
+        Self {
+            word: word.into(),
+            readings,
+            previous: Neighbor::Any,
+            next: Neighbor::Any,
+        }
+    }
+
+    pub fn previous(mut self, previous: Neighbor) -> Self {
+        self.previous = previous;
+        self
+    }
+
+    pub fn next(mut self, next: Neighbor) -> Self {
+        self.next = next;
+        self
+    }
+
+    /// Checks whether the rule applies to its word between the given characters.
+    pub fn applies(&self, previous: Option<char>, next: Option<char>) -> bool {
+        self.previous.matches(previous) && self.next.matches(next)
+    }
+
+    /// Checks whether both rules can apply to the same word at the same place.
+    pub fn overlaps(&self, other: &ContextRule) -> bool {
+        self.word == other.word
+            && self.previous.overlaps(other.previous)
+            && self.next.overlaps(other.next)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{KanjiAccuracy, MapConfig};
+    use std::collections::HashMap;
+
+    #[test]
+    fn applies_rules_by_neighbors() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("生".to_string(), vec!["せい".to_string()]);
+        kanji_to_readings.insert("卵".to_string(), vec!["たまご".to_string()]);
+        let config = MapConfig::builder()
+            .context_rules(vec![
+                ContextRule::new("生", vec!["なま".to_string()]).next(Neighbor::NotKanji)
+            ])
+            .build();
+
+        let accuracy = |word, reading| {
+            let mappings = crate::map_with(word, reading, &kanji_to_readings, &config);
+            mappings.best().unwrap().furigana[0].kanji_accuracy()
+        };
+        assert_eq!(
+            accuracy("生クリーム", "なまくりーむ"),
+            Some(KanjiAccuracy::Accurate)
+        );
+        assert_eq!(
+            accuracy("生卵", "なまたまご"),
+            Some(KanjiAccuracy::Inaccurate)
+        );
+
+        assert!(Neighbor::Kana.overlaps(Neighbor::NotKanji));
+        assert!(!Neighbor::Kanji.overlaps(Neighbor::Edge));
+        assert!(!Neighbor::Kana.overlaps(Neighbor::Edge));
+    }
+}
@@ -1,40 +1,138 @@
-//! Contains iterators that segment a Japanese word.
+//! Contains iterators that segment a Japanese word or its reading.
 
 use crate::utils;
+use std::{collections::VecDeque, iter::FusedIterator};
 
 /// Segment of a Japanese word.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
 pub enum Segment<'a> {
     Kana(&'a str),
     Kanji(&'a str),
-    Alphanumeric(&'a str),
+    Alphabetic(&'a str),
+    Numeric(&'a str),
     Exception(&'a str),
     Other(&'a str),
 }
 
+/// The kind of a `Segment` without its string, for matching on segments without depending on their payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum SegmentKind {
+    Kana,
+    Kanji,
+    Alphabetic,
+    Numeric,
+    Exception,
+    Other,
+}
+
 impl<'a> Segment<'a> {
     /// Returns the inner string.
     pub fn inner(self) -> &'a str {
         match self {
             Self::Kana(kana) => kana,
             Self::Kanji(kanji) => kanji,
-            Self::Alphanumeric(alpha) => alpha,
+            Self::Alphabetic(alpha) => alpha,
+            Self::Numeric(number) => number,
             Self::Exception(exception) => exception,
             Self::Other(other) => other,
         }
     }
+
+    /// Returns the kind of the segment.
+    pub fn kind(self) -> SegmentKind {
+        match self {
+            Self::Kana(_) => SegmentKind::Kana,
+            Self::Kanji(_) => SegmentKind::Kanji,
+            Self::Alphabetic(_) => SegmentKind::Alphabetic,
+            Self::Numeric(_) => SegmentKind::Numeric,
+            Self::Exception(_) => SegmentKind::Exception,
+            Self::Other(_) => SegmentKind::Other,
+        }
+    }
+
+    /// Checks whether the segment is made of kanji.
+    pub fn is_kanji(self) -> bool {
+        self.kind() == SegmentKind::Kanji
+    }
+
+    /// Checks whether the segment is made of kana.
+    pub fn is_kana(self) -> bool {
+        self.kind() == SegmentKind::Kana
+    }
+
+    /// Returns the length of the inner string in chars.
+    pub fn len_chars(self) -> usize {
+        self.inner().chars().count()
+    }
 }
 
 /// Iterator over a word's sequences of kanji and kana.
 /// Differs from `FineSegmentation` in that sequences of kanji are considered single segments.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CoarseSegmentation<'a> {
-    rest: &'a str,
+    rest: Rest<'a>,
+    options: SegmentationOptions,
+    // the segments of the rest, once they've been needed from the back
+    segmented: Option<VecDeque<Segment<'a>>>,
+}
+
+/// Options for `CoarseSegmentation::with_options` and `FineSegmentation::with_options`.
+/// The mapping functions try the default options and their `alternatives`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SegmentationOptions {
+    /// Groups consecutive Latin letters into a single segment, like the CD in CDプレイヤー.
+    pub group_letters: bool,
+    /// Includes ヶ in the kanji runs around it, like in 一ヶ月, instead of making it a segment of its own.
+    pub ke_in_kanji: bool,
+    /// Treats the marks that can also stand for kanji as kanji: ヶ as in 関ヶ原, 〆 as in 〆切,
+    /// and 々 when it doesn't follow a kanji.
+    pub kanji_marks: bool,
+}
+
+impl SegmentationOptions {
+    /// Returns the options worth trying for the word, starting with these ones.
+    /// Words with marks that can be read as kanji or as marks, like ヶ in 一ヶ月 and 関ヶ原,
+    /// also get the options that treat them as kanji.
+    pub fn alternatives(self, word: &str) -> Vec<Self> {
+        let mut alternatives = vec![self];
+        let mut previous = None;
+        let has_kanji_marks = word.chars().any(|c| {
+            let is_mark = is_kanji_mark(previous, c);
+            previous = Some(c);
+            is_mark
+        });
+        if has_kanji_marks && !self.kanji_marks {
+            alternatives.push(Self {
+                kanji_marks: true,
+                ..self
+            });
+        }
+        alternatives
+    }
 }
 
 impl<'a> CoarseSegmentation<'a> {
     pub fn new(word: &'a str) -> Self {
-        Self { rest: word }
+        Self::with_options(word, SegmentationOptions::default())
+    }
+
+    pub fn with_options(word: &'a str, options: SegmentationOptions) -> Self {
+        let mut rest = Rest::new(word);
+        rest.kanji_marks = options.kanji_marks;
+        Self {
+            rest,
+            options,
+            segmented: None,
+        }
+    }
+
+    /// Classifies ー and 一 by the characters around them, see `MapConfig::dashes_by_context`.
+    /// `before` and `after` are the characters around the word if it's a part of a longer one.
+    pub fn dashes_by_context(mut self, before: Option<char>, after: Option<char>) -> Self {
+        self.rest.context = Some((before, after));
+        self
     }
 }
 
@@ -42,68 +140,83 @@ impl<'a> Iterator for CoarseSegmentation<'a> {
     type Item = Segment<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next = self.rest.chars().next()?;
-        match classify_char(next) {
-            Char::Alphanumeric => {
-                let next_len = next.len_utf8();
-                let next = &self.rest[..next_len];
-                self.rest = &self.rest[next_len..];
-                Some(Segment::Alphanumeric(next))
-            }
-            Char::Exception => {
-                let next_len = next.len_utf8();
-                let next = &self.rest[..next_len];
-                self.rest = &self.rest[next_len..];
-                Some(Segment::Exception(next))
-            }
-            Char::Other => {
-                let next_len = next.len_utf8();
-                let next = &self.rest[..next_len];
-                self.rest = &self.rest[next_len..];
-                Some(Segment::Other(next))
-            }
-            Char::Kanji => {
-                let next = if let Some(idx) = self
-                    .rest
-                    // 々 is special cased so that it can be included in a kanji segment
-                    .find(|c| classify_char(c) != Char::Kanji && c != '々')
-                {
-                    let next = &self.rest[..idx];
-                    self.rest = &self.rest[idx..];
-                    next
-                } else {
-                    let next = self.rest;
-                    self.rest = "";
-                    next
-                };
-                Some(Segment::Kanji(next))
-            }
-            Char::Kana => {
-                let next = if let Some(idx) = self.rest.find(|c| classify_char(c) != Char::Kana) {
-                    let next = &self.rest[..idx];
-                    self.rest = &self.rest[idx..];
-                    next
-                } else {
-                    let next = self.rest;
-                    self.rest = "";
-                    next
-                };
-                Some(Segment::Kana(next))
-            }
+        if let Some(segmented) = &mut self.segmented {
+            return segmented.pop_front();
+        }
+        let rest = &mut self.rest;
+        let ke_in_kanji = self.options.ke_in_kanji;
+        // 々 is special cased so that it can be included in a kanji segment
+        let in_kanji_run =
+            |c: char, class: Char| class == Char::Kanji || c == '々' || (ke_in_kanji && c == 'ヶ');
+        match rest.first_class()? {
+            Char::Alphabetic if self.options.group_letters => Some(Segment::Alphabetic(
+                rest.take_while(|_, class| class == Char::Alphabetic),
+            )),
+            Char::Alphabetic => Some(Segment::Alphabetic(rest.take_char())),
+            Char::Numeric => Some(Segment::Numeric(rest.take_number())),
+            Char::Exception if ke_in_kanji => Some(Segment::Kanji(rest.take_while(in_kanji_run))),
+            Char::Exception => Some(Segment::Exception(rest.take_char())),
+            Char::Other => Some(Segment::Other(rest.take_other())),
+            Char::Kanji => Some(Segment::Kanji(rest.take_while(in_kanji_run))),
+            Char::Kana => Some(Segment::Kana(
+                rest.take_while(|_, class| class == Char::Kana),
+            )),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.segmented {
+            Some(segmented) => (segmented.len(), Some(segmented.len())),
+            None => self.rest.size_hint(),
         }
     }
 }
 
+impl DoubleEndedIterator for CoarseSegmentation<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.segmented.is_none() {
+            // segmenting from the end would need every rule to work in reverse,
+            // so the rest is segmented from the start once, the first time a segment is needed from the back
+            let segments = self.by_ref().collect();
+            self.segmented = Some(segments);
+        }
+        self.segmented.as_mut()?.pop_back()
+    }
+}
+
+impl FusedIterator for CoarseSegmentation<'_> {}
+
 /// Iterator over a word's kanji and sequences of kana.
 /// Differs from `CoarseSegmentation` in that each kanji is its own segment.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct FineSegmentation<'a> {
-    rest: &'a str,
+    rest: Rest<'a>,
+    options: SegmentationOptions,
+    // the segments of the rest, once they've been needed from the back
+    segmented: Option<VecDeque<Segment<'a>>>,
 }
 
 impl<'a> FineSegmentation<'a> {
     pub fn new(word: &'a str) -> Self {
-        Self { rest: word }
+        Self::with_options(word, SegmentationOptions::default())
+    }
+
+    /// Creates a segmentation with the given options, where `ke_in_kanji` makes ヶ a kanji of its own.
+    pub fn with_options(word: &'a str, options: SegmentationOptions) -> Self {
+        let mut rest = Rest::new(word);
+        rest.kanji_marks = options.kanji_marks;
+        Self {
+            rest,
+            options,
+            segmented: None,
+        }
+    }
+
+    /// Classifies ー and 一 by the characters around them, see `MapConfig::dashes_by_context`.
+    /// `before` and `after` are the characters around the word if it's a part of a longer one.
+    pub fn dashes_by_context(mut self, before: Option<char>, after: Option<char>) -> Self {
+        self.rest.context = Some((before, after));
+        self
     }
 }
 
@@ -111,72 +224,280 @@ impl<'a> Iterator for FineSegmentation<'a> {
     type Item = Segment<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next = self.rest.chars().next()?;
-        let next_class = classify_char(next);
-        match next_class {
-            Char::Alphanumeric => {
-                let next_len = next.len_utf8();
-                let next = &self.rest[..next_len];
-                self.rest = &self.rest[next_len..];
-                Some(Segment::Alphanumeric(next))
-            }
-            Char::Kanji => {
-                let idx = next.len_utf8();
-                let next = &self.rest[..idx];
-                self.rest = &self.rest[idx..];
-                Some(Segment::Kanji(next))
-            }
-            Char::Kana => {
-                let next = if let Some(idx) = self.rest.find(utils::is_kanji) {
-                    let next = &self.rest[..idx];
-                    self.rest = &self.rest[idx..];
-                    next
-                } else {
-                    let next = self.rest;
-                    self.rest = "";
-                    next
-                };
-                Some(Segment::Kana(next))
-            }
-            Char::Exception => {
-                let idx = next.len_utf8();
-                let next = &self.rest[..idx];
-                self.rest = &self.rest[idx..];
-                Some(Segment::Exception(next))
-            }
-            Char::Other => {
-                let idx = next.len_utf8();
-                let next = &self.rest[..idx];
-                self.rest = &self.rest[idx..];
-                Some(Segment::Other(next))
+        if let Some(segmented) = &mut self.segmented {
+            return segmented.pop_front();
+        }
+        let rest = &mut self.rest;
+        match rest.first_class()? {
+            Char::Alphabetic if self.options.group_letters => Some(Segment::Alphabetic(
+                rest.take_while(|_, class| class == Char::Alphabetic),
+            )),
+            Char::Alphabetic => Some(Segment::Alphabetic(rest.take_char())),
+            Char::Numeric => Some(Segment::Numeric(rest.take_number())),
+            Char::Kanji => Some(Segment::Kanji(rest.take_char())),
+            Char::Kana => Some(Segment::Kana(
+                rest.take_while(|_, class| class == Char::Kana),
+            )),
+            Char::Exception if self.options.ke_in_kanji => Some(Segment::Kanji(rest.take_char())),
+            Char::Exception => Some(Segment::Exception(rest.take_char())),
+            Char::Other => Some(Segment::Other(rest.take_other())),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.segmented {
+            Some(segmented) => (segmented.len(), Some(segmented.len())),
+            None => self.rest.size_hint(),
+        }
+    }
+}
+
+impl DoubleEndedIterator for FineSegmentation<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        // segmented from the start once like in CoarseSegmentation
+        if self.segmented.is_none() {
+            let segments = self.by_ref().collect();
+            self.segmented = Some(segments);
+        }
+        self.segmented.as_mut()?.pop_back()
+    }
+}
+
+impl FusedIterator for FineSegmentation<'_> {}
+
+/// Iterator over the mora of a reading, such as きょ, う, っ, and か in きょうっか.
+/// Small kana like the ょ in きょ and dakuten written separately are kept with the kana before them,
+/// while っ, ー and ん are mora of their own. Other characters are returned one at a time.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReadingSegmentation<'a> {
+    rest: &'a str,
+}
+
+impl<'a> ReadingSegmentation<'a> {
+    pub fn new(reading: &'a str) -> Self {
+        Self { rest: reading }
+    }
+}
+
+impl<'a> Iterator for ReadingSegmentation<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chars = self.rest.char_indices();
+        let (_, first) = chars.next()?;
+        let len = if utils::is_kana(first) || utils::is_halfwidth_katakana(first) {
+            chars
+                .find(|(_, c)| !is_mora_extension(*c))
+                .map_or(self.rest.len(), |(idx, _)| idx)
+        } else {
+            first.len_utf8()
+        };
+        let (mora, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        Some(mora)
+    }
+
+    // every mora has at least one char, which has at least one byte
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::from(!self.rest.is_empty()), Some(self.rest.len()))
+    }
+}
+
+impl FusedIterator for ReadingSegmentation<'_> {}
+
+// small kana and dakuten that form a single mora with the kana before them
+fn is_mora_extension(c: char) -> bool {
+    "ぁぃぅぇぉゃゅょゎァィゥェォャュョヮｧｨｩｪｫｬｭｮ".contains(c) || utils::is_standalone_dakuten(c)
+}
+
+// the part of the word that hasn't been segmented yet
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Rest<'a> {
+    rest: &'a str,
+    // the char before rest
+    previous: Option<char>,
+    // the chars around the whole word when classifying dashes by context
+    context: Option<(Option<char>, Option<char>)>,
+    // see SegmentationOptions::kanji_marks
+    kanji_marks: bool,
+}
+
+impl<'a> Rest<'a> {
+    fn new(word: &'a str) -> Self {
+        Self {
+            rest: word,
+            previous: None,
+            context: None,
+            kanji_marks: false,
+        }
+    }
+
+    fn class_at(&self, idx: usize, c: char) -> Char {
+        if self.kanji_marks {
+            let previous = if idx == 0 {
+                self.previous
+            } else {
+                self.rest[..idx].chars().last()
+            };
+            if is_kanji_mark(previous, c) {
+                return Char::Kanji;
             }
         }
+        let (before, after) = match self.context {
+            Some(context) => context,
+            None => return classify_char(c),
+        };
+        let previous = if idx == 0 {
+            self.previous.or(before)
+        } else {
+            self.rest[..idx].chars().last()
+        };
+        let next = self.rest[idx + c.len_utf8()..].chars().next().or(after);
+        classify_in_context(previous, c, next)
+    }
+
+    fn first_class(&self) -> Option<Char> {
+        let first = self.rest.chars().next()?;
+        Some(self.class_at(0, first))
+    }
+
+    fn take(&mut self, len: usize) -> &'a str {
+        let (taken, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        if let Some(last) = taken.chars().last() {
+            self.previous = Some(last);
+        }
+        taken
+    }
+
+    // every segment has at least one char, which has at least one byte
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::from(!self.rest.is_empty()), Some(self.rest.len()))
+    }
+
+    fn take_char(&mut self) -> &'a str {
+        let len = self.rest.chars().next().map_or(0, char::len_utf8);
+        self.take(len)
+    }
+
+    fn take_while(&mut self, f: impl Fn(char, Char) -> bool) -> &'a str {
+        let len = self
+            .rest
+            .char_indices()
+            .find(|(idx, c)| !f(*c, self.class_at(*idx, *c)))
+            .map_or(self.rest.len(), |(idx, _)| idx);
+        self.take(len)
+    }
+
+    fn take_number(&mut self) -> &'a str {
+        let len = split_number(self.rest).0.len();
+        self.take(len)
+    }
+
+    fn take_other(&mut self) -> &'a str {
+        let len = split_other(self.rest).0.len();
+        self.take(len)
     }
 }
 
+// splits a number or a single roman numeral off the start of the string
+// ASCII and full-width digits are part of the same run since they can be mixed in a single number,
+// as are magnitudes like 万 that come after digits, as in 3万5000
+fn split_number(s: &str) -> (&str, &str) {
+    if let Some(c) = s.chars().next().filter(|c| utils::is_roman_numeral(*c)) {
+        return s.split_at(c.len_utf8());
+    }
+    let mut end = 0;
+    loop {
+        let digits_end = s[end..]
+            .find(|c| !utils::is_digit(c))
+            .map_or(s.len(), |idx| end + idx);
+        if digits_end == end {
+            break;
+        }
+        end = digits_end;
+        match s[end..].chars().next() {
+            Some(c) if utils::is_magnitude(c) => end += c.len_utf8(),
+            _ => break,
+        }
+    }
+    s.split_at(end)
+}
+
+// splits the character at the start of the string off
+// with the unicode-segmentation feature, the whole grapheme cluster is split off instead
+// so that emoji and combining sequences aren't broken up
+#[cfg(feature = "unicode-segmentation")]
+fn split_other(s: &str) -> (&str, &str) {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    let len = s.graphemes(true).next().map_or(0, str::len);
+    s.split_at(len)
+}
+
+#[cfg(not(feature = "unicode-segmentation"))]
+fn split_other(s: &str) -> (&str, &str) {
+    let len = s.chars().next().map_or(0, char::len_utf8);
+    s.split_at(len)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-enum Char {
+pub(crate) enum Char {
     Kanji,
     Kana,
-    Alphanumeric,
+    Alphabetic,
+    Numeric,
     Exception,
     Other,
 }
 
-fn classify_char(c: char) -> Char {
+// checks whether the char is a mark that can also stand for a kanji, like ヶ for 箇 or 〆 for 締
+// 々 is only one when it doesn't follow a kanji, since it repeats the kanji before it otherwise
+fn is_kanji_mark(previous: Option<char>, c: char) -> bool {
+    match c {
+        'ヶ' | '〆' => true,
+        '々' => !previous.map_or(false, |p| utils::is_kanji(p) || p == '々'),
+        _ => false,
+    }
+}
+
+pub(crate) fn classify_char(c: char) -> Char {
     if c == 'ヶ' {
         Char::Exception
     } else if utils::is_kanji(c) {
         Char::Kanji
-    } else if utils::is_kana(c) {
+    } else if utils::is_kana(c) || utils::is_halfwidth_katakana(c) {
         Char::Kana
-    } else if utils::is_alphanumeric(c) {
-        Char::Alphanumeric
+    } else if utils::is_digit(c) || utils::is_roman_numeral(c) {
+        Char::Numeric
+    } else if utils::is_alphabetic(c) {
+        Char::Alphabetic
     } else {
         Char::Other
     }
 }
 
+/// Classifies the char like `classify_char`, except for ー and 一 which are often swapped in OCR output and sloppy input:
+/// 一 between katakana is taken as ー, as in ラ一メン, and ー next to kanji at both sides or the edge of the word as 一, as in 統ー or ー番.
+pub(crate) fn classify_in_context(previous: Option<char>, c: char, next: Option<char>) -> Char {
+    let is_katakana = |c: Option<char>| {
+        c.map_or(false, |c| {
+            utils::is_katakana(c) || utils::is_halfwidth_katakana(c)
+        })
+    };
+    let is_kanji_or_edge = |c: Option<char>| c.map_or(true, utils::is_kanji);
+    match c {
+        '一' if is_katakana(previous) && (next.is_none() || is_katakana(next)) => Char::Kana,
+        'ー' if (previous.is_some() || next.is_some())
+            && is_kanji_or_edge(previous)
+            && is_kanji_or_edge(next) =>
+        {
+            Char::Kanji
+        }
+        _ => classify_char(c),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -232,12 +553,147 @@ mod test {
     #[test]
     fn segments_mixed() {
         let mut cs = CoarseSegmentation::new("CDプレイヤー");
-        assert_eq!(Segment::Alphanumeric("C"), cs.next().unwrap());
-        assert_eq!(Segment::Alphanumeric("D"), cs.next().unwrap());
+        assert_eq!(Segment::Alphabetic("C"), cs.next().unwrap());
+        assert_eq!(Segment::Alphabetic("D"), cs.next().unwrap());
         assert_eq!(Segment::Kana("プレイヤー"), cs.next().unwrap());
         let mut fs = FineSegmentation::new("CDプレイヤー");
-        assert_eq!(Segment::Alphanumeric("C"), fs.next().unwrap());
-        assert_eq!(Segment::Alphanumeric("D"), fs.next().unwrap());
+        assert_eq!(Segment::Alphabetic("C"), fs.next().unwrap());
+        assert_eq!(Segment::Alphabetic("D"), fs.next().unwrap());
         assert_eq!(Segment::Kana("プレイヤー"), fs.next().unwrap());
     }
+
+    #[test]
+    fn segments_with_options() {
+        let options = SegmentationOptions {
+            group_letters: true,
+            ke_in_kanji: true,
+            kanji_marks: false,
+        };
+        let segments = CoarseSegmentation::with_options("CDを一ヶ月", options).collect::<Vec<_>>();
+        assert_eq!(
+            segments,
+            [
+                Segment::Alphabetic("CD"),
+                Segment::Kana("を"),
+                Segment::Kanji("一ヶ月")
+            ]
+        );
+        let segments = CoarseSegmentation::new("一ヶ月").collect::<Vec<_>>();
+        assert_eq!(segments[1], Segment::Exception("ヶ"));
+    }
+
+    #[test]
+    fn segments_alternatives() {
+        let options = SegmentationOptions::default();
+        assert_eq!(options.alternatives("日々"), [options]);
+        let alternatives = options.alternatives("〆切");
+        assert_eq!(alternatives.len(), 2);
+
+        let segments = FineSegmentation::with_options("〆切", alternatives[1]).collect::<Vec<_>>();
+        assert_eq!(segments, [Segment::Kanji("〆"), Segment::Kanji("切")]);
+        let segments = FineSegmentation::new("〆切").collect::<Vec<_>>();
+        assert_eq!(segments[0], Segment::Other("〆"));
+        let segments =
+            CoarseSegmentation::with_options("の々", alternatives[1]).collect::<Vec<_>>();
+        assert_eq!(segments, [Segment::Kana("の"), Segment::Kanji("々")]);
+    }
+
+    #[test]
+    fn segments_from_the_end() {
+        for word in [
+            "物の怪",
+            "3万5000円を払った",
+            "ラ一メン一杯",
+            "CDプレイヤー",
+        ] {
+            let forward = FineSegmentation::new(word)
+                .dashes_by_context(None, None)
+                .collect::<Vec<_>>();
+            let mut backward = FineSegmentation::new(word)
+                .dashes_by_context(None, None)
+                .rev()
+                .collect::<Vec<_>>();
+            backward.reverse();
+            assert_eq!(forward, backward, "{word}");
+        }
+        let mut cs = CoarseSegmentation::new("物の怪");
+        assert_eq!(cs.size_hint(), (1, Some(9)));
+        assert_eq!(cs.next_back(), Some(Segment::Kanji("怪")));
+        // the rest is known exactly once it's been segmented for next_back
+        assert_eq!(cs.size_hint(), (2, Some(2)));
+        assert_eq!(cs.next(), Some(Segment::Kanji("物")));
+        assert_eq!(cs.next_back(), Some(Segment::Kana("の")));
+        assert_eq!(cs.next_back(), None);
+        assert_eq!(cs.size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn segments_readings_into_mora() {
+        let mora = ReadingSegmentation::new("きょうっかシェーんｷﾞｮ?").collect::<Vec<_>>();
+        assert_eq!(
+            mora,
+            ["きょ", "う", "っ", "か", "シェ", "ー", "ん", "ｷﾞｮ", "?"]
+        );
+        assert_eq!(ReadingSegmentation::new("").next(), None);
+    }
+
+    #[test]
+    fn describes_segments() {
+        let segments = FineSegmentation::new("物の怪A").collect::<Vec<_>>();
+        assert!(segments[0].is_kanji());
+        assert!(segments[1].is_kana());
+        assert_eq!(segments[3].kind(), SegmentKind::Alphabetic);
+        assert_eq!(Segment::Kana("もの").len_chars(), 2);
+    }
+
+    #[test]
+    fn segments_dashes_by_context() {
+        let mut fs = FineSegmentation::new("ラ一メン一杯").dashes_by_context(None, None);
+        assert_eq!(Segment::Kana("ラ一メン"), fs.next().unwrap());
+        assert_eq!(Segment::Kanji("一"), fs.next().unwrap());
+        assert_eq!(Segment::Kanji("杯"), fs.next().unwrap());
+        let mut cs = CoarseSegmentation::new("統ー戦").dashes_by_context(Some('の'), None);
+        assert_eq!(Segment::Kanji("統ー戦"), cs.next().unwrap());
+        // kana after the ー make it a long vowel mark
+        let mut cs = CoarseSegmentation::new("凄ーい").dashes_by_context(None, None);
+        assert_eq!(Segment::Kanji("凄"), cs.next().unwrap());
+        assert_eq!(Segment::Kana("ーい"), cs.next().unwrap());
+        let mut cs = CoarseSegmentation::new("ー").dashes_by_context(Some('ラ'), None);
+        assert_eq!(Segment::Kana("ー"), cs.next().unwrap());
+    }
+
+    #[test]
+    fn segments_numbers() {
+        let mut cs = CoarseSegmentation::new("１2０円");
+        assert_eq!(Segment::Numeric("１2０"), cs.next().unwrap());
+        assert_eq!(Segment::Kanji("円"), cs.next().unwrap());
+        assert!(cs.next().is_none());
+        let mut fs = FineSegmentation::new("第１2０回");
+        assert_eq!(Segment::Kanji("第"), fs.next().unwrap());
+        assert_eq!(Segment::Numeric("１2０"), fs.next().unwrap());
+        assert_eq!(Segment::Kanji("回"), fs.next().unwrap());
+        assert!(fs.next().is_none());
+        let mut cs = CoarseSegmentation::new("3万5000円");
+        assert_eq!(Segment::Numeric("3万5000"), cs.next().unwrap());
+        assert_eq!(Segment::Kanji("円"), cs.next().unwrap());
+        assert!(cs.next().is_none());
+        let mut fs = FineSegmentation::new("1億2000万人");
+        assert_eq!(Segment::Numeric("1億2000万"), fs.next().unwrap());
+        assert_eq!(Segment::Kanji("人"), fs.next().unwrap());
+        assert!(fs.next().is_none());
+        let mut fs = FineSegmentation::new("ⅢⅣ世");
+        assert_eq!(Segment::Numeric("Ⅲ"), fs.next().unwrap());
+        assert_eq!(Segment::Numeric("Ⅳ"), fs.next().unwrap());
+        assert_eq!(Segment::Kanji("世"), fs.next().unwrap());
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn segments_grapheme_clusters() {
+        let mut fs = FineSegmentation::new("👍🏽物🇯🇵");
+        assert_eq!(Segment::Other("👍🏽"), fs.next().unwrap());
+        assert_eq!(Segment::Kanji("物"), fs.next().unwrap());
+        assert_eq!(Segment::Other("🇯🇵"), fs.next().unwrap());
+        assert!(fs.next().is_none());
+    }
 }
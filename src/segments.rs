@@ -0,0 +1,193 @@
+//! Contains the storage for the segments of a `Furigana`.
+
+use crate::furigana::FuriganaSegment;
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
+    ops::{Deref, DerefMut},
+};
+
+// most words have at most this many segments
+const INLINE_LEN: usize = 4;
+
+const EMPTY: FuriganaSegment<'static> = FuriganaSegment {
+    segment: "",
+    furigana: None,
+    reading: "",
+    kanji_accuracy: None,
+    source: None,
+};
+
+/// The segments of a mapping, stored inline when there are only a few of them
+/// so that flattening a tree of short words doesn't allocate for each mapping.
+/// Behaves like a `Vec` of the segments.
+#[derive(Clone)]
+pub(crate) struct Segments<'a> {
+    // the number of segments in inline, unused once they've moved to heap
+    len: usize,
+    inline: [FuriganaSegment<'a>; INLINE_LEN],
+    // empty until there are more segments than fit inline, after which it holds all of them
+    heap: Vec<FuriganaSegment<'a>>,
+}
+
+impl<'a> Segments<'a> {
+    pub(crate) fn push(&mut self, segment: FuriganaSegment<'a>) {
+        let len = self.len();
+        self.insert(len, segment);
+    }
+
+    /// Panics if the index is greater than the length.
+    pub(crate) fn insert(&mut self, index: usize, segment: FuriganaSegment<'a>) {
+        if !self.heap.is_empty() {
+            self.heap.insert(index, segment);
+        } else if self.len < INLINE_LEN {
+            assert!(index <= self.len, "insertion index out of bounds");
+            self.inline.copy_within(index..self.len, index + 1);
+            self.inline[index] = segment;
+            self.len += 1;
+        } else {
+            let mut heap = Vec::with_capacity(INLINE_LEN * 2);
+            heap.extend_from_slice(&self.inline);
+            heap.insert(index, segment);
+            self.heap = heap;
+        }
+    }
+
+    pub(crate) fn into_vec(self) -> Vec<FuriganaSegment<'a>> {
+        if self.heap.is_empty() {
+            self.inline[..self.len].to_vec()
+        } else {
+            self.heap
+        }
+    }
+}
+
+impl<'a> From<&[FuriganaSegment<'a>]> for Segments<'a> {
+    fn from(slice: &[FuriganaSegment<'a>]) -> Self {
+        if slice.len() > INLINE_LEN {
+            return Self::from(slice.to_vec());
+        }
+        let mut segments = Self::default();
+        segments.inline[..slice.len()].copy_from_slice(slice);
+        segments.len = slice.len();
+        segments
+    }
+}
+
+impl<'a> From<Vec<FuriganaSegment<'a>>> for Segments<'a> {
+    fn from(vec: Vec<FuriganaSegment<'a>>) -> Self {
+        if vec.len() <= INLINE_LEN {
+            return Self::from(vec.as_slice());
+        }
+        Self {
+            heap: vec,
+            ..Self::default()
+        }
+    }
+}
+
+impl<'a> FromIterator<FuriganaSegment<'a>> for Segments<'a> {
+    fn from_iter<I: IntoIterator<Item = FuriganaSegment<'a>>>(iter: I) -> Self {
+        let mut segments = Self::default();
+        for segment in iter {
+            segments.push(segment);
+        }
+        segments
+    }
+}
+
+impl Default for Segments<'_> {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            inline: [EMPTY; INLINE_LEN],
+            heap: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Deref for Segments<'a> {
+    type Target = [FuriganaSegment<'a>];
+
+    fn deref(&self) -> &Self::Target {
+        if self.heap.is_empty() {
+            &self.inline[..self.len]
+        } else {
+            &self.heap
+        }
+    }
+}
+
+impl<'a> DerefMut for Segments<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        if self.heap.is_empty() {
+            &mut self.inline[..self.len]
+        } else {
+            &mut self.heap
+        }
+    }
+}
+
+impl<'s, 'a> IntoIterator for &'s Segments<'a> {
+    type Item = &'s FuriganaSegment<'a>;
+    type IntoIter = std::slice::Iter<'s, FuriganaSegment<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// the rest compare and hash the segments like a Vec would, regardless of where they're stored
+
+impl Debug for Segments<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl PartialEq for Segments<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Segments<'_> {}
+
+impl PartialOrd for Segments<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Segments<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl Hash for Segments<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spills_to_the_heap() {
+        let segment = |reading| FuriganaSegment::new("", None, reading, None);
+        let mut segments = Segments::from(vec![segment("b"), segment("d")]);
+        segments.insert(0, segment("a"));
+        segments.insert(2, segment("c"));
+        assert!(segments.heap.is_empty());
+        segments.push(segment("e"));
+        assert_eq!(segments.heap.len(), 5);
+
+        let readings = segments.iter().map(|s| s.reading).collect::<String>();
+        assert_eq!(readings, "abcde");
+        assert_eq!(segments.into_vec().len(), 5);
+    }
+}
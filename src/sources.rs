@@ -0,0 +1,25 @@
+//! Contains the sources of kanji readings used to grade mappings.
+
+use std::collections::HashMap;
+
+/// A named set of kanji readings, such as KANJIDIC or a project's own overrides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadingSource<'d> {
+    /// The name of the source, for telling the sources apart when auditing the results.
+    pub name: &'d str,
+    /// Readings of kanji and words made up of multiple kanji, like the parameter of `map`.
+    pub kanji_to_readings: &'d HashMap<String, Vec<String>>,
+}
+
+impl<'d> ReadingSource<'d> {
+    pub fn new(name: &'d str, kanji_to_readings: &'d HashMap<String, Vec<String>>) -> Self {
+        Self {
+            name,
+            kanji_to_readings,
+        }
+    }
+
+    pub(crate) fn get(&self, kanji: &str) -> Option<&'d [String]> {
+        self.kanji_to_readings.get(kanji).map(Vec::as_slice)
+    }
+}
@@ -0,0 +1,147 @@
+//! Contains a generator of realistic (word, reading) pairs for benchmarks and fuzzing.
+
+use crate::utils;
+use std::{collections::HashMap, ops::RangeInclusive};
+
+// kana placed between the kanji of the generated words, like the の in 物の怪
+const PARTICLES: &[&str] = &["の", "が", "を", "に", "と", "か", "な", "る", "い", "す"];
+
+/// Generates random (word, reading) pairs that can be mapped, built from the kanji and readings of a dictionary.
+/// The same seed and settings always generate the same pairs.
+#[derive(Debug, Clone)]
+pub struct PairGenerator<'d> {
+    kanji: Vec<(&'d str, Vec<String>)>,
+    state: u64,
+    length: RangeInclusive<usize>,
+    ambiguity: u8,
+}
+
+impl<'d> PairGenerator<'d> {
+    /// Creates a generator that uses the single kanji entries of the dictionary.
+    /// Readings in the KANJIDIC format, such as た.べる or -か, are reduced to the part that belongs to the kanji.
+    pub fn new(kanji_to_readings: &'d HashMap<String, Vec<String>>, seed: u64) -> Self {
+        let mut kanji = kanji_to_readings
+            .iter()
+            .filter(|(kanji, _)| {
+                let mut chars = kanji.chars();
+                chars.next().map_or(false, utils::is_kanji) && chars.next().is_none()
+            })
+            .map(|(kanji, readings)| {
+                let readings = readings
+                    .iter()
+                    .filter_map(|reading| utils::normalize_reading(reading))
+                    .collect::<Vec<_>>();
+                (kanji.as_str(), readings)
+            })
+            .filter(|(_, readings)| !readings.is_empty())
+            .collect::<Vec<_>>();
+        // the order of a HashMap is random, which would make the output differ between runs
+        kanji.sort();
+
+        Self {
+            kanji,
+            // xorshift gets stuck on 0
+            state: seed ^ 0x9E37_79B9_7F4A_7C15,
+            length: 1..=4,
+            ambiguity: 50,
+        }
+    }
+
+    /// Sets the range of the number of kanji in the generated words. 1..=4 by default.
+    pub fn length(mut self, length: RangeInclusive<usize>) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Sets the chance in percent that a kanji is followed directly by another kanji rather than by kana.
+    /// Runs of kanji without kana between them can be split in more ways, so a higher chance results in more mappings per pair.
+    /// 50 by default.
+    pub fn ambiguity(mut self, ambiguity: u8) -> Self {
+        self.ambiguity = ambiguity.min(100);
+        self
+    }
+
+    /// Generates a pair, or returns None if the dictionary has no usable kanji.
+    pub fn generate(&mut self) -> Option<(String, String)> {
+        if self.kanji.is_empty() {
+            return None;
+        }
+
+        let (min, max) = (*self.length.start(), *self.length.end());
+        let kanji_count = min + self.below(max.saturating_sub(min) + 1);
+        let mut word = String::new();
+        let mut reading = String::new();
+        for idx in 0..kanji_count {
+            if idx > 0 && self.below(100) >= self.ambiguity as usize {
+                let particle = PARTICLES[self.below(PARTICLES.len())];
+                word.push_str(particle);
+                reading.push_str(particle);
+            }
+            let kanji_idx = self.below(self.kanji.len());
+            let reading_idx = self.below(self.kanji[kanji_idx].1.len());
+            let (kanji, readings) = &self.kanji[kanji_idx];
+            word.push_str(kanji);
+            reading.push_str(&readings[reading_idx]);
+        }
+        Some((word, reading))
+    }
+
+    // a random number in 0..bound, bound must not be 0
+    fn below(&mut self, bound: usize) -> usize {
+        // xorshift64*
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let random = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (random % bound as u64) as usize
+    }
+}
+
+impl Iterator for PairGenerator<'_> {
+    type Item = (String, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.generate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generates_mappable_pairs() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert(
+            "物".to_string(),
+            vec!["モツ".to_string(), "もの".to_string()],
+        );
+        kanji_to_readings.insert("食".to_string(), vec!["た.べる".to_string()]);
+        kanji_to_readings.insert("怪".to_string(), vec!["け".to_string(), "-x".to_string()]);
+        kanji_to_readings.insert("大人".to_string(), vec!["おとな".to_string()]);
+
+        let pairs = PairGenerator::new(&kanji_to_readings, 1)
+            .length(2..=3)
+            .ambiguity(30)
+            .take(50)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            PairGenerator::new(&kanji_to_readings, 1)
+                .length(2..=3)
+                .ambiguity(30)
+                .take(50)
+                .collect::<Vec<_>>()
+        );
+        for (word, reading) in &pairs {
+            let kanji_count = word.chars().filter(|c| utils::is_kanji(*c)).count();
+            assert!((2..=3).contains(&kanji_count));
+            assert!(!word.contains("大人"));
+            assert!(!reading.contains("モツ"));
+            assert!(crate::is_mappable(word, reading), "{word} {reading}");
+            for mapping in &crate::map(word, reading, &kanji_to_readings) {
+                assert_eq!(crate::check_invariants(mapping, word, reading), Ok(()));
+            }
+        }
+    }
+}
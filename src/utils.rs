@@ -1,15 +1,34 @@
-pub fn is_alphanumeric(c: char) -> bool {
-    c.is_ascii_alphanumeric() || is_fullwidth(c) || is_halfwidth(c)
+use std::ops::Range;
+
+pub fn is_alphabetic(c: char) -> bool {
+    c.is_ascii_alphabetic() || is_fullwidth(c) || is_halfwidth(c)
+}
+
+pub fn is_digit(c: char) -> bool {
+    c.is_ascii_digit() || ('０'..='９').contains(&c)
+}
+
+pub fn is_magnitude(c: char) -> bool {
+    matches!(c, '万' | '億' | '兆' | '京')
+}
+
+pub fn is_roman_numeral(c: char) -> bool {
+    (0x2160..=0x216B).contains(&(c as u32)) || (0x2170..=0x217B).contains(&(c as u32))
 }
 
 pub fn is_fullwidth(c: char) -> bool {
-    ('０'..='９').contains(&c) || ('Ａ'..='Ｚ').contains(&c) || ('ａ'..='ｚ').contains(&c)
+    ('Ａ'..='Ｚ').contains(&c) || ('ａ'..='ｚ').contains(&c)
 }
 
 pub fn is_halfwidth(c: char) -> bool {
     (0xFF61..=0xFF9F).contains(&(c as u32))
 }
 
+/// Checks whether the char is half-width katakana like ｶ, including ｰ and the half-width dakuten ﾞ and handakuten ﾟ.
+pub fn is_halfwidth_katakana(c: char) -> bool {
+    (0xFF66..=0xFF9F).contains(&(c as u32))
+}
+
 pub fn is_hiragana(c: char) -> bool {
     (0x3040..=0x309F).contains(&(c as u32))
 }
@@ -25,3 +44,129 @@ pub fn is_kana(c: char) -> bool {
 pub fn is_kanji(c: char) -> bool {
     (0x4E00..=0x9FFF).contains(&(c as u32))
 }
+
+/// Returns the kana without its dakuten or handakuten, or the char itself if it has neither.
+pub fn unvoiced(c: char) -> char {
+    const VOICED: &str = "がぎぐげござじずぜぞだぢづでどばびぶべぼぱぴぷぺぽゔガギグゲゴザジズゼゾダヂヅデドバビブベボパピプペポヴ";
+    const UNVOICED: &str = "かきくけこさしすせそたちつてとはひふへほはひふへほうカキクケコサシスセソタチツテトハヒフヘホハヒフヘホウ";
+    VOICED
+        .chars()
+        .position(|voiced| voiced == c)
+        .and_then(|idx| UNVOICED.chars().nth(idx))
+        .unwrap_or(c)
+}
+
+/// Returns the kana with a dakuten added, like が for か, or None if it can't have one.
+pub fn voiced(c: char) -> Option<char> {
+    const UNVOICED: &str =
+        "かきくけこさしすせそたちつてとはひふへほカキクケコサシスセソタチツテトハヒフヘホ";
+    const VOICED: &str =
+        "がぎぐげござじずぜぞだぢづでどばびぶべぼガギグゲゴザジズゼゾダヂヅデドバビブベボ";
+    UNVOICED
+        .chars()
+        .position(|unvoiced| unvoiced == c)
+        .and_then(|idx| VOICED.chars().nth(idx))
+}
+
+/// Checks whether the char is a dakuten or handakuten that isn't part of a kana, like the ゛ in か゛, including the combining ones.
+pub fn is_standalone_dakuten(c: char) -> bool {
+    matches!(
+        c,
+        '゛' | '゜' | '\u{3099}' | '\u{309A}' | '\u{FF9E}' | '\u{FF9F}'
+    )
+}
+
+/// Checks whether the char is a middle dot like the ・ in シー・ディー, including the half-width one.
+pub fn is_middle_dot(c: char) -> bool {
+    matches!(c, '・' | '･')
+}
+
+/// Combines the standalone dakuten and handakuten in the text with the kana before them, turning か゛ into が and は゜ into ぱ.
+/// Marks that can't be combined with the char before them are kept as they are.
+pub fn combine_dakuten(text: &str) -> String {
+    const UNVOICED: &str = "はひふへほハヒフヘホ";
+    const SEMIVOICED: &str = "ぱぴぷぺぽパピプペポ";
+    let mut combined = String::with_capacity(text.len());
+    for c in text.chars() {
+        let previous = combined.chars().last();
+        let replacement = match (previous, c) {
+            (Some('う'), '゛' | '\u{3099}' | 'ﾞ') => Some('ゔ'),
+            (Some('ウ'), '゛' | '\u{3099}' | 'ﾞ') => Some('ヴ'),
+            (Some(previous), '゛' | '\u{3099}' | 'ﾞ') => voiced(previous),
+            (Some(previous), '゜' | '\u{309A}' | 'ﾟ') => UNVOICED
+                .chars()
+                .position(|unvoiced| unvoiced == previous)
+                .and_then(|idx| SEMIVOICED.chars().nth(idx)),
+            _ => None,
+        };
+        match (previous, replacement) {
+            (Some(previous), Some(replacement)) => {
+                combined.truncate(combined.len() - previous.len_utf8());
+                combined.push(replacement);
+            }
+            _ => combined.push(c),
+        }
+    }
+    combined
+}
+
+/// Turns the half-width katakana in the text into full-width katakana, combining ﾞ and ﾟ with the kana before them,
+/// so that ｶﾞｯｺｳ becomes ガッコウ. Standalone dakuten that were already in the text are combined as well.
+pub fn widen_katakana(text: &str) -> String {
+    const HALFWIDTH: &str = "ｦｧｨｩｪｫｬｭｮｯｰｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜﾝ";
+    const FULLWIDTH: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+    let widened = text
+        .chars()
+        .map(|c| {
+            HALFWIDTH
+                .chars()
+                .position(|halfwidth| halfwidth == c)
+                .and_then(|idx| FULLWIDTH.chars().nth(idx))
+                .unwrap_or(c)
+        })
+        .collect::<String>();
+    combine_dakuten(&widened)
+}
+
+/// Turns a reading in the KANJIDIC format into the hiragana that belongs to the kanji, such as た for た.べる and か for -か.
+/// Returns None for readings that aren't kana.
+pub fn normalize_reading(reading: &str) -> Option<String> {
+    let reading = reading.split('.').next()?.trim_matches('-');
+    if reading.is_empty() || !reading.chars().all(is_kana) {
+        return None;
+    }
+    Some(reading.chars().map(crate::to_hiragana).collect())
+}
+
+/// Checks whether the kana is voiced with a dakuten, like が, as opposed to unvoiced or semi-voiced like か or ぱ.
+pub fn is_voiced_obstruent(c: char) -> bool {
+    "がぎぐげござじずぜぞだぢづでどばびぶべぼゔガギグゲゴザジズゼゾダヂヅデドバビブベボヴ"
+        .contains(c)
+}
+
+/// Returns the byte range that the part takes up in the whole string, or None if the part doesn't point into it.
+/// Unlike searching for the part, finds the right one of several equal parts.
+pub fn subslice_range(whole: &str, part: &str) -> Option<Range<usize>> {
+    let start = (part.as_ptr() as usize).checked_sub(whole.as_ptr() as usize)?;
+    let end = start + part.len();
+    if end <= whole.len() {
+        Some(start..end)
+    } else {
+        None
+    }
+}
+
+/// Splits the string after the given number of chars, or returns None if it doesn't have that many.
+pub fn split_at_char(s: &str, chars: usize) -> Option<(&str, &str)> {
+    if chars == 0 {
+        return Some(s.split_at(0));
+    }
+    let (idx, c) = s.char_indices().nth(chars - 1)?;
+    Some(s.split_at(idx + c.len_utf8()))
+}
+
+/// Splits off as many chars from the start of the string as there are in the prefix, or returns None if it doesn't have that many.
+/// Unlike slicing by the length of the prefix in bytes, never panics or cuts chars in half when the strings use chars of different lengths.
+pub fn split_like<'a>(s: &'a str, prefix: &str) -> Option<(&'a str, &'a str)> {
+    split_at_char(s, prefix.chars().count())
+}
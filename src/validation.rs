@@ -0,0 +1,186 @@
+//! Contains checks that explain why a hand-written mapping isn't valid.
+
+use crate::{
+    check_kanji_accuracy, irregular, kana_equivalent, utils, CandidateFlags, KanjiAccuracy,
+};
+use std::{collections::HashMap, fmt::Display};
+
+/// A reason why a proposed mapping of a reading to a word isn't valid.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Violation {
+    /// The segments don't add up to the word.
+    Text { segments: String },
+    /// The segment at the index contains characters that need a reading, but it has none.
+    MissingReading { index: usize },
+    /// The segment at the index is kana but its reading isn't the same kana.
+    Kana { index: usize },
+    /// The reading of the segment at the index doesn't match the reading of the word at that point.
+    /// The readings of the later segments aren't checked against the reading of the word.
+    ReadingMismatch { index: usize },
+    /// The readings of the segments only cover the start of the reading of the word, leaving the rest.
+    ReadingLeftover { leftover: String },
+    /// The reading of the kanji segment at the index isn't one of its readings in the dictionary,
+    /// even when allowing for rendaku and sokuonbin.
+    DictionaryMiss { index: usize },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text { segments } => write!(f, "the segments add up to {segments}"),
+            Self::MissingReading { index } => write!(f, "segment {index} needs a reading"),
+            Self::Kana { index } => write!(f, "segment {index} is kana with a different reading"),
+            Self::ReadingMismatch { index } => {
+                write!(
+                    f,
+                    "the reading of segment {index} doesn't match the word's reading"
+                )
+            }
+            Self::ReadingLeftover { leftover } => {
+                write!(f, "the segments leave {leftover} of the reading unused")
+            }
+            Self::DictionaryMiss { index } => {
+                write!(f, "the reading of segment {index} isn't in the dictionary")
+            }
+        }
+    }
+}
+
+/// Checks a hand-written mapping of the reading to the word, returning every reason why it isn't valid,
+/// or nothing if it is. The mapping is given as (segment, reading) pairs, where segments without a reading are read as written.
+/// Kanji segments are checked against the dictionary and the built-in irregular readings.
+pub fn why_not(
+    word: &str,
+    reading: &str,
+    proposed: &[(&str, Option<&str>)],
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+) -> Vec<Violation> {
+    let mut violations = vec![];
+    let segments = proposed
+        .iter()
+        .map(|(segment, _)| *segment)
+        .collect::<String>();
+    if segments != word {
+        violations.push(Violation::Text { segments });
+    }
+
+    // the part of the reading that the remaining segments should cover, or None after a mismatch
+    let mut rest = Some(reading);
+    for (index, (segment, segment_reading)) in proposed.iter().enumerate() {
+        let is_kana = segment.chars().all(utils::is_kana);
+        let segment_reading = match segment_reading {
+            Some(segment_reading) => *segment_reading,
+            None if is_kana => segment,
+            None => {
+                violations.push(Violation::MissingReading { index });
+                rest = None;
+                continue;
+            }
+        };
+        if is_kana && !kana_equivalent(segment, segment_reading) {
+            violations.push(Violation::Kana { index });
+        }
+        if !segment.is_empty()
+            && segment.chars().all(utils::is_kanji)
+            && !in_dictionary(
+                segment,
+                segment_reading,
+                index,
+                proposed.len(),
+                kanji_to_readings,
+            )
+        {
+            violations.push(Violation::DictionaryMiss { index });
+        }
+
+        if let Some(remaining) = rest {
+            rest = utils::split_like(remaining, segment_reading)
+                .filter(|(start, _)| kana_equivalent(start, segment_reading))
+                .map(|(_, remaining)| remaining);
+            if rest.is_none() {
+                violations.push(Violation::ReadingMismatch { index });
+            }
+        }
+    }
+    if let Some(leftover) = rest.filter(|leftover| !leftover.is_empty()) {
+        violations.push(Violation::ReadingLeftover {
+            leftover: leftover.to_string(),
+        });
+    }
+    violations
+}
+
+fn in_dictionary(
+    segment: &str,
+    reading: &str,
+    index: usize,
+    segment_count: usize,
+    kanji_to_readings: &HashMap<String, Vec<String>>,
+) -> bool {
+    if irregular::readings(segment).map_or(false, |readings| {
+        readings.iter().any(|r| kana_equivalent(r, reading))
+    }) {
+        return true;
+    }
+    let flags = CandidateFlags {
+        can_be_rendaku: index > 0,
+        can_be_sokuonbin: index + 1 < segment_count,
+        ..CandidateFlags::default()
+    };
+    let kanji_readings = kanji_to_readings.get(segment).map(Vec::as_slice);
+    !matches!(
+        check_kanji_accuracy(kanji_readings, reading, flags),
+        None | Some(KanjiAccuracy::Inaccurate)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn explains_violations() {
+        let mut kanji_to_readings = HashMap::new();
+        kanji_to_readings.insert("物".to_string(), vec!["もの".to_string()]);
+        kanji_to_readings.insert("怪".to_string(), vec!["け".to_string()]);
+        let word = "物の怪";
+        let reading = "もののけ";
+        let valid = [("物", Some("もの")), ("の", None), ("怪", Some("け"))];
+        assert!(why_not(word, reading, &valid, &kanji_to_readings).is_empty());
+
+        let proposed = [("物", Some("も")), ("の", Some("の")), ("怪", Some("け"))];
+        assert_eq!(
+            why_not(word, reading, &proposed, &kanji_to_readings),
+            vec![
+                Violation::DictionaryMiss { index: 0 },
+                Violation::ReadingMismatch { index: 2 },
+            ]
+        );
+
+        let proposed = [("物", Some("もの")), ("の", Some("が"))];
+        assert_eq!(
+            why_not(word, reading, &proposed, &kanji_to_readings),
+            vec![
+                Violation::Text {
+                    segments: "物の".to_string()
+                },
+                Violation::Kana { index: 1 },
+                Violation::ReadingMismatch { index: 1 },
+            ]
+        );
+
+        let proposed = [("物", Some("もの")), ("の怪", None)];
+        assert_eq!(
+            why_not(word, reading, &proposed, &kanji_to_readings),
+            vec![Violation::MissingReading { index: 1 }]
+        );
+        let proposed = [("物", Some("もの")), ("の", None)];
+        assert_eq!(
+            why_not("物の", reading, &proposed, &kanji_to_readings),
+            vec![Violation::ReadingLeftover {
+                leftover: "け".to_string()
+            }]
+        );
+    }
+}